@@ -0,0 +1,162 @@
+//! Validation of detached CAdES signatures (ETSI EN 319 122-1) with trust
+//! anchors from a trusted list.
+//!
+//! Every signer info must carry signed attributes with the message digest
+//! of the data and an ESS signing certificate attribute. The signer's
+//! certification path is validated at the claimed signing time, and the
+//! certificate's qualification is determined as by `qualify`, together with
+//! the status of its CA/QC service at signing time.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::chain::{self, PathOptions, ValidPath};
+use crate::cms::{self, SignedData};
+use crate::error::CaExtractorError;
+use crate::etsi;
+use crate::extractor::ExtractedCertificate;
+use crate::model::TrustedList;
+use crate::qualify::{self, QualificationResult};
+use crate::x509::Certificate;
+
+/// Result of validating one signer of a CMS or JAdES signature
+#[derive(Debug, Clone)]
+pub struct SignerValidation<'a> {
+    pub certificate: Certificate,
+    /// The `signing-time` attribute, `None` when absent
+    pub signing_time: Option<DateTime<Utc>>,
+    /// Time the path and the service status were evaluated at: the signing
+    /// time, else the validation time
+    pub validation_time: DateTime<Utc>,
+    pub path: ValidPath<'a>,
+    /// Qualification of the certificate, determined at its issuance
+    pub qualification: QualificationResult<'a>,
+    /// Status of the certificate's CA/QC service at `validation_time`
+    pub service_status: Option<String>,
+}
+
+impl SignerValidation<'_> {
+    /// Whether the certificate is qualified and its CA/QC service was still
+    /// granted at signing time
+    pub fn qualified_at_signing_time(&self) -> bool {
+        self.qualification.qualified
+            && self
+                .service_status
+                .as_deref()
+                .is_some_and(|status| etsi::GRANTED_STATUSES.contains(&status))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "signer": self.certificate.subject,
+            "signing_time": self.signing_time.map(|time| time.to_rfc3339()),
+            "validation_time": self.validation_time.to_rfc3339(),
+            "path": self.path.to_json(),
+            "qualification": self.qualification.to_json(),
+            "service_status_at_signing_time": self.service_status,
+            "qualified_at_signing_time": self.qualified_at_signing_time(),
+        })
+    }
+}
+
+/// Validates a detached CMS signature over `data`. `certificates`
+/// complement those embedded in the signature; `anchors_at` selects the
+/// trusted list certificates the signers must chain to by the status of
+/// their services at a signer's signing time. Without a signing-time
+/// attribute, signers are validated at `time`.
+pub fn validate<'a>(
+    signature: &[u8],
+    data: &[u8],
+    certificates: &[Certificate],
+    anchors_at: impl Fn(DateTime<Utc>) -> Vec<ExtractedCertificate<'a>>,
+    list: &'a TrustedList,
+    time: DateTime<Utc>,
+) -> Result<Vec<SignerValidation<'a>>, CaExtractorError> {
+    let signed_data = SignedData::parse(signature)?;
+    if let Some(content) = &signed_data.content {
+        if content.as_slice() != data {
+            return Err(invalid("the signature encapsulates content other than the data".to_string()));
+        }
+    }
+    if signed_data.signers.is_empty() {
+        return Err(invalid("the signature has no signer".to_string()));
+    }
+
+    let mut intermediates = signed_data.certificates.clone();
+    intermediates.extend_from_slice(certificates);
+
+    let mut validations = Vec::new();
+    for signer in &signed_data.signers {
+        let certificate = signed_data
+            .signer_certificate(signer, certificates)
+            .ok_or_else(|| invalid("a signer certificate is neither embedded nor given with --chain".to_string()))?;
+        if !signer.check_signing_certificate(&certificate)? {
+            return Err(invalid(format!(
+                "the signer info of '{}' has no signing certificate attribute, required by CAdES",
+                certificate.subject
+            )));
+        }
+        if signer.signed_attribute(cms::OID_MESSAGE_DIGEST).is_none() {
+            return Err(invalid(format!("the signer info of '{}' has no signed attributes", certificate.subject)));
+        }
+        signer.verify(data, &signed_data.content_type, &certificate)?;
+        let anchors = anchors_at(signer.signing_time().unwrap_or(time));
+        validations.push(validate_signer(
+            certificate,
+            &intermediates,
+            &anchors,
+            list,
+            signer.signing_time(),
+            time,
+        )?);
+    }
+    Ok(validations)
+}
+
+/// Checks the key usage, path and qualification of the certificate of a
+/// verified signature, at `signing_time` if claimed, else at `time`
+pub(crate) fn validate_signer<'a>(
+    certificate: Certificate,
+    intermediates: &[Certificate],
+    anchors: &[ExtractedCertificate<'a>],
+    list: &'a TrustedList,
+    signing_time: Option<DateTime<Utc>>,
+    time: DateTime<Utc>,
+) -> Result<SignerValidation<'a>, CaExtractorError> {
+    if certificate.key_usage.is_some()
+        && !certificate.has_key_usage("digitalSignature")
+        && !certificate.has_key_usage("nonRepudiation")
+    {
+        return Err(invalid(format!(
+            "the key usage of '{}' allows neither digitalSignature nor nonRepudiation",
+            certificate.subject
+        )));
+    }
+
+    let options = PathOptions {
+        time: signing_time.unwrap_or(time),
+        purpose: None,
+        require_service_in_force: false,
+    };
+    let path = chain::validate(&certificate, intermediates, anchors, &options)
+        .map_err(CaExtractorError::PathValidationFailed)?;
+
+    let qualification = qualify::qualify(list, &certificate, intermediates);
+    let service_status = qualification
+        .service
+        .and_then(|service| service.information_at(options.time))
+        .map(|information| information.status.clone());
+
+    Ok(SignerValidation {
+        certificate,
+        signing_time,
+        validation_time: options.time,
+        path,
+        qualification,
+        service_status,
+    })
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
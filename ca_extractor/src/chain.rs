@@ -0,0 +1,359 @@
+//! Offline certification path building and validation against trust anchors
+//! taken from a trusted list.
+//!
+//! Paths are built from the end-entity certificate through the supplied
+//! intermediates by issuer name and signature. Each complete path is then
+//! checked for validity periods, basic constraints, key usage and extended
+//! key usage. Trust anchors are the service digital identities: their own
+//! validity period is not checked, their constraints are, and so is the
+//! status of their service at the validation time.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::etsi;
+use crate::extractor::ExtractedCertificate;
+use crate::x509::Certificate;
+
+/// `anyExtendedKeyUsage`
+pub const OID_ANY_EXTENDED_KEY_USAGE: &str = "2.5.29.37.0";
+
+/// Key purposes accepted by name on the command line
+pub const KEY_PURPOSES: &[(&str, &str)] = &[
+    ("serverAuth", "1.3.6.1.5.5.7.3.1"),
+    ("clientAuth", "1.3.6.1.5.5.7.3.2"),
+    ("codeSigning", "1.3.6.1.5.5.7.3.3"),
+    ("emailProtection", "1.3.6.1.5.5.7.3.4"),
+    ("timeStamping", "1.3.6.1.5.5.7.3.8"),
+    ("OCSPSigning", "1.3.6.1.5.5.7.3.9"),
+];
+
+/// Resolves a key purpose given by name or OID
+pub fn key_purpose_oid(value: &str) -> Option<String> {
+    if let Some((_, oid)) = KEY_PURPOSES.iter().find(|(name, _)| name.eq_ignore_ascii_case(value)) {
+        return Some(oid.to_string());
+    }
+    let is_arc = |arc: &str| !arc.is_empty() && arc.bytes().all(|b| b.is_ascii_digit());
+    let is_oid = value.contains('.') && value.split('.').all(is_arc);
+    is_oid.then(|| value.to_string())
+}
+
+/// Why a candidate path was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathFailure {
+    /// Neither the intermediates nor the trust anchors contain a certificate
+    /// named as the issuer
+    IssuerNotFound { subject: String, issuer: String },
+    /// Certificates named as the issuer exist but none verifies the signature
+    BadSignature { subject: String, issuer: String },
+    NotYetValid { subject: String, not_before: DateTime<Utc> },
+    Expired { subject: String, not_after: DateTime<Utc> },
+    /// An issuing certificate lacks basic constraints with `cA` set
+    NotACa { subject: String },
+    PathLengthExceeded { subject: String, path_len: u32, below: u32 },
+    /// An issuing certificate's key usage lacks `keyCertSign`
+    MissingKeyCertSign { subject: String },
+    /// The extended key usage does not allow the requested purpose
+    ExtendedKeyUsage { subject: String, purpose: String },
+    /// The service of the trust anchor was not in force at the validation
+    /// time (see [`etsi::is_in_force`]); `status` is `None` if the service
+    /// was not listed yet
+    ServiceNotInForce {
+        subject: String,
+        service: String,
+        status: Option<String>,
+    },
+}
+
+impl fmt::Display for PathFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathFailure::IssuerNotFound { subject, issuer } => write!(
+                f,
+                "no intermediate or trust anchor named '{}', the issuer of '{}'",
+                issuer, subject
+            ),
+            PathFailure::BadSignature { subject, issuer } => write!(
+                f,
+                "the signature of '{}' does not verify with any certificate named '{}'",
+                subject, issuer
+            ),
+            PathFailure::NotYetValid { subject, not_before } => {
+                write!(f, "'{}' is not valid before {}", subject, not_before.to_rfc3339())
+            }
+            PathFailure::Expired { subject, not_after } => {
+                write!(f, "'{}' expired on {}", subject, not_after.to_rfc3339())
+            }
+            PathFailure::NotACa { subject } => {
+                write!(f, "'{}' issues certificates but is not a CA (basicConstraints)", subject)
+            }
+            PathFailure::PathLengthExceeded { subject, path_len, below } => write!(
+                f,
+                "'{}' allows {} intermediate(s) below it (pathLenConstraint) but the path has {}",
+                subject, path_len, below
+            ),
+            PathFailure::MissingKeyCertSign { subject } => {
+                write!(f, "'{}' issues certificates but its key usage lacks keyCertSign", subject)
+            }
+            PathFailure::ExtendedKeyUsage { subject, purpose } => {
+                write!(f, "the extended key usage of '{}' does not allow {}", subject, purpose)
+            }
+            PathFailure::ServiceNotInForce { subject, service, status } => match status {
+                Some(status) => write!(
+                    f,
+                    "the service '{}' of trust anchor '{}' has status {} at the validation time, \
+                     neither granted nor recognised at national level",
+                    service,
+                    subject,
+                    etsi::short_name(status)
+                ),
+                None => write!(
+                    f,
+                    "the service '{}' of trust anchor '{}' was not listed yet at the validation time",
+                    service, subject
+                ),
+            },
+        }
+    }
+}
+
+/// Options of the path validation
+#[derive(Debug, Clone)]
+pub struct PathOptions {
+    /// Time at which every certificate must be valid
+    pub time: DateTime<Utc>,
+    /// Key purpose OID the end-entity certificate must be usable for
+    pub purpose: Option<String>,
+    /// Reject paths to anchors whose service is not in force at `time`, see
+    /// [`PathFailure::ServiceNotInForce`]. Callers reporting the service
+    /// status themselves, such as signature validation, may leave it off.
+    pub require_service_in_force: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions {
+            time: Utc::now(),
+            purpose: None,
+            require_service_in_force: true,
+        }
+    }
+}
+
+/// A validated certification path
+#[derive(Debug, Clone)]
+pub struct ValidPath<'a> {
+    /// The trusted list certificate the path ends at
+    pub anchor: ExtractedCertificate<'a>,
+    /// Status URI of the anchor's service at the validation time, `None` if
+    /// the service was not listed yet
+    pub anchor_status: Option<String>,
+    /// Certificates from the end-entity one up to, not including, the anchor
+    pub chain: Vec<Certificate>,
+}
+
+impl ValidPath<'_> {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "chain": self.chain.iter().map(|cert| cert.subject.clone()).collect::<Vec<_>>(),
+            "anchor": self.anchor.to_json(),
+            "anchor_status": self.anchor_status,
+        })
+    }
+}
+
+/// A trust anchor as the search sees it
+struct Anchor {
+    /// `None` if the listed certificate does not parse
+    certificate: Option<Certificate>,
+    /// Status of the service at the validation time
+    status: Option<String>,
+    /// Why paths ending at the anchor are rejected regardless of their content
+    rejection: Option<PathFailure>,
+}
+
+/// Builds and validates a path from `certificate` to one of `anchors`.
+///
+/// Every candidate path is tried; when none is valid, the failures of the
+/// candidates are returned, the ones of the longest paths first.
+pub fn validate<'a>(
+    certificate: &Certificate,
+    intermediates: &[Certificate],
+    anchors: &[ExtractedCertificate<'a>],
+    options: &PathOptions,
+) -> Result<ValidPath<'a>, Vec<PathFailure>> {
+    let anchor_certificates: Vec<Anchor> = anchors
+        .iter()
+        .map(|anchor| {
+            let certificate = Certificate::from_der(anchor.der).ok();
+            let status = anchor.service.information_at(options.time).map(|information| information.status.clone());
+            let in_force = !options.require_service_in_force || status.as_deref().is_some_and(etsi::is_in_force);
+            let rejection = match &certificate {
+                Some(certificate) if !in_force => Some(PathFailure::ServiceNotInForce {
+                    subject: certificate.subject.clone(),
+                    service: anchor.service.information.name().unwrap_or_default().to_string(),
+                    status: status.clone(),
+                }),
+                _ => None,
+            };
+            Anchor {
+                certificate,
+                status,
+                rejection,
+            }
+        })
+        .collect();
+
+    let mut failures: Vec<(usize, PathFailure)> = Vec::new();
+    let mut chain = vec![certificate.clone()];
+    let found = search(&mut chain, intermediates, &anchor_certificates, options, &mut failures);
+
+    match found {
+        Some(index) => Ok(ValidPath {
+            anchor: anchors[index].clone(),
+            anchor_status: anchor_certificates[index].status.clone(),
+            chain,
+        }),
+        None => {
+            failures.sort_by_key(|(depth, _)| std::cmp::Reverse(*depth));
+            let mut reasons: Vec<PathFailure> = Vec::new();
+            for (_, failure) in failures {
+                if !reasons.contains(&failure) {
+                    reasons.push(failure);
+                }
+            }
+            Err(reasons)
+        }
+    }
+}
+
+/// Depth-first search for a valid path extending `chain`. Returns the index
+/// of the anchor, leaving the path in `chain`.
+fn search(
+    chain: &mut Vec<Certificate>,
+    intermediates: &[Certificate],
+    anchors: &[Anchor],
+    options: &PathOptions,
+    failures: &mut Vec<(usize, PathFailure)>,
+) -> Option<usize> {
+    let current = chain.last().expect("the chain starts with the certificate").clone();
+    let named = |candidate: &Certificate| candidate.subject_raw == current.issuer_raw;
+    let mut any_named = false;
+    let known_failures = failures.len();
+
+    for (index, Anchor { certificate, rejection, .. }) in anchors.iter().enumerate() {
+        let Some(anchor) = certificate else {
+            continue;
+        };
+        if !named(anchor) {
+            continue;
+        }
+        any_named = true;
+        if !current.is_signed_by(anchor) {
+            continue;
+        }
+        match check_path(chain, anchor, options).and(rejection.clone().map_or(Ok(()), Err)) {
+            Ok(()) => return Some(index),
+            Err(failure) => failures.push((chain.len(), failure)),
+        }
+    }
+
+    for candidate in intermediates {
+        if !named(candidate) || chain.contains(candidate) {
+            continue;
+        }
+        any_named = true;
+        if !current.is_signed_by(candidate) {
+            continue;
+        }
+        chain.push(candidate.clone());
+        if let Some(index) = search(chain, intermediates, anchors, options, failures) {
+            return Some(index);
+        }
+        chain.pop();
+    }
+
+    let subject = current.subject.clone();
+    let issuer = current.issuer.clone();
+    if !any_named {
+        failures.push((chain.len(), PathFailure::IssuerNotFound { subject, issuer }));
+    } else if failures.len() == known_failures {
+        // Every certificate named as the issuer failed the signature check
+        failures.push((chain.len(), PathFailure::BadSignature { subject, issuer }));
+    }
+    None
+}
+
+/// Checks the constraints of a complete path: `chain` from the end-entity
+/// certificate, issued by `anchor`
+fn check_path(chain: &[Certificate], anchor: &Certificate, options: &PathOptions) -> Result<(), PathFailure> {
+    for cert in chain {
+        if options.time < cert.not_before {
+            return Err(PathFailure::NotYetValid {
+                subject: cert.subject.clone(),
+                not_before: cert.not_before,
+            });
+        }
+        if options.time > cert.not_after {
+            return Err(PathFailure::Expired {
+                subject: cert.subject.clone(),
+                not_after: cert.not_after,
+            });
+        }
+    }
+
+    // Issuing certificates, from the one closest to the end entity up to the anchor
+    let issuers = chain.iter().skip(1).chain(std::iter::once(anchor));
+    for (position, cert) in issuers.enumerate() {
+        if cert.ca != Some(true) {
+            return Err(PathFailure::NotACa {
+                subject: cert.subject.clone(),
+            });
+        }
+        if cert.key_usage.is_some() && !cert.has_key_usage("keyCertSign") {
+            return Err(PathFailure::MissingKeyCertSign {
+                subject: cert.subject.clone(),
+            });
+        }
+        if let Some(path_len) = cert.path_len_constraint {
+            // Intermediates between this certificate and the end entity,
+            // not counting self-issued ones
+            let below = chain[1..=position].iter().filter(|c| !c.is_self_issued()).count() as u32;
+            if below > path_len {
+                return Err(PathFailure::PathLengthExceeded {
+                    subject: cert.subject.clone(),
+                    path_len,
+                    below,
+                });
+            }
+        }
+    }
+
+    if let Some(purpose) = &options.purpose {
+        // The end entity must allow the purpose; CAs restricting their
+        // extended key usage must allow it as well
+        for cert in chain.iter().chain(std::iter::once(anchor)) {
+            if let Some(usages) = &cert.extended_key_usage {
+                if !usages.iter().any(|oid| oid == purpose || oid == OID_ANY_EXTENDED_KEY_USAGE) {
+                    return Err(PathFailure::ExtendedKeyUsage {
+                        subject: cert.subject.clone(),
+                        purpose: key_purpose_name(purpose),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of a known key purpose, or the OID itself
+pub fn key_purpose_name(oid: &str) -> String {
+    KEY_PURPOSES
+        .iter()
+        .find(|(_, purpose)| *purpose == oid)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| oid.to_string())
+}
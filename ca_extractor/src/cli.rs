@@ -1,11 +1,320 @@
-use clap::{Parser, ValueEnum};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+use chrono::{DateTime, Utc};
+use clap::builder::{PossibleValue, TypedValueParser};
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Arg, ArgGroup, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+
+use crate::chain::{self, PathOptions};
+use crate::error::CaExtractorError;
+use crate::etsi::{self, ServiceSelector};
+use crate::expression::Expression;
+use crate::extractor::CertificateExtractor;
+use crate::filter::{self, CertificateFilter};
+use crate::limits::{ParseLimits, DEFAULT_MAX_DEPTH, DEFAULT_MAX_DOWNLOAD_SIZE, DEFAULT_MAX_TEXT_SIZE};
+use crate::model::{self, QualificationLevel};
+use crate::ocsp::OcspClient;
+use crate::x509;
+
+/// A kind of trust service to select: one of the named kinds, or a service
+/// type URI, optionally narrowed by an additional service information URI
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ServiceType {
-    /// Qualified certificate for website authentication
     QWAC,
-    /// Qualified certificate for electronic seal
     QSealC,
+    QESig,
+    QTST,
+    QVal,
+    QPres,
+    QERDS,
+    QEDS,
+    OcspQC,
+    CrlQC,
+    NationalRootCaQC,
+    QEAA,
+    PubEAA,
+    QArchiving,
+    QLedger,
+    /// Any service type URI, known or not, given in full or after
+    /// `http://uri.etsi.org/TrstSvc/Svctype/`
+    Uri {
+        service_type: String,
+        additional_information: Option<String>,
+    },
+}
+
+/// Named service kinds with their command line names and descriptions
+pub const SERVICE_TYPE_NAMES: &[(ServiceType, &str, &str)] = &[
+    (ServiceType::QWAC, "QWAC", "Qualified certificate for website authentication"),
+    (ServiceType::QSealC, "QSealC", "Qualified certificate for electronic seal"),
+    (ServiceType::QESig, "QESig", "Qualified certificate for electronic signature"),
+    (ServiceType::QTST, "QTST", "Qualified electronic time stamp authority"),
+    (ServiceType::QVal, "QVal", "Qualified validation service for qualified signatures and seals"),
+    (ServiceType::QPres, "QPres", "Qualified preservation service for qualified signatures and seals"),
+    (ServiceType::QERDS, "QERDS", "Qualified electronic registered delivery (registered e-mail)"),
+    (ServiceType::QEDS, "QEDS", "Qualified electronic delivery service"),
+    (ServiceType::OcspQC, "OCSP-QC", "OCSP responder for qualified certificates"),
+    (ServiceType::CrlQC, "CRL-QC", "CRL issuer for qualified certificates"),
+    (ServiceType::NationalRootCaQC, "NationalRootCA-QC", "National root CA for qualified certificates"),
+    (ServiceType::QEAA, "QEAA", "Qualified electronic attestation of attributes issuance (eIDAS 2.0)"),
+    (ServiceType::PubEAA, "Pub-EAA", "Attestation of attributes issued by a public sector body (eIDAS 2.0)"),
+    (ServiceType::QArchiving, "QArchiving", "Qualified electronic archiving service (eIDAS 2.0)"),
+    (ServiceType::QLedger, "QLedger", "Qualified electronic ledger (eIDAS 2.0)"),
+];
+
+impl ServiceType {
+    /// The service type and additional service information URIs identifying
+    /// services of this kind in a trusted list
+    pub fn selector(&self) -> ServiceSelector<'_> {
+        let (service_type, additional_information, national_service_type) = match self {
+            ServiceType::QWAC => (
+                etsi::SVCTYPE_CA_QC,
+                Some(etsi::ASI_FOR_WEB_SITE_AUTHENTICATION),
+                Some(etsi::SVCTYPE_CA_PKC),
+            ),
+            ServiceType::QSealC => (etsi::SVCTYPE_CA_QC, Some(etsi::ASI_FOR_ESEALS), Some(etsi::SVCTYPE_CA_PKC)),
+            ServiceType::QESig => (etsi::SVCTYPE_CA_QC, Some(etsi::ASI_FOR_ESIGNATURES), Some(etsi::SVCTYPE_CA_PKC)),
+            ServiceType::QTST => (etsi::SVCTYPE_TSA_QTST, None, Some(etsi::SVCTYPE_TSA)),
+            ServiceType::QVal => (etsi::SVCTYPE_QESVALIDATION_Q, None, Some(etsi::SVCTYPE_ADES_VALIDATION)),
+            ServiceType::QPres => (etsi::SVCTYPE_PSES_Q, None, Some(etsi::SVCTYPE_PSES)),
+            ServiceType::QERDS => (etsi::SVCTYPE_EDS_REM_Q, None, Some(etsi::SVCTYPE_EDS_REM)),
+            ServiceType::QEDS => (etsi::SVCTYPE_EDS_Q, None, Some(etsi::SVCTYPE_EDS)),
+            ServiceType::OcspQC => (etsi::SVCTYPE_OCSP_QC, None, Some(etsi::SVCTYPE_OCSP)),
+            ServiceType::CrlQC => (etsi::SVCTYPE_CRL_QC, None, Some(etsi::SVCTYPE_CRL)),
+            ServiceType::NationalRootCaQC => (etsi::SVCTYPE_NATIONAL_ROOT_CA_QC, None, None),
+            ServiceType::QEAA => (etsi::SVCTYPE_QEAA, None, Some(etsi::SVCTYPE_EAA)),
+            ServiceType::PubEAA => (etsi::SVCTYPE_PUB_EAA, None, None),
+            ServiceType::QArchiving => (etsi::SVCTYPE_ARCHIV_Q, None, Some(etsi::SVCTYPE_ARCHIV)),
+            ServiceType::QLedger => (etsi::SVCTYPE_LEDGER_Q, None, Some(etsi::SVCTYPE_LEDGER)),
+            // The service type itself also selects nationally recognised services
+            ServiceType::Uri {
+                service_type,
+                additional_information,
+            } => (service_type.as_str(), additional_information.as_deref(), None),
+        };
+        ServiceSelector {
+            service_type,
+            additional_information,
+            national_service_type,
+        }
+    }
+}
+
+impl fmt::Display for ServiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceType::Uri {
+                service_type,
+                additional_information,
+            } => {
+                write!(f, "{}", etsi::short_name(service_type))?;
+                match additional_information {
+                    Some(uri) => write!(f, "+{}", etsi::short_name(uri)),
+                    None => Ok(()),
+                }
+            }
+            named => {
+                let (_, name, _) =
+                    SERVICE_TYPE_NAMES.iter().find(|(kind, _, _)| kind == named).expect("every kind is named");
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = String;
+
+    /// Parses a named kind (`QWAC`), or `<service type>[+<additional service
+    /// information>]` where both are URIs or their short names, e.g.
+    /// `CA/QC+ForeSeals`. Short names must be defined by ETSI; full URIs may
+    /// be of any service type. Additional service information alone selects
+    /// `CA/QC` services, the only ones it narrows.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some((kind, _, _)) = SERVICE_TYPE_NAMES.iter().find(|(_, name, _)| *name == value) {
+            return Ok(kind.clone());
+        }
+
+        let additional_information_uri = |value: &str| -> Option<String> {
+            if value.contains("://") {
+                return value.starts_with(etsi::SVCINFOEXT_PREFIX).then(|| value.to_string());
+            }
+            let uri = format!("{}{}", etsi::SVCINFOEXT_PREFIX, value);
+            etsi::ADDITIONAL_INFORMATION_URIS.contains(&uri.as_str()).then_some(uri)
+        };
+        let (service_type, additional_information) = match value.split_once('+') {
+            Some((service_type, additional_information)) => {
+                let uri = additional_information_uri(additional_information).ok_or_else(|| {
+                    format!("'{}' is not an additional service information URI", additional_information)
+                })?;
+                (service_type, Some(uri))
+            }
+            None => match additional_information_uri(value) {
+                Some(uri) => (etsi::SVCTYPE_CA_QC, Some(uri)),
+                None => (value, None),
+            },
+        };
+        let service_type = if service_type.contains("://") {
+            service_type.to_string()
+        } else {
+            let uri = format!("{}{}", etsi::SVCTYPE_PREFIX, service_type);
+            if etsi::service_type(&uri).is_none() {
+                let names: Vec<&str> = SERVICE_TYPE_NAMES.iter().map(|(_, name, _)| *name).collect();
+                return Err(format!(
+                    "'{}' is neither one of {} nor a service type URI or ETSI short name (e.g. CA/QC)",
+                    service_type,
+                    names.join(", ")
+                ));
+            }
+            uri
+        };
+        Ok(ServiceType::Uri {
+            service_type,
+            additional_information,
+        })
+    }
+}
+
+/// Command line parser of [`ServiceType`], listing the named kinds in the help
+#[derive(Debug, Copy, Clone)]
+pub struct ServiceTypeParser;
+
+impl TypedValueParser for ServiceTypeParser {
+    type Value = ServiceType;
+
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&Arg>, value: &OsStr) -> Result<ServiceType, clap::Error> {
+        let value = value.to_str().ok_or_else(|| clap::Error::new(ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+        value.parse().map_err(|message: String| {
+            let arg = arg.map_or_else(|| "...".to_string(), ToString::to_string);
+            let mut error =
+                clap::Error::raw(ErrorKind::InvalidValue, format!("invalid value '{}' for '{}': {}\n", value, arg, message))
+                    .with_cmd(cmd);
+            // Lets Args::try_parse_command_line tell it from other invalid values
+            error.insert(ContextKind::Custom, ContextValue::String(message));
+            error
+        })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let named = SERVICE_TYPE_NAMES.iter().map(|(_, name, help)| PossibleValue::new(*name).help(*help));
+        Some(Box::new(named))
+    }
+}
+
+/// Which qualification levels of services to extract certificates from
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum QualificationFilter {
+    /// Qualified trust services only
+    #[default]
+    Qualified,
+    /// Nationally recognised, non-qualified services only (e.g. CA/PKC)
+    National,
+    /// Services no longer in force, e.g. withdrawn, qualified or not
+    Withdrawn,
+    /// Qualified and nationally recognised services, both in force
+    All,
+}
+
+impl fmt::Display for QualificationFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no qualification filter is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+impl QualificationFilter {
+    pub fn accepts(self, level: QualificationLevel) -> bool {
+        match self {
+            QualificationFilter::Qualified => level == QualificationLevel::Qualified,
+            QualificationFilter::National => level == QualificationLevel::National,
+            QualificationFilter::Withdrawn => level == QualificationLevel::Withdrawn,
+            QualificationFilter::All => matches!(level, QualificationLevel::Qualified | QualificationLevel::National),
+        }
+    }
+}
+
+/// Position of a CA certificate in its hierarchy
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum CaKind {
+    /// Self-signed certificates
+    Root,
+    /// Certificates issued by another CA
+    Intermediate,
+}
+
+impl fmt::Display for CaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaKind::Root => write!(f, "self-signed"),
+            CaKind::Intermediate => write!(f, "intermediate"),
+        }
+    }
+}
+
+/// Format of the extracted certificates
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One annotated PEM file per certificate
+    #[default]
+    Pem,
+    /// A single JSON file describing every certificate and its service
+    Json,
+}
+
+/// What the extraction does with CA certificates found revoked
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum RevokedAction {
+    /// Keep them, with their revocation status in the output
+    #[default]
+    Flag,
+    /// Leave them out of the output
+    Drop,
+}
+
+/// How OCSP requests use the nonce extension
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum OcspNonce {
+    /// Send no nonce
+    Off,
+    /// Send a nonce and check it when the responder echoes it
+    #[default]
+    Send,
+    /// Send a nonce and reject responses that do not echo it; disables the response cache
+    Require,
+}
+
+/// Format of the `list-endpoints` output
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum EndpointFormat {
+    /// Endpoints grouped by service
+    #[default]
+    Text,
+    /// A JSON array of endpoints
+    Json,
+    /// The host names of the endpoints, one per line
+    Hosts,
+}
+
+/// Format of a subcommand's report on stdout
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How errors are reported on stderr
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Human readable message with its cause chain
+    Text,
+    /// A single JSON object
+    Json,
 }
 
 #[derive(Parser, Debug)]
@@ -13,30 +322,616 @@ pub enum ServiceType {
     author, 
     version, 
     about = "Extract CA certificates from eIDAS Trusted List", 
-    long_about = "A tool to extract CA certificates from XML files available through eIDAS Trusted List"
+    long_about = "A tool to extract CA certificates from XML files available through eIDAS Trusted List",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    group = ArgGroup::new("revocation_check").args(["check_revocation", "ocsp"]).multiple(true)
 )]
 pub struct Args {
-    /// Type of service to retrieve certificate for
-    #[arg(value_enum)]
-    pub service: ServiceType,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Types of service to retrieve certificates for, separated by commas
+    #[arg(value_parser = ServiceTypeParser, required = true, num_args = 1, value_delimiter = ',')]
+    pub service: Vec<ServiceType>,
 
     /// ISO 3166-1 alpha-2 country code (only EEA countries are supported)
-    pub country: String,
+    #[arg(required = true)]
+    pub country: Option<String>,
 
     /// Target folder to save certificate files in
-    #[arg(long, default_value = ".")]
+    #[arg(long = "target_folder", alias = "target-folder", default_value = ".")]
     pub target_folder: String,
 
+    /// Qualification level of the services to extract certificates from
+    #[arg(long, value_enum, default_value_t = QualificationFilter::Qualified)]
+    pub qualification: QualificationFilter,
+
+    /// Only extract from services declaring this qualifier (URI or short name, e.g. QCWithQSCD); repeatable
+    #[arg(long = "qualifier", value_parser = parse_qualifier)]
+    pub qualifiers: Vec<String>,
+
+    /// Skip services declaring this qualifier (URI or short name, e.g. NotQualified); repeatable
+    #[arg(long = "exclude-qualifier", value_parser = parse_qualifier)]
+    pub excluded_qualifiers: Vec<String>,
+
+    /// Skip services that have been taken over by another TSP
+    #[arg(long, default_value_t = false)]
+    pub exclude_taken_over: bool,
+
+    /// Take the status of each service at this time (RFC 3339), from its status history, instead of the current time
+    #[arg(long, value_parser = parse_time)]
+    pub at: Option<DateTime<Utc>>,
+
+    /// Certificate content filters applied to the certificates of the selected services
+    #[command(flatten)]
+    pub certificate_filter: CertificateFilterArgs,
+
+    /// Format of the extracted certificates
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pem)]
+    pub output_format: OutputFormat,
+
+    /// Print why each certificate of the trusted list is selected or left out instead of extracting
+    #[arg(long, default_value_t = false)]
+    pub explain: bool,
+
+    /// Only explain the certificate with this SHA-256 fingerprint (hex, `:` separators allowed, or a prefix);
+    /// implies --explain
+    #[arg(long, value_name = "SHA256")]
+    pub explain_fingerprint: Option<String>,
+
+    /// Check the trusted list against the ETSI TS 119 612 schema before extracting
+    #[arg(long, default_value_t = false)]
+    pub validate: bool,
+
+    /// Check extracted CA certificates that are not self-issued against the CRLs of their issuers
+    #[arg(long, default_value_t = false)]
+    pub check_revocation: bool,
+
+    /// What to do with CA certificates found revoked
+    #[arg(long, value_enum, default_value_t = RevokedAction::Flag, requires = "revocation_check")]
+    pub revoked: RevokedAction,
+
+    /// Directory downloaded CRLs are cached in [default: ca_extractor/crls in the user's cache directory]
+    #[arg(long, requires = "check_revocation")]
+    pub crl_cache: Option<PathBuf>,
+
+    /// OCSP checking of the extracted CA certificates, before their CRLs with --check-revocation
+    #[command(flatten)]
+    pub ocsp: OcspArgs,
+
+    /// Maximum size in bytes of the downloaded trusted list
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_DOWNLOAD_SIZE)]
+    pub max_download_size: u64,
+
+    /// Maximum XML element nesting depth
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_DEPTH)]
+    pub max_depth: usize,
+
+    /// Maximum size in bytes of a single XML text node, and of the text of an element
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_TEXT_SIZE)]
+    pub max_text_size: usize,
+
+    /// Preferred languages of names and addresses in reports, separated by commas (e.g. en,de);
+    /// the first entry of the trusted list is used when none is available
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub lang: Vec<String>,
+
+    /// On failure, print the error's context and an excerpt of the offending XML
+    #[arg(long, global = true, default_value_t = false)]
+    pub explain_errors: bool,
+
+    /// Format of error output on stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    pub error_format: ErrorFormat,
+
     /// Enable verbose logging
     #[arg(short, long, default_value_t = false)]
     pub verbose: bool,
 }
 
-impl From<ServiceType> for String {
-    fn from(service_type: ServiceType) -> Self {
-        match service_type {
-            ServiceType::QWAC => "QWAC".to_string(),
-            ServiceType::QSealC => "QSealC".to_string(),
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Validate a trusted list against the ETSI TS 119 612 schema
+    Validate(ListSourceArgs),
+    /// Check a trusted list against the ETSI TS 119 612 semantic conformance rules
+    Check(ListSourceArgs),
+    /// Determine the qualification of an end-entity certificate (ETSI TS 119 615)
+    Qualify(CertificateArgs),
+    /// Analyse the QcStatements and PSD2 attributes of a certificate and cross-check them
+    /// against the QWAC and QSealC CAs of a trusted list
+    #[command(name = "inspect-qc")]
+    InspectQc(CertificateArgs),
+    /// Validate the certification path of a certificate to a CA of a trusted list
+    Verify(VerifyArgs),
+    /// Validate a two-certificate QWAC TLS certificate binding (ETSI TS 119 411-5)
+    #[command(name = "verify-2qwac")]
+    Verify2Qwac(TwoQwacArgs),
+    /// Validate an RFC 3161 time-stamp token as a qualified electronic time-stamp
+    #[command(name = "verify-timestamp")]
+    VerifyTimestamp(TimestampArgs),
+    /// Validate a detached CMS (CAdES) signature against the QSealC and QESig CAs of a trusted list
+    #[command(name = "verify-cms")]
+    VerifyCms(CmsArgs),
+    /// Validate a JAdES baseline-B signature against the QSealC and QESig CAs of a trusted list
+    #[command(name = "verify-jades")]
+    VerifyJades(JadesArgs),
+    /// List the supply points, service definition URIs, CRL distribution points and OCSP responders
+    /// of the services CA certificates are extracted from
+    #[command(name = "list-endpoints")]
+    ListEndpoints(EndpointsArgs),
+    /// List the trust service providers of a trusted list with their names, registration identifiers
+    /// and addresses
+    #[command(name = "list-tsps")]
+    ListTsps(TspsArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct OcspArgs {
+    /// Query the OCSP responders named in the certificates' authority information access
+    /// or in the services' supply points
+    #[arg(long, default_value_t = false)]
+    pub ocsp: bool,
+
+    /// How OCSP requests use the nonce extension
+    #[arg(long, value_enum, default_value_t = OcspNonce::Send, requires = "ocsp")]
+    pub ocsp_nonce: OcspNonce,
+
+    /// Directory OCSP responses are cached in until their nextUpdate; responses are not cached without it
+    #[arg(long, requires = "ocsp")]
+    pub ocsp_cache: Option<PathBuf>,
+}
+
+impl OcspArgs {
+    /// OCSP client configured by these arguments, `None` without `--ocsp`
+    pub fn client(&self, limits: ParseLimits) -> Option<OcspClient> {
+        if !self.ocsp {
+            return None;
         }
+        let client = OcspClient::new().with_nonce(self.ocsp_nonce).with_limits(limits);
+        Some(match &self.ocsp_cache {
+            Some(directory) => client.with_cache(directory),
+            None => client,
+        })
     }
 }
+
+#[derive(clap::Args, Debug)]
+pub struct CertificateFilterArgs {
+    /// Only select certificates asserting every one of these key usage bits (e.g. keyCertSign,crlSign)
+    #[arg(long, value_delimiter = ',', value_parser = parse_key_usage)]
+    pub key_usage: Vec<String>,
+
+    /// Only select certificates whose extended key usage allows one of these key purposes (serverAuth,
+    /// clientAuth, ... or an OID), separated by commas; `none` also selects certificates without the extension
+    #[arg(long, value_delimiter = ',', value_parser = parse_eku)]
+    pub eku: Vec<String>,
+
+    /// Only select certificates asserting one of these certificate policies (QCP-w, EVCP, ... or an OID),
+    /// separated by commas
+    #[arg(long, value_delimiter = ',', value_parser = parse_policy)]
+    pub policy: Vec<String>,
+
+    /// Only select certificates whose subject DN matches this regular expression, e.g. "O=Example Trust"
+    #[arg(long, value_parser = parse_regex)]
+    pub subject: Option<Regex>,
+
+    /// Only select certificates whose issuer DN matches this regular expression
+    #[arg(long, value_parser = parse_regex)]
+    pub issuer: Option<Regex>,
+
+    /// Only select self-signed root or intermediate CA certificates
+    #[arg(long, value_enum)]
+    pub ca_kind: Option<CaKind>,
+
+    /// Only select certificates valid from this time (RFC 3339) [default with --valid-until: now]
+    #[arg(long, value_parser = parse_time)]
+    pub valid_from: Option<DateTime<Utc>>,
+
+    /// Only select certificates valid until this time (RFC 3339) [default with --valid-from: that time]
+    #[arg(long, value_parser = parse_time)]
+    pub valid_until: Option<DateTime<Utc>>,
+
+    /// Only select certificates for which this expression holds, e.g.
+    /// 'service.type == "QC" && "ForWebSiteAuthentication" in service.additional && cert.not_after > now()'
+    #[arg(long = "where", value_name = "EXPRESSION", value_parser = parse_expression)]
+    pub condition: Option<Expression>,
+}
+
+impl CertificateFilterArgs {
+    /// `extractor` with the requested filters and condition
+    pub fn configure(&self, mut extractor: CertificateExtractor) -> CertificateExtractor {
+        if let Some(filter) = self.filter() {
+            extractor = extractor.with_certificate_filter(filter);
+        }
+        if let Some(condition) = &self.condition {
+            extractor = extractor.with_condition(condition.clone());
+        }
+        extractor
+    }
+
+    /// Every requested filter combined, `None` when no filter was requested
+    pub fn filter(&self) -> Option<CertificateFilter> {
+        let mut filters = Vec::new();
+        if !self.key_usage.is_empty() {
+            filters.push(CertificateFilter::KeyUsage(self.key_usage.clone()));
+        }
+        if !self.eku.is_empty() {
+            filters.push(CertificateFilter::ExtendedKeyUsage {
+                purposes: self.eku.iter().filter(|purpose| *purpose != EKU_NONE).cloned().collect(),
+                allow_absent: self.eku.iter().any(|purpose| purpose == EKU_NONE),
+            });
+        }
+        if !self.policy.is_empty() {
+            filters.push(CertificateFilter::Policy(self.policy.clone()));
+        }
+        if let Some(pattern) = &self.subject {
+            filters.push(CertificateFilter::Subject(pattern.clone()));
+        }
+        if let Some(pattern) = &self.issuer {
+            filters.push(CertificateFilter::Issuer(pattern.clone()));
+        }
+        if let Some(kind) = self.ca_kind {
+            filters.push(CertificateFilter::Kind(kind));
+        }
+        if self.valid_from.is_some() || self.valid_until.is_some() {
+            let from = self.valid_from.unwrap_or_else(Utc::now);
+            filters.push(CertificateFilter::ValidDuring {
+                from,
+                until: self.valid_until.unwrap_or(from),
+            });
+        }
+
+        match filters.len() {
+            0 => None,
+            1 => filters.pop(),
+            _ => Some(CertificateFilter::All(filters)),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ListSourceArgs {
+    /// ISO 3166-1 alpha-2 country code of the trusted list to download
+    #[arg(required_unless_present = "file")]
+    pub country: Option<String>,
+
+    /// Read a local trusted list file instead of downloading one
+    #[arg(long, conflicts_with = "country")]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CertificateArgs {
+    /// PEM or DER certificate to examine; further certificates in a PEM file are used as intermediates
+    pub cert: PathBuf,
+
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// PEM bundle of intermediate CA certificates between the certificate and the trusted list
+    #[arg(long)]
+    pub chain: Option<PathBuf>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub certificate: CertificateArgs,
+
+    /// Types of service whose certificates are trust anchors, separated by commas
+    #[arg(long, value_parser = ServiceTypeParser, value_delimiter = ',', default_value = "QWAC,QSealC,QESig")]
+    pub service: Vec<ServiceType>,
+
+    /// Qualification level of the services whose certificates are trust anchors
+    #[arg(long, value_enum, default_value_t = QualificationFilter::Qualified)]
+    pub qualification: QualificationFilter,
+
+    /// Key purpose the certificate must be valid for (serverAuth, clientAuth, ... or an OID)
+    #[arg(long, value_parser = parse_key_purpose)]
+    pub purpose: Option<String>,
+
+    /// Validation time (RFC 3339) instead of the current time
+    #[arg(long, value_parser = parse_time)]
+    pub at: Option<DateTime<Utc>>,
+
+    #[command(flatten)]
+    pub ocsp: OcspArgs,
+}
+
+impl VerifyArgs {
+    /// Options of the path validation, at `--at` or else the current time
+    pub fn path_options(&self) -> PathOptions {
+        PathOptions {
+            time: self.at.unwrap_or_else(Utc::now),
+            purpose: self.purpose.clone(),
+            require_service_in_force: true,
+        }
+    }
+
+    /// Selects the services of `--service` at `--qualification` by their
+    /// status at the validation `time`, not at the current time
+    pub fn anchor_extractor(
+        &self,
+        country: &str,
+        time: DateTime<Utc>,
+    ) -> Result<CertificateExtractor, CaExtractorError> {
+        let extractor = CertificateExtractor::new(&self.service, country)?;
+        Ok(extractor.with_qualification(self.qualification).with_time(time))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TwoQwacArgs {
+    /// TLS certificate binding: a compact JAdES signature with a detached payload
+    pub binding: PathBuf,
+
+    /// PEM or DER TLS certificate the website presented
+    pub tls_cert: PathBuf,
+
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// PEM bundle of intermediate CA certificates missing from the binding's x5c
+    #[arg(long)]
+    pub chain: Option<PathBuf>,
+
+    /// Validation time (RFC 3339) instead of the current time
+    #[arg(long, value_parser = parse_time)]
+    pub at: Option<DateTime<Utc>>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+impl TwoQwacArgs {
+    /// Options of the path validation of the 2-QWAC, at `--at` or else the
+    /// current time
+    pub fn path_options(&self) -> PathOptions {
+        PathOptions {
+            time: self.at.unwrap_or_else(Utc::now),
+            purpose: None,
+            require_service_in_force: true,
+        }
+    }
+
+    /// Selects the QWAC services by their status at the validation `time`,
+    /// not at the current time
+    pub fn anchor_extractor(
+        &self,
+        country: &str,
+        time: DateTime<Utc>,
+    ) -> Result<CertificateExtractor, CaExtractorError> {
+        Ok(CertificateExtractor::new(&[ServiceType::QWAC], country)?.with_time(time))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TimestampArgs {
+    /// DER time-stamp response (.tsr) or time-stamp token
+    pub token: PathBuf,
+
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// The time-stamped file, checked against the token's message imprint
+    #[arg(long)]
+    pub data: Option<PathBuf>,
+
+    /// PEM bundle with the TSA certificate and its CAs, when the token does not embed them
+    #[arg(long)]
+    pub chain: Option<PathBuf>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CmsArgs {
+    /// DER detached CMS signature (.p7s)
+    pub signature: PathBuf,
+
+    /// The signed file
+    pub data: PathBuf,
+
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// PEM bundle of signer or intermediate certificates the signature does not embed
+    #[arg(long)]
+    pub chain: Option<PathBuf>,
+
+    /// Validation time (RFC 3339) for signers without a signing-time attribute, instead of the current time
+    #[arg(long, value_parser = parse_time)]
+    pub at: Option<DateTime<Utc>>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EndpointsArgs {
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// Types of service to list endpoints for, separated by commas
+    #[arg(long, value_parser = ServiceTypeParser, value_delimiter = ',', default_value = "QWAC,QSealC,QESig")]
+    pub service: Vec<ServiceType>,
+
+    /// Qualification level of the services to list endpoints for
+    #[arg(long, value_enum, default_value_t = QualificationFilter::Qualified)]
+    pub qualification: QualificationFilter,
+
+    #[command(flatten)]
+    pub certificate_filter: CertificateFilterArgs,
+
+    /// Format of the list
+    #[arg(long, value_enum, default_value_t = EndpointFormat::Text)]
+    pub format: EndpointFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TspsArgs {
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// Only list TSPs with a name or trade name containing this text, ignoring case
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Only list TSPs with a registration identifier starting with this text, e.g. VATDE-123456789 or NTRDE
+    #[arg(long)]
+    pub identifier: Option<String>,
+
+    /// Format of the list
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct JadesArgs {
+    /// JAdES signature in JWS compact serialization
+    pub signature: PathBuf,
+
+    #[command(flatten)]
+    pub source: ListSourceArgs,
+
+    /// The payload of a detached signature
+    #[arg(long)]
+    pub payload: Option<PathBuf>,
+
+    /// PEM bundle of intermediate CA certificates missing from the x5c header
+    #[arg(long)]
+    pub chain: Option<PathBuf>,
+
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+fn parse_key_purpose(value: &str) -> Result<String, String> {
+    chain::key_purpose_oid(value).ok_or_else(|| {
+        let known: Vec<&str> = chain::KEY_PURPOSES.iter().map(|(name, _)| *name).collect();
+        format!("unknown key purpose, expected an OID or one of {}", known.join(", "))
+    })
+}
+
+/// `--eku` value selecting certificates without an extended key usage extension
+const EKU_NONE: &str = "none";
+
+fn parse_eku(value: &str) -> Result<String, String> {
+    if value.eq_ignore_ascii_case(EKU_NONE) {
+        return Ok(EKU_NONE.to_string());
+    }
+    parse_key_purpose(value)
+}
+
+fn parse_key_usage(value: &str) -> Result<String, String> {
+    x509::KEY_USAGE_BITS
+        .iter()
+        .find(|bit| bit.eq_ignore_ascii_case(value))
+        .map(|bit| bit.to_string())
+        .ok_or_else(|| format!("unknown key usage, expected one of {}", x509::KEY_USAGE_BITS.join(", ")))
+}
+
+fn parse_policy(value: &str) -> Result<String, String> {
+    filter::policy_oid(value).ok_or_else(|| {
+        let known: Vec<&str> = filter::POLICIES.iter().map(|(name, _)| *name).collect();
+        format!("unknown certificate policy, expected an OID or one of {}", known.join(", "))
+    })
+}
+
+fn parse_expression(value: &str) -> Result<Expression, String> {
+    Expression::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|e| e.to_string())
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    model::parse_date_time(value).ok_or_else(|| "expected an RFC 3339 date and time".to_string())
+}
+
+fn parse_qualifier(value: &str) -> Result<String, String> {
+    etsi::qualifier_uri(value).map(str::to_string).ok_or_else(|| {
+        let known: Vec<&str> = etsi::QUALIFIERS.iter().map(|uri| etsi::short_name(uri)).collect();
+        format!("unknown qualifier, expected one of {}", known.join(", "))
+    })
+}
+
+/// Why the command line could not be parsed
+#[derive(Debug)]
+pub enum CommandLineError {
+    /// Reported by clap itself: usage errors, `--help` and `--version`
+    Clap(clap::Error),
+    /// An invalid `--service` value, to be reported in the requested error format
+    ServiceType(CaExtractorError, ErrorFormat),
+}
+
+impl Args {
+    /// Parse the command line like [`Parser::try_parse_from`], except that an
+    /// invalid service type becomes [`CaExtractorError::InvalidServiceType`],
+    /// so that it has that error's exit code and JSON `kind`
+    pub fn try_parse_command_line<I, T>(args: I) -> Result<Args, CommandLineError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let error = match Args::try_parse_from(&args) {
+            Ok(args) => return Ok(args),
+            Err(error) => error,
+        };
+        let message = match error.get(ContextKind::Custom) {
+            Some(ContextValue::String(message)) if error.kind() == ErrorKind::InvalidValue => message.clone(),
+            _ => return Err(CommandLineError::Clap(error)),
+        };
+        // clap stops at the invalid value, so look for --error-format in the raw arguments
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+        let mut format = ErrorFormat::Text;
+        for (i, arg) in args.iter().enumerate() {
+            let value = match arg.strip_prefix("--error-format") {
+                Some("") => args.get(i + 1).map(|value| value.as_ref()),
+                Some(value) => value.strip_prefix('='),
+                None => None,
+            };
+            if let Some(value) = value.and_then(|value| ErrorFormat::from_str(value, false).ok()) {
+                format = value;
+            }
+        }
+        Err(CommandLineError::ServiceType(CaExtractorError::InvalidServiceType(message), format))
+    }
+
+    /// Safety limits requested on the command line
+    pub fn limits(&self) -> ParseLimits {
+        ParseLimits {
+            max_download_size: self.max_download_size,
+            max_depth: self.max_depth,
+            max_text_size: self.max_text_size,
+        }
+    }
+
+    /// Directory of the CRL cache used by `--check-revocation`, by default in
+    /// the user's cache directory
+    pub fn crl_cache_dir(&self) -> PathBuf {
+        self.crl_cache.clone().unwrap_or_else(|| user_cache_dir().join("ca_extractor").join("crls"))
+    }
+}
+
+/// The per-user cache directory: `$XDG_CACHE_HOME`, `%LOCALAPPDATA%` or
+/// `~/.cache`, falling back to the temporary directory
+fn user_cache_dir() -> PathBuf {
+    let absolute = |name: &str| std::env::var_os(name).map(PathBuf::from).filter(|path| path.is_absolute());
+    absolute("XDG_CACHE_HOME")
+        .or_else(|| absolute("LOCALAPPDATA"))
+        .or_else(|| absolute("HOME").map(|home| home.join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+}
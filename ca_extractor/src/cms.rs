@@ -0,0 +1,475 @@
+//! CMS SignedData (RFC 5652), the container of RFC 3161 time-stamp tokens
+//! and CAdES signatures.
+//!
+//! Only what verification needs is parsed: the encapsulated content, the
+//! embedded certificates and the signer infos with their attributes.
+//! Definite length BER is accepted, as produced by every common signer.
+
+use chrono::{DateTime, Utc};
+use ring::{digest, signature};
+use x509_parser::der_parser::asn1_rs::{Any, Class, FromBer, Tag};
+
+use crate::error::CaExtractorError;
+use crate::x509::Certificate;
+
+/// `id-signedData`
+pub const OID_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+/// `id-data`
+pub const OID_DATA: &str = "1.2.840.113549.1.7.1";
+pub const OID_CONTENT_TYPE: &str = "1.2.840.113549.1.9.3";
+pub const OID_MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+pub const OID_SIGNING_TIME: &str = "1.2.840.113549.1.9.5";
+/// ESS `id-aa-signingCertificate` (RFC 2634), identifying the signer by SHA-1
+pub const OID_SIGNING_CERTIFICATE: &str = "1.2.840.113549.1.9.16.2.12";
+/// ESS `id-aa-signingCertificateV2` (RFC 5035)
+pub const OID_SIGNING_CERTIFICATE_V2: &str = "1.2.840.113549.1.9.16.2.47";
+
+const OID_SHA1: &str = "1.3.14.3.2.26";
+const OID_SHA256: &str = "2.16.840.1.101.3.4.2.1";
+const OID_SHA384: &str = "2.16.840.1.101.3.4.2.2";
+const OID_SHA512: &str = "2.16.840.1.101.3.4.2.3";
+
+/// A signed or unsigned attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub oid: String,
+    /// DER encoding of each value
+    pub values: Vec<Vec<u8>>,
+}
+
+/// How a signer info names the signer's certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerIdentifier {
+    IssuerAndSerialNumber { issuer_raw: Vec<u8>, serial: Vec<u8> },
+    SubjectKeyIdentifier(Vec<u8>),
+}
+
+impl SignerIdentifier {
+    pub fn matches(&self, certificate: &Certificate) -> bool {
+        match self {
+            SignerIdentifier::IssuerAndSerialNumber { issuer_raw, serial } => {
+                *issuer_raw == certificate.issuer_raw && *serial == certificate.serial
+            }
+            SignerIdentifier::SubjectKeyIdentifier(id) => certificate.subject_key_id.as_ref() == Some(id),
+        }
+    }
+}
+
+/// A `SignerInfo`
+#[derive(Debug, Clone)]
+pub struct SignerInfo {
+    pub signer: SignerIdentifier,
+    pub digest_algorithm: String,
+    pub signed_attributes: Vec<Attribute>,
+    /// DER of the signed attributes as the `SET OF` the signature covers
+    signed_attributes_der: Option<Vec<u8>>,
+    pub signature_algorithm: String,
+    pub signature: Vec<u8>,
+    pub unsigned_attributes: Vec<Attribute>,
+}
+
+impl SignerInfo {
+    /// First value of the signed attribute `oid`
+    pub fn signed_attribute(&self, oid: &str) -> Option<&[u8]> {
+        self.signed_attributes
+            .iter()
+            .find(|attribute| attribute.oid == oid)
+            .and_then(|attribute| attribute.values.first())
+            .map(Vec::as_slice)
+    }
+
+    /// The `signing-time` attribute: the time claimed by the signer
+    pub fn signing_time(&self) -> Option<DateTime<Utc>> {
+        let element = first(self.signed_attribute(OID_SIGNING_TIME)?).ok()?;
+        let time = match element.any.tag() {
+            Tag::UtcTime => element.any.as_utctime().ok()?.utc_adjusted_datetime().ok()?,
+            Tag::GeneralizedTime => element.any.as_generalizedtime().ok()?.utc_datetime().ok()?,
+            _ => return None,
+        };
+        DateTime::from_timestamp(time.unix_timestamp(), time.nanosecond())
+    }
+
+    /// Verifies the signature over `content` with the key of `certificate`.
+    /// With signed attributes, the `content-type` attribute must name
+    /// `content_type` and the `message-digest` attribute the digest of
+    /// `content`.
+    pub fn verify(
+        &self,
+        content: &[u8],
+        content_type: &str,
+        certificate: &Certificate,
+    ) -> Result<(), CaExtractorError> {
+        let algorithm = digest_algorithm(&self.digest_algorithm)
+            .ok_or_else(|| invalid(format!("unsupported digest algorithm {}", self.digest_algorithm)))?;
+
+        let message = match &self.signed_attributes_der {
+            Some(der) => {
+                let signed_type = self
+                    .signed_attribute(OID_CONTENT_TYPE)
+                    .map(|value| first(value).and_then(|element| oid(&element)))
+                    .transpose()?;
+                if signed_type.as_deref() != Some(content_type) {
+                    return Err(invalid(format!(
+                        "the content-type attribute does not name the content type {}",
+                        content_type
+                    )));
+                }
+                let message_digest = self
+                    .signed_attribute(OID_MESSAGE_DIGEST)
+                    .ok_or_else(|| invalid("no message-digest attribute".to_string()))
+                    .and_then(|value| octets(&first(value)?))?;
+                if message_digest != digest::digest(algorithm, content).as_ref() {
+                    return Err(invalid("the message-digest attribute does not match the content".to_string()));
+                }
+                der.as_slice()
+            }
+            None => content,
+        };
+
+        let verification = verification_algorithm(&self.signature_algorithm, &self.digest_algorithm, certificate)
+            .ok_or_else(|| invalid(format!("unsupported signature algorithm {}", self.signature_algorithm)))?;
+        if certificate.verifies(verification, message, &self.signature) {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "the CMS signature does not verify with the key of '{}'",
+                certificate.subject
+            )))
+        }
+    }
+
+    /// Checks the ESS signing certificate attribute, v2 or v1, against
+    /// `certificate`. Returns whether the attribute is present.
+    pub fn check_signing_certificate(&self, certificate: &Certificate) -> Result<bool, CaExtractorError> {
+        let (value, v2) = match (
+            self.signed_attribute(OID_SIGNING_CERTIFICATE_V2),
+            self.signed_attribute(OID_SIGNING_CERTIFICATE),
+        ) {
+            (Some(value), _) => (value, true),
+            (None, Some(value)) => (value, false),
+            (None, None) => return Ok(false),
+        };
+
+        // SigningCertificate(V2) ::= SEQUENCE { certs SEQUENCE OF ESSCertID(v2), policies OPTIONAL }
+        let malformed = || invalid("malformed signing certificate attribute".to_string());
+        let certs = first(value)?.children()?;
+        let cert_id = certs.first().ok_or_else(malformed)?.children_of_first()?;
+        let mut fields = cert_id.iter();
+        let mut field = fields.next().ok_or_else(malformed)?;
+        let mut algorithm = if v2 { OID_SHA256.to_string() } else { OID_SHA1.to_string() };
+        if v2 && field.any.tag() == Tag::Sequence {
+            // hashAlgorithm, DEFAULT sha256
+            algorithm = field.children()?.first().ok_or_else(malformed).and_then(oid)?;
+            field = fields.next().ok_or_else(malformed)?;
+        }
+        let hash = octets(field)?;
+
+        let digest_algorithm = digest_algorithm(&algorithm)
+            .ok_or_else(|| invalid(format!("unsupported signing certificate digest algorithm {}", algorithm)))?;
+        if hash != digest::digest(digest_algorithm, &certificate.der).as_ref() {
+            return Err(invalid(format!(
+                "the signing certificate attribute does not identify '{}'",
+                certificate.subject
+            )));
+        }
+        Ok(true)
+    }
+}
+
+/// A parsed `SignedData`
+#[derive(Debug, Clone)]
+pub struct SignedData {
+    /// `eContentType` of the encapsulated content
+    pub content_type: String,
+    /// `eContent`, `None` for a detached signature
+    pub content: Option<Vec<u8>>,
+    /// Certificates embedded by the signer, other choices are skipped
+    pub certificates: Vec<Certificate>,
+    pub signers: Vec<SignerInfo>,
+}
+
+impl SignedData {
+    /// Parses a `ContentInfo` wrapping a `SignedData`
+    pub fn parse(data: &[u8]) -> Result<Self, CaExtractorError> {
+        let content_info = Element::single(data)?.children()?;
+        let [content_type, content] = content_info.as_slice() else {
+            return Err(invalid("a ContentInfo has 2 fields".to_string()));
+        };
+        if oid(content_type)? != OID_SIGNED_DATA {
+            return Err(invalid(format!("content type {} is not signed data", oid(content_type)?)));
+        }
+        Self::from_element(&content.explicit(0)?)
+    }
+
+    fn from_element(signed_data: &Element) -> Result<Self, CaExtractorError> {
+        let malformed = || invalid("malformed SignedData".to_string());
+        let fields = signed_data.children()?;
+        // version, digestAlgorithms, encapContentInfo
+        let encapsulated = fields.get(2).ok_or_else(malformed)?.children()?;
+        let content_type = oid(encapsulated.first().ok_or_else(malformed)?)?;
+        let content = encapsulated
+            .get(1)
+            .map(|content| content.explicit(0).and_then(|content| octets(&content)))
+            .transpose()?;
+
+        let mut certificates = Vec::new();
+        let mut signers = Vec::new();
+        for field in &fields[3..] {
+            match (field.any.class(), field.any.tag()) {
+                (Class::ContextSpecific, Tag(0)) => {
+                    for choice in field.children()? {
+                        if choice.any.class() == Class::Universal && choice.any.tag() == Tag::Sequence {
+                            certificates.push(Certificate::from_der(choice.raw)?);
+                        }
+                    }
+                }
+                // crls
+                (Class::ContextSpecific, Tag(1)) => {}
+                (Class::Universal, Tag::Set) => {
+                    for signer in field.children()? {
+                        signers.push(parse_signer_info(&signer)?);
+                    }
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(SignedData {
+            content_type,
+            content,
+            certificates,
+            signers,
+        })
+    }
+
+    /// The certificate of `signer`, embedded or among `extra`
+    pub fn signer_certificate(&self, signer: &SignerInfo, extra: &[Certificate]) -> Option<Certificate> {
+        self.certificates
+            .iter()
+            .chain(extra)
+            .find(|certificate| signer.signer.matches(certificate))
+            .cloned()
+    }
+}
+
+fn parse_signer_info(element: &Element) -> Result<SignerInfo, CaExtractorError> {
+    let malformed = || invalid("malformed SignerInfo".to_string());
+    let fields = element.children()?;
+    let mut fields = fields.iter().skip(1);
+    let mut next = || fields.next().ok_or_else(malformed);
+
+    let sid = next()?;
+    let signer = if sid.any.class() == Class::ContextSpecific {
+        SignerIdentifier::SubjectKeyIdentifier(sid.any.data.to_vec())
+    } else {
+        let issuer_and_serial = sid.children()?;
+        let [issuer, serial] = issuer_and_serial.as_slice() else {
+            return Err(malformed());
+        };
+        SignerIdentifier::IssuerAndSerialNumber {
+            issuer_raw: issuer.raw.to_vec(),
+            serial: serial.any.data.to_vec(),
+        }
+    };
+    let digest_algorithm = algorithm(next()?)?;
+
+    let mut field = next()?;
+    let mut signed_attributes = Vec::new();
+    let mut signed_attributes_der = None;
+    if field.any.class() == Class::ContextSpecific {
+        // [0] IMPLICIT, signed as the universal SET OF
+        let mut der = field.raw.to_vec();
+        der[0] = 0x31;
+        signed_attributes_der = Some(der);
+        signed_attributes = parse_attributes(field)?;
+        field = next()?;
+    }
+    let signature_algorithm = algorithm(field)?;
+    let signature = octets(next()?)?;
+    let unsigned_attributes = match fields.next() {
+        Some(field) => parse_attributes(field)?,
+        None => Vec::new(),
+    };
+
+    Ok(SignerInfo {
+        signer,
+        digest_algorithm,
+        signed_attributes,
+        signed_attributes_der,
+        signature_algorithm,
+        signature,
+        unsigned_attributes,
+    })
+}
+
+fn parse_attributes(element: &Element) -> Result<Vec<Attribute>, CaExtractorError> {
+    element
+        .children()?
+        .iter()
+        .map(|attribute| {
+            let fields = attribute.children()?;
+            let [attribute_type, values] = fields.as_slice() else {
+                return Err(invalid("an Attribute has 2 fields".to_string()));
+            };
+            Ok(Attribute {
+                oid: oid(attribute_type)?,
+                values: values.children()?.iter().map(|value| value.raw.to_vec()).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Digest algorithm of a CMS `DigestAlgorithmIdentifier` OID
+pub fn digest_algorithm(oid: &str) -> Option<&'static digest::Algorithm> {
+    match oid {
+        OID_SHA1 => Some(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        OID_SHA256 => Some(&digest::SHA256),
+        OID_SHA384 => Some(&digest::SHA384),
+        OID_SHA512 => Some(&digest::SHA512),
+        _ => None,
+    }
+}
+
+/// Verification algorithm of a `SignatureAlgorithmIdentifier`. ECDSA keys
+/// are told apart by the length of their uncompressed point; bare
+/// `id-ecPublicKey` and `rsaEncryption` take the hash of the digest
+/// algorithm.
+pub(crate) fn verification_algorithm(
+    signature_oid: &str,
+    digest_oid: &str,
+    certificate: &Certificate,
+) -> Option<&'static dyn signature::VerificationAlgorithm> {
+    let hash = match signature_oid {
+        "1.2.840.10045.4.3.2" | "1.2.840.113549.1.1.11" => OID_SHA256,
+        "1.2.840.10045.4.3.3" | "1.2.840.113549.1.1.12" => OID_SHA384,
+        "1.2.840.10045.4.3.4" | "1.2.840.113549.1.1.13" => OID_SHA512,
+        _ => digest_oid,
+    };
+    let ecdsa = signature_oid.starts_with("1.2.840.10045.");
+    let pss = signature_oid == "1.2.840.113549.1.1.10";
+    let algorithm: &'static dyn signature::VerificationAlgorithm = match (ecdsa, pss, hash) {
+        (true, _, OID_SHA256) if certificate.public_key.len() == 97 => &signature::ECDSA_P384_SHA256_ASN1,
+        (true, _, OID_SHA256) => &signature::ECDSA_P256_SHA256_ASN1,
+        (true, _, OID_SHA384) if certificate.public_key.len() == 65 => &signature::ECDSA_P256_SHA384_ASN1,
+        (true, _, OID_SHA384) => &signature::ECDSA_P384_SHA384_ASN1,
+        (false, true, OID_SHA256) => &signature::RSA_PSS_2048_8192_SHA256,
+        (false, true, OID_SHA384) => &signature::RSA_PSS_2048_8192_SHA384,
+        (false, true, OID_SHA512) => &signature::RSA_PSS_2048_8192_SHA512,
+        (false, false, OID_SHA256) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (false, false, OID_SHA384) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (false, false, OID_SHA512) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        _ => return None,
+    };
+    Some(algorithm)
+}
+
+/// A BER element with its complete encoding
+#[derive(Debug, Clone)]
+pub(crate) struct Element<'a> {
+    pub any: Any<'a>,
+    pub raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    /// Parses `data` as exactly one element
+    pub fn single(data: &'a [u8]) -> Result<Self, CaExtractorError> {
+        match elements(data)?.as_slice() {
+            [element] => Ok(element.clone()),
+            _ => Err(invalid("expected a single ASN.1 element".to_string())),
+        }
+    }
+
+    /// Elements of a constructed element's content
+    pub fn children(&self) -> Result<Vec<Element<'a>>, CaExtractorError> {
+        if !self.any.header.is_constructed() {
+            return Err(invalid("expected a constructed ASN.1 element".to_string()));
+        }
+        elements(self.any.data)
+    }
+
+    /// Content of the first child, itself constructed
+    fn children_of_first(&self) -> Result<Vec<Element<'a>>, CaExtractorError> {
+        self.children()?
+            .first()
+            .ok_or_else(|| invalid("empty ASN.1 element".to_string()))?
+            .children()
+    }
+
+    /// The element wrapped in the explicit context specific tag `tag`
+    pub fn explicit(&self, tag: u32) -> Result<Element<'a>, CaExtractorError> {
+        if self.any.class() != Class::ContextSpecific || self.any.tag() != Tag(tag) {
+            return Err(invalid(format!("expected the context specific tag [{}]", tag)));
+        }
+        Element::single(self.any.data)
+    }
+}
+
+pub(crate) fn elements(mut data: &[u8]) -> Result<Vec<Element<'_>>, CaExtractorError> {
+    let mut result = Vec::new();
+    while !data.is_empty() {
+        let (rest, any) = Any::from_ber(data).map_err(|e| invalid(format!("malformed ASN.1: {}", e)))?;
+        result.push(Element {
+            any,
+            raw: &data[..data.len() - rest.len()],
+        });
+        data = rest;
+    }
+    Ok(result)
+}
+
+/// DER encoding of an element with a one-byte `tag` and `content`
+pub(crate) fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    match content.len() {
+        length @ 0..=0x7f => der.push(length as u8),
+        length => {
+            let bytes: Vec<u8> = length.to_be_bytes().into_iter().skip_while(|byte| *byte == 0).collect();
+            der.push(0x80 | bytes.len() as u8);
+            der.extend(bytes);
+        }
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+/// Parses the single element of the DER `value`
+fn first(value: &[u8]) -> Result<Element<'_>, CaExtractorError> {
+    Element::single(value)
+}
+
+pub(crate) fn oid(element: &Element) -> Result<String, CaExtractorError> {
+    element
+        .any
+        .as_oid()
+        .map(|oid| oid.to_id_string())
+        .map_err(|_| invalid("expected an OBJECT IDENTIFIER".to_string()))
+}
+
+/// Content of an OCTET STRING, primitive or constructed
+pub(crate) fn octets(element: &Element) -> Result<Vec<u8>, CaExtractorError> {
+    if element.any.tag() != Tag::OctetString && element.any.class() == Class::Universal {
+        return Err(invalid("expected an OCTET STRING".to_string()));
+    }
+    if element.any.header.is_constructed() {
+        let mut content = Vec::new();
+        for chunk in element.children()? {
+            content.extend(octets(&chunk)?);
+        }
+        Ok(content)
+    } else {
+        Ok(element.any.data.to_vec())
+    }
+}
+
+/// OID of an `AlgorithmIdentifier`
+pub(crate) fn algorithm(element: &Element) -> Result<String, CaExtractorError> {
+    element
+        .children()?
+        .first()
+        .ok_or_else(|| invalid("empty AlgorithmIdentifier".to_string()))
+        .and_then(oid)
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
@@ -0,0 +1,299 @@
+//! Semantic conformance rules of ETSI TS 119 612 that go beyond the schema.
+//!
+//! Each rule inspects the parsed [`TrustedList`] and reports findings with its
+//! rule ID, severity and the location of the offending element.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::etsi;
+use crate::model::{DigitalId, Location, ServiceInformation, TrustService, TrustServiceProvider, TrustedList};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// A rule violation found in a trusted list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub location: Location,
+    /// TSP and service the finding belongs to, if any
+    pub context: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} at {}", self.severity, self.rule, self.location)?;
+        if !self.context.is_empty() {
+            write!(f, " ({})", self.context)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A semantic conformance rule
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+    check: fn(&TrustedList, &mut Vec<Violation>),
+}
+
+/// What a rule reports before it is tagged with the rule's ID and severity
+struct Violation {
+    location: Location,
+    context: String,
+    message: String,
+}
+
+/// All rules, in the order they are evaluated
+pub static RULES: &[Rule] = &[
+    Rule {
+        id: "TL-001",
+        severity: Severity::Error,
+        description: "Service history instances are in descending order of status starting time",
+        check: check_history_order,
+    },
+    Rule {
+        id: "TL-002",
+        severity: Severity::Error,
+        description: "Service status URIs are valid for the service type",
+        check: check_status_uris,
+    },
+    Rule {
+        id: "TL-003",
+        severity: Severity::Error,
+        description: "ForeSignatures, ForeSeals and ForWebSiteAuthentication only qualify CA/QC services",
+        check: check_additional_information,
+    },
+    Rule {
+        id: "TL-004",
+        severity: Severity::Error,
+        description: "NextUpdate is later than ListIssueDateTime",
+        check: check_next_update,
+    },
+    Rule {
+        id: "TL-005",
+        severity: Severity::Error,
+        description: "Digital identities are not duplicated within a service or across services of the same type",
+        check: check_duplicate_identities,
+    },
+    Rule {
+        id: "TL-006",
+        severity: Severity::Error,
+        description: "TSLVersionIdentifier is supported and defines every service type used in the list",
+        check: check_version,
+    },
+];
+
+/// Evaluates every rule against `list`
+pub fn check(list: &TrustedList) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for rule in RULES {
+        let mut violations = Vec::new();
+        (rule.check)(list, &mut violations);
+        findings.extend(violations.into_iter().map(|violation| Finding {
+            rule: rule.id,
+            severity: rule.severity,
+            location: violation.location,
+            context: violation.context,
+            message: violation.message,
+        }));
+    }
+
+    findings
+}
+
+fn context(provider: &TrustServiceProvider, information: &ServiceInformation) -> String {
+    format!(
+        "TSP '{}', service '{}'",
+        provider.name().unwrap_or("<unnamed>"),
+        information.name().unwrap_or("<unnamed>")
+    )
+}
+
+/// Current information followed by every history instance of a service
+fn all_information(service: &TrustService) -> impl Iterator<Item = &ServiceInformation> {
+    std::iter::once(&service.information).chain(service.history.iter())
+}
+
+fn check_history_order(list: &TrustedList, out: &mut Vec<Violation>) {
+    for (provider, service) in list.services() {
+        let mut previous = &service.information;
+        for instance in &service.history {
+            if let (Some(newer), Some(older)) = (previous.status_starting_time, instance.status_starting_time) {
+                if older > newer {
+                    out.push(Violation {
+                        location: instance.location,
+                        context: context(provider, &service.information),
+                        message: format!(
+                            "history instance starting {} is newer than the preceding entry starting {}",
+                            older.to_rfc3339(),
+                            newer.to_rfc3339()
+                        ),
+                    });
+                }
+            }
+            previous = instance;
+        }
+    }
+}
+
+fn check_status_uris(list: &TrustedList, out: &mut Vec<Violation>) {
+    for (provider, service) in list.services() {
+        for (index, information) in all_information(service).enumerate() {
+            let qualified = etsi::is_qualified_service_type(&information.service_type);
+            let current_statuses: &[&str] = if qualified {
+                &[etsi::STATUS_GRANTED, etsi::STATUS_WITHDRAWN]
+            } else {
+                &[
+                    etsi::STATUS_RECOGNISED_AT_NATIONAL_LEVEL,
+                    etsi::STATUS_DEPRECATED_AT_NATIONAL_LEVEL,
+                ]
+            };
+
+            let status = information.status.as_str();
+            let is_history = index > 0;
+            if current_statuses.contains(&status) || (is_history && etsi::LEGACY_STATUSES.contains(&status)) {
+                continue;
+            }
+
+            out.push(Violation {
+                location: information.location,
+                context: context(provider, &service.information),
+                message: format!(
+                    "status '{}' is not valid for service type '{}'",
+                    etsi::short_name(status),
+                    etsi::short_name(&information.service_type)
+                ),
+            });
+        }
+    }
+}
+
+fn check_additional_information(list: &TrustedList, out: &mut Vec<Violation>) {
+    for (provider, service) in list.services() {
+        for information in all_information(service) {
+            if information.service_type == etsi::SVCTYPE_CA_QC {
+                continue;
+            }
+            for uri in &information.additional_information {
+                if etsi::CA_QC_PURPOSES.contains(&uri.as_str()) {
+                    out.push(Violation {
+                        location: information.location,
+                        context: context(provider, &service.information),
+                        message: format!(
+                            "additional service information '{}' on a '{}' service",
+                            etsi::short_name(uri),
+                            etsi::short_name(&information.service_type)
+                        ),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn check_next_update(list: &TrustedList, out: &mut Vec<Violation>) {
+    if let (Some(issued), Some(next_update)) = (list.issue_date, list.next_update) {
+        if next_update <= issued {
+            out.push(Violation {
+                location: list.location,
+                context: String::new(),
+                message: format!(
+                    "NextUpdate {} is not after ListIssueDateTime {}",
+                    next_update.to_rfc3339(),
+                    issued.to_rfc3339()
+                ),
+            });
+        }
+    }
+}
+
+fn check_duplicate_identities(list: &TrustedList, out: &mut Vec<Violation>) {
+    // (service type, certificate) -> context of the first service listing it
+    let mut seen: HashMap<(&str, &[u8]), String> = HashMap::new();
+
+    for (provider, service) in list.services() {
+        let information = &service.information;
+        let here = context(provider, information);
+        let mut in_service: Vec<&[u8]> = Vec::new();
+
+        for id in &information.digital_ids {
+            let DigitalId::Certificate(der) = id else {
+                continue;
+            };
+
+            if in_service.contains(&der.as_slice()) {
+                out.push(Violation {
+                    location: information.location,
+                    context: here.clone(),
+                    message: "the same certificate is listed twice in this service".to_string(),
+                });
+                continue;
+            }
+            in_service.push(der);
+
+            let key = (information.service_type.as_str(), der.as_slice());
+            match seen.get(&key) {
+                Some(first) => out.push(Violation {
+                    location: information.location,
+                    context: here.clone(),
+                    message: format!("certificate is also the digital identity of {}", first),
+                }),
+                None => {
+                    seen.insert(key, here.clone());
+                }
+            }
+        }
+    }
+}
+
+fn check_version(list: &TrustedList, out: &mut Vec<Violation>) {
+    let Some(version) = list.version else {
+        return;
+    };
+    if !etsi::TSL_VERSIONS.contains(&version) {
+        out.push(Violation {
+            location: list.location,
+            context: String::new(),
+            message: format!("TSLVersionIdentifier {} is not supported", version),
+        });
+        return;
+    }
+
+    for (provider, service) in list.services() {
+        for information in all_information(service) {
+            let Some(def) = etsi::service_type(&information.service_type) else {
+                continue;
+            };
+            if def.since_version > version {
+                out.push(Violation {
+                    location: information.location,
+                    context: context(provider, &service.information),
+                    message: format!(
+                        "service type '{}' requires TSLVersionIdentifier {}, the list declares {}",
+                        etsi::short_name(def.uri),
+                        def.since_version,
+                        version
+                    ),
+                });
+            }
+        }
+    }
+}
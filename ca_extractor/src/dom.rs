@@ -0,0 +1,148 @@
+use xml::reader::XmlEvent;
+
+use crate::error::{CaExtractorError, ErrorContext};
+use crate::limits::ParseLimits;
+
+/// Namespace of the ETSI TS 119 612 trusted list schema
+pub const TSL_NS: &str = "http://uri.etsi.org/02231/v2#";
+/// Namespace of the TS 119 612 additional types schema
+pub const TSLX_NS: &str = "http://uri.etsi.org/02231/v2/additionaltypes#";
+/// Namespace of the service information extensions schema
+pub const SIE_NS: &str = "http://uri.etsi.org/TrstSvc/SvcInfoExt/eSigDir-1999-93-EC-TrustedList/#";
+/// Namespace of the XML digital signature schema
+pub const DS_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// Rejects content that is not an XML document, such as an error the API
+/// returned in JSON. The error points at the first non-blank character, so
+/// that `--explain-errors` shows how the content starts.
+pub fn check_xml_content(content: &str) -> Result<(), CaExtractorError> {
+    let start = content.trim_start();
+    if start.starts_with('<') {
+        return Ok(());
+    }
+    let message = if start.is_empty() {
+        "API returned an empty response"
+    } else if start.starts_with('{') || start.starts_with('[') {
+        "API returned non-XML content, possibly JSON"
+    } else {
+        "API returned unrecognized content, not an XML document"
+    };
+    let blank = &content[..content.len() - start.len()];
+    let line = blank.matches('\n').count() as u64 + 1;
+    let column = blank.rsplit('\n').next().unwrap_or_default().chars().count() as u64 + 1;
+    Err(CaExtractorError::InvalidResponseFormat(message.to_string())
+        .with_context(ErrorContext::at(line, column))
+        .with_snippet_from(content))
+}
+
+/// An element of a parsed XML document, with its position in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    /// Local name, without prefix
+    pub name: String,
+    /// Namespace URI, if any
+    pub namespace: Option<String>,
+    /// Attributes as (qualified name, value) pairs, e.g. `("xml:lang", "en")`
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// Concatenated text directly contained in this element
+    pub text: String,
+    /// One-based line of the start tag
+    pub line: u64,
+    /// One-based column of the start tag
+    pub column: u64,
+}
+
+impl Element {
+    /// Returns the value of the attribute with the given qualified name
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the first child element with the given local name
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Iterates over the child elements with the given local name
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Follows a path of local names, returning the first match
+    pub fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |element, name| element.child(name))
+    }
+
+    /// Trimmed text content
+    pub fn text(&self) -> &str {
+        self.text.trim()
+    }
+
+    /// Whether the element lives in the given namespace
+    pub fn in_namespace(&self, namespace: &str) -> bool {
+        self.namespace.as_deref() == Some(namespace)
+    }
+}
+
+/// Parses `xml_content` into an element tree, enforcing the given limits
+pub fn parse_document(xml_content: &str, limits: &ParseLimits) -> Result<Element, CaExtractorError> {
+    let mut events = limits.event_reader(xml_content);
+    let mut stack: Vec<Element> = Vec::new();
+
+    while let Some(event) = events.next() {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let (line, column) = events.location();
+                stack.push(Element {
+                    name: name.local_name,
+                    namespace: name.namespace,
+                    attributes: attributes
+                        .into_iter()
+                        .map(|attribute| {
+                            let key = match attribute.name.prefix {
+                                Some(prefix) => format!("{}:{}", prefix, attribute.name.local_name),
+                                None => attribute.name.local_name,
+                            };
+                            (key, attribute.value)
+                        })
+                        .collect(),
+                    children: Vec::new(),
+                    text: String::new(),
+                    line,
+                    column,
+                });
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&text);
+                    // Text split across CDATA sections passes the per-node limit
+                    if current.text.len() > limits.max_text_size {
+                        let (line, column) = events.location();
+                        let message =
+                            format!("text of <{}> larger than {} bytes", current.name, limits.max_text_size);
+                        let context = ErrorContext::at(line, column).with_snippet_from(xml_content);
+                        return Err(CaExtractorError::SafetyLimitExceeded(message).with_context(context));
+                    }
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or_else(|| {
+                    CaExtractorError::InvalidResponseFormat("Unbalanced XML end tag".to_string())
+                })?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(CaExtractorError::InvalidResponseFormat(
+        "XML document has no root element".to_string(),
+    ))
+}
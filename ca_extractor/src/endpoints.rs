@@ -0,0 +1,130 @@
+//! Network endpoints of the services CA certificates are extracted from: the
+//! `ServiceSupplyPoints` and `TSPServiceDefinitionURI`s of each service and
+//! the CRL distribution points and OCSP responders its certificates name.
+//! These are what a proxy or firewall has to allow for revocation checking.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::extractor::ExtractedCertificate;
+use crate::model::{TrustService, TrustServiceProvider};
+use crate::x509::Certificate;
+
+/// Where an endpoint was found
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EndpointKind {
+    /// A `ServiceSupplyPoint` of the service
+    SupplyPoint,
+    /// A `TSPServiceDefinitionURI` of the service, e.g. its CPS
+    ServiceDefinition,
+    /// A CRL distribution point of a certificate of the service
+    CrlDistributionPoint,
+    /// An OCSP responder in the authority information access of a certificate of the service
+    OcspResponder,
+}
+
+impl fmt::Display for EndpointKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndpointKind::SupplyPoint => write!(f, "supply-point"),
+            EndpointKind::ServiceDefinition => write!(f, "service-definition"),
+            EndpointKind::CrlDistributionPoint => write!(f, "crl"),
+            EndpointKind::OcspResponder => write!(f, "ocsp"),
+        }
+    }
+}
+
+/// A URL published by a service or named in one of its certificates
+#[derive(Debug, Clone)]
+pub struct Endpoint<'a> {
+    pub url: String,
+    pub kind: EndpointKind,
+    pub provider: &'a TrustServiceProvider,
+    pub service: &'a TrustService,
+}
+
+impl Endpoint<'_> {
+    /// Host name of the URL, followed by the port when it is not the
+    /// scheme's default. `None` for URLs without a host.
+    pub fn host(&self) -> Option<String> {
+        let url = reqwest::Url::parse(&self.url).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        Some(match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let information = &self.service.information;
+        json!({
+            "url": self.url,
+            "kind": self.kind.to_string(),
+            "host": self.host(),
+            "tsp": self.provider.name(),
+            "service": information.name(),
+            "service_type": information.service_type,
+        })
+    }
+}
+
+/// Endpoints of the services `certificates` were selected from, grouped by
+/// service in selection order. Each URL is listed once per service, under
+/// the first kind it was found as.
+pub fn collect<'a>(certificates: &[ExtractedCertificate<'a>]) -> Vec<Endpoint<'a>> {
+    let mut services: Vec<(&'a TrustServiceProvider, &'a TrustService)> = Vec::new();
+    for certificate in certificates {
+        if !services.iter().any(|(_, service)| std::ptr::eq(*service, certificate.service)) {
+            services.push((certificate.provider, certificate.service));
+        }
+    }
+
+    let mut endpoints: Vec<Endpoint<'a>> = Vec::new();
+    for (provider, service) in services {
+        let information = &service.information;
+        let mut found: Vec<(String, EndpointKind)> = Vec::new();
+        found.extend(information.service_supply_points.iter().map(|url| (url.clone(), EndpointKind::SupplyPoint)));
+        found.extend(
+            information
+                .service_definition_uris
+                .iter()
+                .map(|uri| (uri.value.clone(), EndpointKind::ServiceDefinition)),
+        );
+        let parsed = certificates
+            .iter()
+            .filter(|certificate| std::ptr::eq(certificate.service, service))
+            .filter_map(|certificate| Certificate::from_der(certificate.der).ok());
+        for certificate in parsed {
+            found.extend(
+                certificate
+                    .crl_distribution_points
+                    .into_iter()
+                    .map(|url| (url, EndpointKind::CrlDistributionPoint)),
+            );
+            found.extend(certificate.ocsp_responders.into_iter().map(|url| (url, EndpointKind::OcspResponder)));
+        }
+
+        let start = endpoints.len();
+        for (url, kind) in found {
+            if url.is_empty() || endpoints[start..].iter().any(|endpoint| endpoint.url == url) {
+                continue;
+            }
+            endpoints.push(Endpoint {
+                url,
+                kind,
+                provider,
+                service,
+            });
+        }
+    }
+    endpoints
+}
+
+/// Sorted host list of `endpoints`, without duplicates
+pub fn hosts(endpoints: &[Endpoint]) -> Vec<String> {
+    let mut hosts: Vec<String> = endpoints.iter().filter_map(Endpoint::host).collect();
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
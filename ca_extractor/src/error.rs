@@ -1,6 +1,10 @@
+use std::fmt;
 use std::io;
 use thiserror::Error;
 
+use crate::chain::PathFailure;
+use crate::schema::SchemaViolation;
+
 #[derive(Error, Debug)]
 pub enum CaExtractorError {
     #[error("IO error: {0}")]
@@ -32,4 +36,307 @@ pub enum CaExtractorError {
     
     #[error("API returned error: {status} - {message}")]
     ApiError { status: u16, message: String },
+
+    #[error("Input rejected by safety limit: {0}")]
+    SafetyLimitExceeded(String),
+
+    #[error("Trusted list does not conform to the ETSI TS 119 612 schema ({} violation(s))", .0.len())]
+    SchemaValidationFailed(Vec<SchemaViolation>),
+
+    #[error("Trusted list violates {errors} ETSI TS 119 612 conformance rule(s)")]
+    ConformanceFailed { errors: usize },
+
+    #[error("No valid certification path to a trusted list service ({} candidate failure(s))", .0.len())]
+    PathValidationFailed(Vec<PathFailure>),
+
+    #[error("Signature validation failed: {0}")]
+    SignatureInvalid(String),
+
+    #[error("Not qualified: {0}")]
+    NotQualified(String),
+
+    #[error("Certificate revoked: {0}")]
+    CertificateRevoked(String),
+
+    #[error("{cause} [{context}]")]
+    WithContext {
+        context: Box<ErrorContext>,
+        #[source]
+        cause: Box<CaExtractorError>,
+    },
+}
+
+impl CaExtractorError {
+    /// Process exit code for this error. Context wrappers report the code
+    /// of the error they wrap.
+    ///
+    /// | Code | Meaning |
+    /// |------|---------|
+    /// | 1    | Certificate extraction failed for another reason |
+    /// | 2    | Invalid command line input (service type, country code) |
+    /// | 3    | No certificates matched the selection |
+    /// | 4    | Network failure or API error status |
+    /// | 5    | The API response is not XML |
+    /// | 6    | The document is not well-formed XML |
+    /// | 7    | A download or parsing safety limit was exceeded |
+    /// | 8    | The trusted list violates the schema |
+    /// | 9    | The trusted list violates conformance rules |
+    /// | 10   | A certificate in the trusted list or given on the command line is malformed |
+    /// | 11   | Local I/O failure |
+    /// | 12   | The certificate does not chain to a trusted list service |
+    /// | 13   | A signature, time-stamp or binding is malformed or does not verify |
+    /// | 14   | The signer or TSA is not a granted qualified service at the relevant time |
+    /// | 15   | A certificate of the validated path is revoked |
+    pub fn exit_code(&self) -> u8 {
+        match self.root_cause() {
+            CaExtractorError::CertificateExtractionError(_) => 1,
+            CaExtractorError::InvalidServiceType(_) | CaExtractorError::InvalidCountryCode(_) => 2,
+            CaExtractorError::NoCertificatesFound { .. } => 3,
+            CaExtractorError::RequestError(_) | CaExtractorError::ApiError { .. } => 4,
+            CaExtractorError::InvalidResponseFormat(_) => 5,
+            CaExtractorError::XmlError(_) => 6,
+            CaExtractorError::SafetyLimitExceeded(_) => 7,
+            CaExtractorError::SchemaValidationFailed(_) => 8,
+            CaExtractorError::ConformanceFailed { .. } => 9,
+            CaExtractorError::InvalidCertificateFormat(_) => 10,
+            CaExtractorError::IoError(_) => 11,
+            CaExtractorError::PathValidationFailed(_) => 12,
+            CaExtractorError::SignatureInvalid(_) => 13,
+            CaExtractorError::NotQualified(_) => 14,
+            CaExtractorError::CertificateRevoked(_) => 15,
+            CaExtractorError::WithContext { cause, .. } => cause.exit_code(),
+        }
+    }
+
+    /// Stable, machine-readable name of the error kind
+    pub fn kind(&self) -> &'static str {
+        match self.root_cause() {
+            CaExtractorError::IoError(_) => "io_error",
+            CaExtractorError::RequestError(_) => "request_error",
+            CaExtractorError::XmlError(_) => "xml_error",
+            CaExtractorError::InvalidServiceType(_) => "invalid_service_type",
+            CaExtractorError::CertificateExtractionError(_) => "certificate_extraction_error",
+            CaExtractorError::InvalidCountryCode(_) => "invalid_country_code",
+            CaExtractorError::NoCertificatesFound { .. } => "no_certificates_found",
+            CaExtractorError::InvalidCertificateFormat(_) => "invalid_certificate_format",
+            CaExtractorError::InvalidResponseFormat(_) => "invalid_response_format",
+            CaExtractorError::ApiError { .. } => "api_error",
+            CaExtractorError::SafetyLimitExceeded(_) => "safety_limit_exceeded",
+            CaExtractorError::SchemaValidationFailed(_) => "schema_validation_failed",
+            CaExtractorError::ConformanceFailed { .. } => "conformance_failed",
+            CaExtractorError::PathValidationFailed(_) => "path_validation_failed",
+            CaExtractorError::SignatureInvalid(_) => "signature_invalid",
+            CaExtractorError::NotQualified(_) => "not_qualified",
+            CaExtractorError::CertificateRevoked(_) => "certificate_revoked",
+            CaExtractorError::WithContext { cause, .. } => cause.kind(),
+        }
+    }
+
+    /// Structured representation for `--error-format json`
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut causes = Vec::new();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            causes.push(serde_json::Value::String(cause.to_string()));
+            source = cause.source();
+        }
+
+        let context = self.context().map(|context| {
+            serde_json::json!({
+                "source": context.source,
+                "line": context.line,
+                "column": context.column,
+                "tsp": context.tsp,
+                "service": context.service,
+                "snippet": context.snippet,
+            })
+        });
+
+        let mut error = serde_json::json!({
+            "kind": self.kind(),
+            "exit_code": self.exit_code(),
+            "message": self.to_string(),
+            "causes": causes,
+            "context": context,
+        });
+
+        if let CaExtractorError::SchemaValidationFailed(violations) = self.root_cause() {
+            error["violations"] = violations
+                .iter()
+                .map(|v| {
+                    serde_json::json!({
+                        "line": v.line,
+                        "column": v.column,
+                        "element": v.element,
+                        "message": v.message,
+                    })
+                })
+                .collect();
+        }
+
+        if let CaExtractorError::PathValidationFailed(failures) = self.root_cause() {
+            error["path_failures"] = failures.iter().map(|f| serde_json::Value::String(f.to_string())).collect();
+        }
+
+        serde_json::json!({ "error": error })
+    }
+
+    /// Attaches context to this error. Fields already known are kept, so
+    /// inner layers can record a position and outer layers the source.
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            CaExtractorError::WithContext { context: mut existing, cause } => {
+                existing.merge(context);
+                CaExtractorError::WithContext { context: existing, cause }
+            }
+            other => CaExtractorError::WithContext {
+                context: Box::new(context),
+                cause: Box::new(other),
+            },
+        }
+    }
+
+    /// Adds an excerpt of `document` to a positioned error that has none yet
+    pub fn with_snippet_from(self, document: &str) -> Self {
+        match self {
+            CaExtractorError::WithContext { context, cause } => CaExtractorError::WithContext {
+                context: Box::new(context.with_snippet_from(document)),
+                cause,
+            },
+            other => other,
+        }
+    }
+
+    /// Context attached with `with_context`, if any
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            CaExtractorError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without any attached context
+    pub fn root_cause(&self) -> &CaExtractorError {
+        match self {
+            CaExtractorError::WithContext { cause, .. } => cause.root_cause(),
+            other => other,
+        }
+    }
+}
+
+/// Where in the input an error occurred
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Country, URL or file the document came from
+    pub source: Option<String>,
+    /// One-based line in the document
+    pub line: Option<u64>,
+    /// One-based column in the document
+    pub column: Option<u64>,
+    /// Name of the enclosing trust service provider
+    pub tsp: Option<String>,
+    /// Name of the enclosing trust service
+    pub service: Option<String>,
+    /// Excerpt of the document around `line` and `column`
+    pub snippet: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn at(line: u64, column: u64) -> Self {
+        ErrorContext {
+            line: Some(line),
+            column: Some(column),
+            ..Default::default()
+        }
+    }
+
+    pub fn source(source: impl Into<String>) -> Self {
+        ErrorContext {
+            source: Some(source.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_tsp(mut self, tsp: impl Into<String>) -> Self {
+        self.tsp = Some(tsp.into());
+        self
+    }
+
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    /// Captures an excerpt of `document` around this context's position
+    pub fn with_snippet_from(mut self, document: &str) -> Self {
+        if let (None, Some(line), Some(column)) = (&self.snippet, self.line, self.column) {
+            self.snippet = Some(snippet(document, line, column));
+        }
+        self
+    }
+
+    fn merge(&mut self, other: ErrorContext) {
+        self.source = self.source.take().or(other.source);
+        self.line = self.line.or(other.line);
+        self.column = self.column.or(other.column);
+        self.tsp = self.tsp.take().or(other.tsp);
+        self.service = self.service.take().or(other.service);
+        self.snippet = self.snippet.take().or(other.snippet);
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(source) = &self.source {
+            parts.push(format!("in {}", source));
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => parts.push(format!("at line {}, column {}", line, column)),
+            (Some(line), None) => parts.push(format!("at line {}", line)),
+            _ => {}
+        }
+        if let Some(tsp) = &self.tsp {
+            parts.push(format!("TSP '{}'", tsp));
+        }
+        if let Some(service) = &self.service {
+            parts.push(format!("service '{}'", service));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Number of lines shown before and after the offending line
+const SNIPPET_CONTEXT_LINES: u64 = 2;
+/// Maximum number of characters shown on each side of the offending column
+const SNIPPET_HALF_WIDTH: usize = 60;
+
+/// Renders the lines around `line` with a caret under `column`.
+///
+/// Long lines, common in minified trusted lists, are cut to a window around
+/// the column.
+pub fn snippet(document: &str, line: u64, column: u64) -> String {
+    let first = line.saturating_sub(SNIPPET_CONTEXT_LINES).max(1);
+    let last = line + SNIPPET_CONTEXT_LINES;
+    let column = column.saturating_sub(1) as usize;
+    let start = column.saturating_sub(SNIPPET_HALF_WIDTH);
+    let width = last.to_string().len();
+
+    let mut out = String::new();
+    for (number, text) in (1u64..).zip(document.lines()) {
+        if number < first {
+            continue;
+        }
+        if number > last {
+            break;
+        }
+
+        let excerpt: String = text.chars().skip(start).take(2 * SNIPPET_HALF_WIDTH).collect();
+        let marker = if number == line { '>' } else { ' ' };
+        out.push_str(&format!("{} {:>width$} | {}\n", marker, number, excerpt, width = width));
+        if number == line {
+            out.push_str(&format!("  {:>width$} | {}^\n", "", " ".repeat(column - start), width = width));
+        }
+    }
+    out
 }
@@ -0,0 +1,311 @@
+//! URIs defined by ETSI TS 119 612 for service types, statuses and
+//! additional service information.
+
+/// Prefix shared by all service type identifiers
+pub const SVCTYPE_PREFIX: &str = "http://uri.etsi.org/TrstSvc/Svctype/";
+/// Prefix shared by all service status identifiers
+pub const SVCSTATUS_PREFIX: &str = "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/";
+/// Prefix shared by all service information extension identifiers
+pub const SVCINFOEXT_PREFIX: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/";
+
+pub const SVCTYPE_CA_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/CA/QC";
+pub const SVCTYPE_CA_PKC: &str = "http://uri.etsi.org/TrstSvc/Svctype/CA/PKC";
+pub const SVCTYPE_OCSP: &str = "http://uri.etsi.org/TrstSvc/Svctype/Certstatus/OCSP";
+pub const SVCTYPE_CRL: &str = "http://uri.etsi.org/TrstSvc/Svctype/Certstatus/CRL";
+pub const SVCTYPE_TSA: &str = "http://uri.etsi.org/TrstSvc/Svctype/TSA";
+pub const SVCTYPE_EDS: &str = "http://uri.etsi.org/TrstSvc/Svctype/EDS";
+pub const SVCTYPE_EDS_REM: &str = "http://uri.etsi.org/TrstSvc/Svctype/EDS/REM";
+pub const SVCTYPE_PSES: &str = "http://uri.etsi.org/TrstSvc/Svctype/PSES";
+pub const SVCTYPE_ADES_VALIDATION: &str = "http://uri.etsi.org/TrstSvc/Svctype/AdESValidation";
+pub const SVCTYPE_OCSP_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/Certstatus/OCSP/QC";
+pub const SVCTYPE_CRL_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/Certstatus/CRL/QC";
+pub const SVCTYPE_TSA_QTST: &str = "http://uri.etsi.org/TrstSvc/Svctype/TSA/QTST";
+pub const SVCTYPE_EDS_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/EDS/Q";
+pub const SVCTYPE_EDS_REM_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/EDS/REM/Q";
+pub const SVCTYPE_PSES_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/PSES/Q";
+pub const SVCTYPE_QESVALIDATION_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/QESValidation/Q";
+pub const SVCTYPE_NATIONAL_ROOT_CA_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/NationalRootCA-QC";
+pub const SVCTYPE_QEAA: &str = "http://uri.etsi.org/TrstSvc/Svctype/EAA/Q";
+pub const SVCTYPE_PUB_EAA: &str = "http://uri.etsi.org/TrstSvc/Svctype/EAA/Pub-EAA";
+pub const SVCTYPE_ARCHIV_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/Archiv/Q";
+pub const SVCTYPE_LEDGER_Q: &str = "http://uri.etsi.org/TrstSvc/Svctype/Ledger/Q";
+pub const SVCTYPE_EAA: &str = "http://uri.etsi.org/TrstSvc/Svctype/EAA";
+pub const SVCTYPE_ARCHIV: &str = "http://uri.etsi.org/TrstSvc/Svctype/Archiv";
+pub const SVCTYPE_LEDGER: &str = "http://uri.etsi.org/TrstSvc/Svctype/Ledger";
+
+/// `TSLVersionIdentifier` of lists following TS 119 612 v2.1.1 (eIDAS)
+pub const TSL_VERSION_EIDAS: u32 = 5;
+/// `TSLVersionIdentifier` of lists following TS 119 612 v2.3.1 (eIDAS 2.0)
+pub const TSL_VERSION_EIDAS2: u32 = 6;
+/// Every supported `TSLVersionIdentifier`
+pub const TSL_VERSIONS: &[u32] = &[TSL_VERSION_EIDAS, TSL_VERSION_EIDAS2];
+
+/// A service type identifier defined by ETSI TS 119 612 clause 5.5.1
+#[derive(Debug)]
+pub struct ServiceTypeDef {
+    pub uri: &'static str,
+    /// Whether services of this type take the qualified statuses (`granted`,
+    /// `withdrawn`) rather than the national ones
+    pub qualified: bool,
+    pub description: &'static str,
+    /// First `TSLVersionIdentifier` defining this service type
+    pub since_version: u32,
+}
+
+const fn svctype(uri: &'static str, qualified: bool, description: &'static str) -> ServiceTypeDef {
+    ServiceTypeDef {
+        uri,
+        qualified,
+        description,
+        since_version: TSL_VERSION_EIDAS,
+    }
+}
+
+/// A service type introduced for the trust services added by eIDAS 2.0
+const fn svctype_eidas2(uri: &'static str, qualified: bool, description: &'static str) -> ServiceTypeDef {
+    ServiceTypeDef {
+        since_version: TSL_VERSION_EIDAS2,
+        ..svctype(uri, qualified, description)
+    }
+}
+
+/// Every service type identifier of ETSI TS 119 612 v2.1.1 and v2.3.1
+pub static SERVICE_TYPES: &[ServiceTypeDef] = &[
+    svctype(SVCTYPE_CA_QC, true, "Qualified certificate issuing CA"),
+    svctype(SVCTYPE_OCSP_QC, true, "OCSP responder for qualified certificates"),
+    svctype(SVCTYPE_CRL_QC, true, "CRL issuer for qualified certificates"),
+    svctype(SVCTYPE_TSA_QTST, true, "Qualified electronic time stamp generation"),
+    svctype(SVCTYPE_EDS_Q, true, "Qualified electronic delivery service"),
+    svctype(SVCTYPE_EDS_REM_Q, true, "Qualified registered electronic mail delivery service"),
+    svctype(SVCTYPE_PSES_Q, true, "Qualified preservation service for qualified signatures or seals"),
+    svctype(SVCTYPE_QESVALIDATION_Q, true, "Qualified validation service for qualified signatures or seals"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/RemoteQSigCDManagement/Q",
+        true,
+        "Qualified remote signature creation device management",
+    ),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/RemoteQSealCDManagement/Q",
+        true,
+        "Qualified remote seal creation device management",
+    ),
+    svctype(SVCTYPE_NATIONAL_ROOT_CA_QC, true, "National root CA for qualified certificates"),
+    svctype(SVCTYPE_CA_PKC, false, "Non-qualified public key certificate issuing CA"),
+    svctype(SVCTYPE_OCSP, false, "OCSP responder for non-qualified certificates"),
+    svctype(SVCTYPE_CRL, false, "CRL issuer for non-qualified certificates"),
+    svctype(SVCTYPE_TSA, false, "Non-qualified time stamping authority"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/TSA/TSS-QC",
+        false,
+        "Time stamping service supporting qualified certificates",
+    ),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/TSA/TSS-AdESQCandQES",
+        false,
+        "Time stamping service supporting AdES with qualified certificates and QES",
+    ),
+    svctype(SVCTYPE_EDS, false, "Non-qualified electronic delivery service"),
+    svctype(SVCTYPE_EDS_REM, false, "Non-qualified registered electronic mail delivery service"),
+    svctype(SVCTYPE_PSES, false, "Non-qualified preservation service"),
+    svctype(SVCTYPE_ADES_VALIDATION, false, "Non-qualified validation service"),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/AdESGeneration", false, "Non-qualified signature generation service"),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/RA", false, "Registration authority"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/RA/nothavingPKIid",
+        false,
+        "Registration authority without its own PKI identity",
+    ),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/ACA", false, "Attribute certification authority"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/SignaturePolicyAuthority",
+        false,
+        "Signature policy authority",
+    ),
+    svctype(SVCTYPE_ARCHIV, false, "Archival service"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/Archiv/nothavingPKIid",
+        false,
+        "Archival service without its own PKI identity",
+    ),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/IdV", false, "Identity verification service"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/IdV/nothavingPKIid",
+        false,
+        "Identity verification service without its own PKI identity",
+    ),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/KEscrow", false, "Key escrow service"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/KEscrow/nothavingPKIid",
+        false,
+        "Key escrow service without its own PKI identity",
+    ),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/PPwd", false, "Personal password based identity service"),
+    svctype(
+        "http://uri.etsi.org/TrstSvc/Svctype/PPwd/nothavingPKIid",
+        false,
+        "Personal password based identity service without its own PKI identity",
+    ),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/TLIssuer", false, "Trusted list issuer"),
+    svctype("http://uri.etsi.org/TrstSvc/Svctype/unspecified", false, "Unspecified service type"),
+    svctype_eidas2(SVCTYPE_QEAA, true, "Qualified electronic attestation of attributes issuance"),
+    svctype_eidas2(
+        SVCTYPE_PUB_EAA,
+        true,
+        "Electronic attestation of attributes issued by or on behalf of a public sector body",
+    ),
+    svctype_eidas2(SVCTYPE_EAA, false, "Non-qualified electronic attestation of attributes issuance"),
+    svctype_eidas2(SVCTYPE_ARCHIV_Q, true, "Qualified electronic archiving service"),
+    svctype_eidas2(SVCTYPE_LEDGER_Q, true, "Qualified electronic ledger"),
+    svctype_eidas2(SVCTYPE_LEDGER, false, "Non-qualified electronic ledger"),
+];
+
+pub const STATUS_GRANTED: &str = "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/granted";
+pub const STATUS_WITHDRAWN: &str = "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/withdrawn";
+pub const STATUS_RECOGNISED_AT_NATIONAL_LEVEL: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/recognisedatnationallevel";
+pub const STATUS_DEPRECATED_AT_NATIONAL_LEVEL: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/deprecatedatnationallevel";
+
+/// Statuses used before the eIDAS regulation applied (1 July 2016). They may
+/// still appear in service history.
+pub const LEGACY_STATUSES: &[&str] = &[
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/undersupervision",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/supervisionincessation",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/supervisionceased",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/supervisionrevoked",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/accredited",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/accreditationceased",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/accreditationrevoked",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/setbynationallaw",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/deprecatedbynationallaw",
+];
+
+/// Statuses under which a CA/QC service issues qualified certificates, the
+/// eIDAS `granted` status and its pre-eIDAS equivalents (ETSI TS 119 615)
+pub const GRANTED_STATUSES: &[&str] = &[
+    STATUS_GRANTED,
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/undersupervision",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/supervisionincessation",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/accredited",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/setbynationallaw",
+];
+
+/// Whether a service with `status` is in force: granted, under a legacy
+/// equivalent of granted, or recognised at national level
+pub fn is_in_force(status: &str) -> bool {
+    GRANTED_STATUSES.contains(&status) || status == STATUS_RECOGNISED_AT_NATIONAL_LEVEL
+}
+
+pub const ASI_FOR_ESIGNATURES: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/ForeSignatures";
+pub const ASI_FOR_ESEALS: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/ForeSeals";
+pub const ASI_FOR_WEB_SITE_AUTHENTICATION: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/ForWebSiteAuthentication";
+pub const ASI_ROOT_CA_QC: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/RootCA-QC";
+
+/// Every additional service information URI of ETSI TS 119 612 v2.1.1
+pub const ADDITIONAL_INFORMATION_URIS: &[&str] = &[
+    ASI_FOR_ESIGNATURES,
+    ASI_FOR_ESEALS,
+    ASI_FOR_WEB_SITE_AUTHENTICATION,
+    ASI_ROOT_CA_QC,
+];
+
+/// Additional service information URIs that narrow the purpose of a CA/QC service
+pub const CA_QC_PURPOSES: &[&str] = &[
+    ASI_FOR_ESIGNATURES,
+    ASI_FOR_ESEALS,
+    ASI_FOR_WEB_SITE_AUTHENTICATION,
+];
+
+pub const QUALIFIER_QC_WITH_SSCD: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCWithSSCD";
+pub const QUALIFIER_QC_NO_SSCD: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCNoSSCD";
+pub const QUALIFIER_QC_SSCD_STATUS_AS_IN_CERT: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCSSCDStatusAsInCert";
+pub const QUALIFIER_QC_WITH_QSCD: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCWithQSCD";
+pub const QUALIFIER_QC_NO_QSCD: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCNoQSCD";
+pub const QUALIFIER_QC_QSCD_STATUS_AS_IN_CERT: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCQSCDStatusAsInCert";
+pub const QUALIFIER_QC_QSCD_MANAGED_ON_BEHALF: &str =
+    "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCQSCDManagedOnBehalf";
+pub const QUALIFIER_QC_FOR_LEGAL_PERSON: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCForLegalPerson";
+pub const QUALIFIER_QC_FOR_ESIG: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCForESig";
+pub const QUALIFIER_QC_FOR_ESEAL: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCForESeal";
+pub const QUALIFIER_QC_FOR_WSA: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCForWSA";
+pub const QUALIFIER_NOT_QUALIFIED: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/NotQualified";
+pub const QUALIFIER_QC_STATEMENT: &str = "http://uri.etsi.org/TrstSvc/TrustedList/SvcInfoExt/QCStatement";
+
+/// Every qualifier URI of a `sie:Qualifications` extension
+pub const QUALIFIERS: &[&str] = &[
+    QUALIFIER_QC_WITH_SSCD,
+    QUALIFIER_QC_NO_SSCD,
+    QUALIFIER_QC_SSCD_STATUS_AS_IN_CERT,
+    QUALIFIER_QC_WITH_QSCD,
+    QUALIFIER_QC_NO_QSCD,
+    QUALIFIER_QC_QSCD_STATUS_AS_IN_CERT,
+    QUALIFIER_QC_QSCD_MANAGED_ON_BEHALF,
+    QUALIFIER_QC_FOR_LEGAL_PERSON,
+    QUALIFIER_QC_FOR_ESIG,
+    QUALIFIER_QC_FOR_ESEAL,
+    QUALIFIER_QC_FOR_WSA,
+    QUALIFIER_NOT_QUALIFIED,
+    QUALIFIER_QC_STATEMENT,
+];
+
+/// Resolves a qualifier given as URI or as its short name, e.g. `QCWithQSCD`
+pub fn qualifier_uri(name: &str) -> Option<&'static str> {
+    QUALIFIERS
+        .iter()
+        .find(|uri| **uri == name || short_name(uri) == name)
+        .copied()
+}
+
+/// A kind of trust service that can be selected for extraction: a service
+/// type, optionally narrowed by an additional service information URI
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ServiceSelector<'a> {
+    pub service_type: &'a str,
+    pub additional_information: Option<&'a str>,
+    /// Nationally defined, non-qualified counterpart of the service type
+    pub national_service_type: Option<&'a str>,
+}
+
+impl ServiceSelector<'_> {
+    /// Whether a service of `service_type` carrying the `additional_information`
+    /// URIs is of this kind
+    pub fn matches<S: AsRef<str>>(&self, service_type: &str, additional_information: &[S]) -> bool {
+        service_type == self.service_type
+            && self
+                .additional_information
+                .is_none_or(|uri| additional_information.iter().any(|a| a.as_ref() == uri))
+    }
+
+    /// Whether a service of `service_type` is the nationally defined
+    /// counterpart of this kind. Additional service information only applies
+    /// to CA/QC services and is not taken into account.
+    pub fn matches_national(&self, service_type: &str) -> bool {
+        self.national_service_type == Some(service_type)
+    }
+}
+
+/// Looks up the definition of a service type URI
+pub fn service_type(uri: &str) -> Option<&'static ServiceTypeDef> {
+    SERVICE_TYPES.iter().find(|def| def.uri == uri)
+}
+
+/// Whether `service_type` identifies a qualified trust service
+pub fn is_qualified_service_type(service_type: &str) -> bool {
+    self::service_type(service_type).is_some_and(|def| def.qualified)
+}
+
+/// Whether `status` is one of the statuses of nationally defined services
+pub fn is_national_status(status: &str) -> bool {
+    status == STATUS_RECOGNISED_AT_NATIONAL_LEVEL || status == STATUS_DEPRECATED_AT_NATIONAL_LEVEL
+}
+
+/// Strips a well-known ETSI prefix from `uri` for display purposes
+pub fn short_name(uri: &str) -> &str {
+    [SVCTYPE_PREFIX, SVCSTATUS_PREFIX, SVCINFOEXT_PREFIX]
+        .iter()
+        .find_map(|prefix| uri.strip_prefix(prefix))
+        .unwrap_or(uri)
+}
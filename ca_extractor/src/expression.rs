@@ -0,0 +1,740 @@
+//! Selection expressions over the typed trusted list model, as given with
+//! `--where`, e.g.
+//!
+//! ```text
+//! service.type == "QC" && "ForWebSiteAuthentication" in service.additional && cert.not_after > now()
+//! ```
+//!
+//! An [`Expression`] is parsed and type checked once, then evaluated for each
+//! certificate against a [`Context`]: the trusted list, the TSP, the service
+//! and the certificate. [`FIELDS`] lists the names an expression can use.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+
+use crate::chain;
+use crate::filter;
+use crate::model::{self, TrustService, TrustServiceProvider, TrustedList};
+use crate::x509::Certificate;
+
+/// Type of a field or subexpression
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    Text,
+    Integer,
+    Time,
+    TextList,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::Text => write!(f, "text"),
+            Type::Integer => write!(f, "integer"),
+            Type::Time => write!(f, "time"),
+            Type::TextList => write!(f, "list"),
+        }
+    }
+}
+
+/// What an expression is evaluated against
+#[derive(Debug, Copy, Clone)]
+pub struct Context<'a> {
+    pub list: &'a TrustedList,
+    pub provider: &'a TrustServiceProvider,
+    pub service: &'a TrustService,
+    pub certificate: &'a Certificate,
+    /// The time `now()` returns
+    pub now: DateTime<Utc>,
+}
+
+/// A field of the model that expressions can refer to
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub value_type: Type,
+    get: fn(&Context) -> Value,
+}
+
+/// Every field available in expressions. URIs and OIDs also equal their
+/// short names: a service type URI equals any trailing part of it after a
+/// `/` (`CA/QC`, or `QC`, which other `.../QC` types end in too), key
+/// purposes and certificate policies equal their names (`serverAuth`,
+/// `QCP-w`).
+pub const FIELDS: &[Field] = &[
+    Field {
+        name: "list.country",
+        value_type: Type::Text,
+        get: |c| optional_text(c.list.scheme_territory.as_deref()),
+    },
+    Field {
+        name: "list.sequence_number",
+        value_type: Type::Integer,
+        get: |c| c.list.sequence_number.map_or(Value::Absent, |number| Value::Integer(number as i64)),
+    },
+    Field {
+        name: "list.issue_date",
+        value_type: Type::Time,
+        get: |c| optional_time(c.list.issue_date),
+    },
+    Field {
+        name: "list.next_update",
+        value_type: Type::Time,
+        get: |c| optional_time(c.list.next_update),
+    },
+    Field {
+        name: "tsp.name",
+        value_type: Type::Text,
+        get: |c| optional_text(c.provider.name()),
+    },
+    Field {
+        name: "tsp.names",
+        value_type: Type::TextList,
+        get: |c| texts(c.provider.names.iter().map(|name| name.value.as_str())),
+    },
+    Field {
+        name: "tsp.trade_names",
+        value_type: Type::TextList,
+        get: |c| texts(c.provider.trade_names.iter().map(|name| name.value.as_str())),
+    },
+    Field {
+        name: "tsp.identifiers",
+        value_type: Type::TextList,
+        get: |c| texts(c.provider.registration_identifiers()),
+    },
+    Field {
+        name: "service.name",
+        value_type: Type::Text,
+        get: |c| optional_text(c.service.information.name()),
+    },
+    Field {
+        name: "service.names",
+        value_type: Type::TextList,
+        get: |c| texts(c.service.information.names.iter().map(|name| name.value.as_str())),
+    },
+    Field {
+        name: "service.type",
+        value_type: Type::Text,
+        get: |c| uri(&c.service.information.service_type),
+    },
+    Field {
+        name: "service.status",
+        value_type: Type::Text,
+        get: |c| uri(&c.service.information.status),
+    },
+    Field {
+        name: "service.status_since",
+        value_type: Type::Time,
+        get: |c| optional_time(c.service.information.status_starting_time),
+    },
+    Field {
+        name: "service.additional",
+        value_type: Type::TextList,
+        get: |c| Value::List(c.service.information.additional_information.iter().map(|u| uri(u)).collect()),
+    },
+    Field {
+        name: "service.qualifiers",
+        value_type: Type::TextList,
+        get: |c| Value::List(c.service.information.qualifiers().map(uri).collect()),
+    },
+    Field {
+        name: "service.qualification",
+        value_type: Type::Text,
+        get: |c| Value::Text(c.service.information.qualification_level().to_string()),
+    },
+    Field {
+        name: "service.taken_over",
+        value_type: Type::Bool,
+        get: |c| Value::Bool(c.service.information.taken_over_by.is_some()),
+    },
+    Field {
+        name: "service.supply_points",
+        value_type: Type::TextList,
+        get: |c| texts(c.service.information.service_supply_points.iter().map(String::as_str)),
+    },
+    Field {
+        name: "cert.subject",
+        value_type: Type::Text,
+        get: |c| Value::Text(c.certificate.subject.clone()),
+    },
+    Field {
+        name: "cert.issuer",
+        value_type: Type::Text,
+        get: |c| Value::Text(c.certificate.issuer.clone()),
+    },
+    Field {
+        name: "cert.serial",
+        value_type: Type::Text,
+        get: |c| Value::Text(hex(&c.certificate.serial)),
+    },
+    Field {
+        name: "cert.fingerprint",
+        value_type: Type::Text,
+        get: |c| Value::Text(hex(&c.certificate.sha256_fingerprint())),
+    },
+    Field {
+        name: "cert.ski",
+        value_type: Type::Text,
+        get: |c| c.certificate.subject_key_id.as_deref().map_or(Value::Absent, |ski| Value::Text(hex(ski))),
+    },
+    Field {
+        name: "cert.not_before",
+        value_type: Type::Time,
+        get: |c| Value::Time(c.certificate.not_before),
+    },
+    Field {
+        name: "cert.not_after",
+        value_type: Type::Time,
+        get: |c| Value::Time(c.certificate.not_after),
+    },
+    Field {
+        name: "cert.ca",
+        value_type: Type::Bool,
+        get: |c| Value::Bool(c.certificate.ca == Some(true)),
+    },
+    Field {
+        name: "cert.self_signed",
+        value_type: Type::Bool,
+        get: |c| Value::Bool(c.certificate.is_self_issued() && c.certificate.is_signed_by(c.certificate)),
+    },
+    Field {
+        name: "cert.path_len",
+        value_type: Type::Integer,
+        get: |c| c.certificate.path_len_constraint.map_or(Value::Absent, |length| Value::Integer(length.into())),
+    },
+    Field {
+        name: "cert.key_usage",
+        value_type: Type::TextList,
+        get: |c| c.certificate.key_usage.as_ref().map_or(Value::Absent, |bits| texts(bits.iter().copied())),
+    },
+    Field {
+        name: "cert.eku",
+        value_type: Type::TextList,
+        get: |c| {
+            let purposes = c.certificate.extended_key_usage.as_ref();
+            purposes.map_or(Value::Absent, |oids| {
+                Value::List(oids.iter().map(|oid| identifier(oid, chain::key_purpose_name(oid))).collect())
+            })
+        },
+    },
+    Field {
+        name: "cert.policies",
+        value_type: Type::TextList,
+        get: |c| {
+            let policies = c.certificate.policies.iter();
+            Value::List(policies.map(|oid| identifier(oid, filter::policy_name(oid))).collect())
+        },
+    },
+    Field {
+        name: "cert.organization_identifier",
+        value_type: Type::Text,
+        get: |c| optional_text(c.certificate.organization_identifier.as_deref()),
+    },
+];
+
+/// Value of a subexpression
+#[derive(Debug, Clone)]
+enum Value {
+    Bool(bool),
+    Text(String),
+    /// A URI or OID, also equal to its short names
+    Identifier { value: String, names: Vec<String> },
+    Integer(i64),
+    Time(DateTime<Utc>),
+    List(Vec<Value>),
+    /// An optional field without a value; every comparison with it is false
+    Absent,
+}
+
+impl Value {
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Text(value) | Value::Identifier { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Identifier { value, names }, Value::Text(text))
+            | (Value::Text(text), Value::Identifier { value, names }) => {
+                value == text || names.iter().any(|name| name.eq_ignore_ascii_case(text))
+            }
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Identifier { value: a, .. }, Value::Identifier { value: b, .. }) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Time(a), Value::Time(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (a, b) => Some(a.text()?.cmp(b.text()?)),
+        }
+    }
+}
+
+fn optional_text(value: Option<&str>) -> Value {
+    value.map_or(Value::Absent, |value| Value::Text(value.to_string()))
+}
+
+fn optional_time(value: Option<DateTime<Utc>>) -> Value {
+    value.map_or(Value::Absent, Value::Time)
+}
+
+fn texts<'a>(values: impl Iterator<Item = &'a str>) -> Value {
+    Value::List(values.map(|value| Value::Text(value.to_string())).collect())
+}
+
+/// A URI, named by every trailing part after a `/`
+fn uri(value: &str) -> Value {
+    let names = value
+        .match_indices('/')
+        .map(|(index, _)| &value[index + 1..])
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    Value::Identifier {
+        value: value.to_string(),
+        names,
+    }
+}
+
+fn identifier(oid: &str, name: String) -> Value {
+    Value::Identifier {
+        value: oid.to_string(),
+        names: vec![name],
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Why an expression was rejected, with the 1-based column of the offending token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Literal(Value),
+    Field(&'static Field),
+    Now,
+    Present(Box<Node>),
+    List(Vec<Node>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Operator, Box<Node>, Box<Node>),
+    In(Box<Node>, Box<Node>),
+    Matches(Box<Node>, Regex),
+}
+
+impl Node {
+    fn evaluate(&self, context: &Context) -> Value {
+        let condition = |node: &Node| matches!(node.evaluate(context), Value::Bool(true));
+        match self {
+            Node::Literal(value) => value.clone(),
+            Node::Field(field) => (field.get)(context),
+            Node::Now => Value::Time(context.now),
+            Node::Present(node) => Value::Bool(!matches!(node.evaluate(context), Value::Absent)),
+            Node::List(nodes) => Value::List(nodes.iter().map(|node| node.evaluate(context)).collect()),
+            Node::Not(node) => Value::Bool(!condition(node)),
+            Node::And(left, right) => Value::Bool(condition(left) && condition(right)),
+            Node::Or(left, right) => Value::Bool(condition(left) || condition(right)),
+            Node::Compare(operator, left, right) => {
+                let (left, right) = (left.evaluate(context), right.evaluate(context));
+                if matches!(left, Value::Absent) || matches!(right, Value::Absent) {
+                    return Value::Bool(false);
+                }
+                let ordering = left.compare(&right);
+                Value::Bool(match operator {
+                    Operator::Equal => left.equals(&right),
+                    Operator::NotEqual => !left.equals(&right),
+                    Operator::Less => ordering.is_some_and(|o| o.is_lt()),
+                    Operator::LessOrEqual => ordering.is_some_and(|o| o.is_le()),
+                    Operator::Greater => ordering.is_some_and(|o| o.is_gt()),
+                    Operator::GreaterOrEqual => ordering.is_some_and(|o| o.is_ge()),
+                })
+            }
+            Node::In(needle, haystack) => Value::Bool(match (needle.evaluate(context), haystack.evaluate(context)) {
+                (Value::Absent, _) | (_, Value::Absent) => false,
+                (needle, Value::List(items)) => items.iter().any(|item| needle.equals(item)),
+                (needle, haystack) => match (needle.text(), haystack.text()) {
+                    (Some(needle), Some(haystack)) => haystack.contains(needle),
+                    _ => false,
+                },
+            }),
+            Node::Matches(node, pattern) => {
+                Value::Bool(node.evaluate(context).text().is_some_and(|text| pattern.is_match(text)))
+            }
+        }
+    }
+}
+
+/// A parsed and type checked selection expression.
+///
+/// Conditions combine with `&&`, `||`, `!` and parentheses. Comparisons are
+/// `==`, `!=`, `<`, `<=`, `>`, `>=` between values of the same type, `in`
+/// (membership in a list such as `service.additional` or `["a", "b"]`, or a
+/// substring of a text) and `=~` (regular expression match). Values are
+/// [`FIELDS`], `"text"`, integers, `true`, `false`, `now()`,
+/// `date("2030-01-01")` and `present(field)`, which tells whether an
+/// optional field has a value. Any comparison with a missing value is false.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            index: 0,
+        };
+        let (root, root_type) = parser.parse_or()?;
+        let (column, token) = parser.peek();
+        if *token != Token::End {
+            return Err(error(*column, format!("unexpected {}", token)));
+        }
+        if root_type != Type::Bool {
+            return Err(error(1, format!("expected a condition, the expression is a {}", root_type)));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Whether the expression holds in `context`
+    pub fn matches(&self, context: &Context) -> bool {
+        matches!(self.root.evaluate(context), Value::Bool(true))
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Expression::parse(source)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn error(column: usize, message: String) -> ExpressionError {
+    ExpressionError { column, message }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Text(String),
+    Integer(i64),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{}`", name),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Integer(value) => write!(f, "{}", value),
+            Token::Symbol(symbol) => write!(f, "`{}`", symbol),
+            Token::End => write!(f, "end of expression"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "!", "(", ")", "[", "]", ","];
+
+/// Splits `source` into tokens, each with its 1-based column
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let start = index;
+        let c = chars[index];
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+        if c == '"' {
+            let mut text = String::new();
+            index += 1;
+            loop {
+                match chars.get(index) {
+                    None => return Err(error(start + 1, "unterminated text".to_string())),
+                    Some('"') => break,
+                    Some('\\') if index + 1 < chars.len() => {
+                        text.push(chars[index + 1]);
+                        index += 2;
+                    }
+                    Some(c) => {
+                        text.push(*c);
+                        index += 1;
+                    }
+                }
+            }
+            index += 1;
+            tokens.push((start + 1, Token::Text(text)));
+        } else if c.is_ascii_digit() {
+            while index < chars.len() && chars[index].is_ascii_digit() {
+                index += 1;
+            }
+            let digits: String = chars[start..index].iter().collect();
+            let value = digits.parse().map_err(|_| error(start + 1, format!("integer {} is too large", digits)))?;
+            tokens.push((start + 1, Token::Integer(value)));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while index < chars.len() && (chars[index].is_ascii_alphanumeric() || matches!(chars[index], '_' | '.')) {
+                index += 1;
+            }
+            tokens.push((start + 1, Token::Name(chars[start..index].iter().collect())));
+        } else {
+            let rest: String = chars[index..chars.len().min(index + 2)].iter().collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| error(start + 1, format!("unexpected character `{}`", c)))?;
+            index += symbol.len();
+            tokens.push((start + 1, Token::Symbol(symbol)));
+        }
+    }
+    tokens.push((chars.len() + 1, Token::End));
+    Ok(tokens)
+}
+
+/// Recursive descent parser; every method returns the node with its type
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &(usize, Token) {
+        &self.tokens[self.index]
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.index].clone();
+        if token.1 != Token::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek().1, Token::Symbol(next) if next == symbol) {
+            self.index += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ExpressionError> {
+        let (column, token) = self.peek().clone();
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        Err(error(column, format!("expected `{}`, found {}", symbol, token)))
+    }
+
+    fn parse_or(&mut self) -> Result<(Node, Type), ExpressionError> {
+        let mut left = self.parse_and()?;
+        loop {
+            let column = self.peek().0;
+            if !self.eat("||") {
+                return Ok(left);
+            }
+            let right = self.parse_and()?;
+            check_conditions("||", column, left.1, right.1)?;
+            left = (Node::Or(Box::new(left.0), Box::new(right.0)), Type::Bool);
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<(Node, Type), ExpressionError> {
+        let mut left = self.parse_not()?;
+        loop {
+            let column = self.peek().0;
+            if !self.eat("&&") {
+                return Ok(left);
+            }
+            let right = self.parse_not()?;
+            check_conditions("&&", column, left.1, right.1)?;
+            left = (Node::And(Box::new(left.0), Box::new(right.0)), Type::Bool);
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<(Node, Type), ExpressionError> {
+        let column = self.peek().0;
+        if !self.eat("!") {
+            return self.parse_comparison();
+        }
+        let (node, node_type) = self.parse_not()?;
+        check_conditions("!", column, Type::Bool, node_type)?;
+        Ok((Node::Not(Box::new(node)), Type::Bool))
+    }
+
+    fn parse_comparison(&mut self) -> Result<(Node, Type), ExpressionError> {
+        let (left, left_type) = self.parse_operand()?;
+        let (column, token) = self.peek().clone();
+        let operator = match &token {
+            Token::Symbol("==") => Operator::Equal,
+            Token::Symbol("!=") => Operator::NotEqual,
+            Token::Symbol("<") => Operator::Less,
+            Token::Symbol("<=") => Operator::LessOrEqual,
+            Token::Symbol(">") => Operator::Greater,
+            Token::Symbol(">=") => Operator::GreaterOrEqual,
+            Token::Name(name) if name == "in" => {
+                self.next();
+                let (right, right_type) = self.parse_operand()?;
+                if left_type != Type::Text || !matches!(right_type, Type::Text | Type::TextList) {
+                    let found = format!("found {} and {}", left_type, right_type);
+                    return Err(error(column, format!("`in` needs a text and a list or text, {}", found)));
+                }
+                return Ok((Node::In(Box::new(left), Box::new(right)), Type::Bool));
+            }
+            Token::Symbol("=~") => {
+                self.next();
+                let (pattern_column, pattern) = self.next();
+                let Token::Text(pattern) = pattern else {
+                    let message = format!("`=~` needs a quoted regular expression, found {}", pattern);
+                    return Err(error(pattern_column, message));
+                };
+                let pattern = Regex::new(&pattern).map_err(|e| error(pattern_column, e.to_string()))?;
+                if left_type != Type::Text {
+                    return Err(error(column, format!("`=~` matches text, found {}", left_type)));
+                }
+                return Ok((Node::Matches(Box::new(left), pattern), Type::Bool));
+            }
+            _ => return Ok((left, left_type)),
+        };
+        self.next();
+        let (right, right_type) = self.parse_operand()?;
+        let ordered = matches!(left_type, Type::Text | Type::Integer | Type::Time);
+        let comparable = match operator {
+            Operator::Equal | Operator::NotEqual => left_type != Type::TextList,
+            _ => ordered,
+        };
+        if left_type != right_type || !comparable {
+            return Err(error(column, format!("{} cannot compare {} and {}", token, left_type, right_type)));
+        }
+        Ok((Node::Compare(operator, Box::new(left), Box::new(right)), Type::Bool))
+    }
+
+    fn parse_operand(&mut self) -> Result<(Node, Type), ExpressionError> {
+        let (column, token) = self.next();
+        match token {
+            Token::Text(text) => Ok((Node::Literal(Value::Text(text)), Type::Text)),
+            Token::Integer(value) => Ok((Node::Literal(Value::Integer(value)), Type::Integer)),
+            Token::Symbol("(") => {
+                let inner = self.parse_or()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Symbol("[") => {
+                let mut items = Vec::new();
+                while !self.eat("]") {
+                    if !items.is_empty() {
+                        self.expect(",")?;
+                    }
+                    let (item_column, item) = self.next();
+                    let Token::Text(text) = item else {
+                        return Err(error(item_column, format!("lists hold quoted text, found {}", item)));
+                    };
+                    items.push(Node::Literal(Value::Text(text)));
+                }
+                Ok((Node::List(items), Type::TextList))
+            }
+            Token::Name(name) if name == "true" || name == "false" => {
+                Ok((Node::Literal(Value::Bool(name == "true")), Type::Bool))
+            }
+            Token::Name(name) if self.peek().1 == Token::Symbol("(") => self.parse_call(column, &name),
+            Token::Name(name) => match FIELDS.iter().find(|field| field.name == name) {
+                Some(field) => Ok((Node::Field(field), field.value_type)),
+                None => {
+                    let known: Vec<&str> = FIELDS.iter().map(|field| field.name).collect();
+                    Err(error(column, format!("unknown field `{}`, expected one of {}", name, known.join(", "))))
+                }
+            },
+            token => Err(error(column, format!("expected a value, found {}", token))),
+        }
+    }
+
+    fn parse_call(&mut self, column: usize, name: &str) -> Result<(Node, Type), ExpressionError> {
+        self.expect("(")?;
+        let call = match name {
+            "now" => (Node::Now, Type::Time),
+            "date" => {
+                let (date_column, argument) = self.next();
+                let time = match &argument {
+                    Token::Text(text) => model::parse_date_time(text).or_else(|| {
+                        let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+                        Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+                    }),
+                    _ => None,
+                };
+                let time = time.ok_or_else(|| {
+                    error(date_column, format!("`date` needs an RFC 3339 date or date and time, found {}", argument))
+                })?;
+                (Node::Literal(Value::Time(time)), Type::Time)
+            }
+            "present" => {
+                let (argument_column, argument) = self.peek().clone();
+                let (node, _) = self.parse_operand()?;
+                if !matches!(node, Node::Field(_)) {
+                    return Err(error(argument_column, format!("`present` needs a field, found {}", argument)));
+                }
+                (Node::Present(Box::new(node)), Type::Bool)
+            }
+            _ => return Err(error(column, format!("unknown function `{}`, expected now, date or present", name))),
+        };
+        self.expect(")")?;
+        Ok(call)
+    }
+}
+
+fn check_conditions(operator: &str, column: usize, left: Type, right: Type) -> Result<(), ExpressionError> {
+    if left == Type::Bool && right == Type::Bool {
+        return Ok(());
+    }
+    let found = if left == Type::Bool { right } else { left };
+    Err(error(column, format!("`{}` combines conditions, found {}", operator, found)))
+}
@@ -1,15 +1,200 @@
-use reqwest;
-use xml::reader::{EventReader, XmlEvent};
+use std::fmt;
 
-use crate::cli::ServiceType;
-use crate::error::CaExtractorError;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ring::digest;
 
+use crate::cli::{QualificationFilter, ServiceType};
+use crate::dom;
+use crate::error::{CaExtractorError, ErrorContext};
+use crate::etsi;
+use crate::expression::{Context, Expression};
+use crate::filter::CertificateFilter;
+use crate::limits::ParseLimits;
+use crate::model::{
+    der_to_pem, DigitalId, QualificationLevel, ServiceInformation, TrustService, TrustServiceProvider, TrustedList,
+};
+use crate::revocation::RevocationStatus;
+use crate::x509::Certificate;
+
+#[derive(Clone)]
 pub struct CertificateExtractor {
+    services: Vec<ServiceType>,
+    qualification: QualificationFilter,
+    required_qualifiers: Vec<String>,
+    excluded_qualifiers: Vec<String>,
+    exclude_taken_over: bool,
+    certificate_filter: Option<CertificateFilter>,
+    condition: Option<Expression>,
+    /// Time the status of each service is taken at, from its history
+    time: DateTime<Utc>,
     country: String,
+    limits: ParseLimits,
+}
+
+/// A certificate selected from a trusted list, with the service it belongs to
+#[derive(Debug, Clone)]
+pub struct ExtractedCertificate<'a> {
+    pub provider: &'a TrustServiceProvider,
+    pub service: &'a TrustService,
+    /// The requested service types this certificate was selected for
+    pub service_types: Vec<ServiceType>,
+    /// DER encoded certificate
+    pub der: &'a [u8],
+    /// Set by a revocation pass, `None` when not checked
+    pub revocation: Option<RevocationStatus>,
+}
+
+impl ExtractedCertificate<'_> {
+    pub fn pem(&self) -> String {
+        der_to_pem(self.der)
+    }
+
+    pub fn qualification(&self) -> QualificationLevel {
+        self.service.information.qualification_level()
+    }
+
+    /// PEM encoding preceded by explanatory text naming the qualification
+    /// level, service type, TSP, service and, once checked, the revocation
+    /// status. PEM readers skip text outside the encapsulation boundaries.
+    pub fn annotated_pem(&self) -> String {
+        let information = &self.service.information;
+        let revocation = match &self.revocation {
+            Some(status) => format!("Revocation: {}\n", status),
+            None => String::new(),
+        };
+        format!(
+            "Qualification: {}\nService type: {}\nTSP: {}\nService: {}\n{}{}",
+            self.qualification(),
+            information.service_type,
+            self.provider.name().unwrap_or_default(),
+            information.name().unwrap_or_default(),
+            revocation,
+            self.pem()
+        )
+    }
+
+    /// JSON description of the certificate and the service it was selected from
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut service = self.service.information.to_json();
+        service["history"] = self.service.history.iter().map(ServiceInformation::to_json).collect();
+
+        serde_json::json!({
+            "tsp": self.provider.to_json(),
+            "service": service,
+            "selected_as": self.service_types.iter().map(ServiceType::to_string).collect::<Vec<_>>(),
+            "qualification": self.qualification().to_string(),
+            "revocation": self.revocation.as_ref().map(RevocationStatus::to_json),
+            "pem": self.pem(),
+        })
+    }
+}
+
+/// Result of one check of an [`Explanation`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    /// Context for the reader that does not affect the decision
+    Info,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "pass"),
+            Outcome::Failed => write!(f, "FAIL"),
+            Outcome::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// One check of the selection, applied to a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// What was checked: `qualification`, `history`, `status`, `service type`,
+    /// `qualifiers`, `taken over`, `certificate`, `certificate filter`,
+    /// `condition` or `duplicate`
+    pub check: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Step {
+    fn new(check: &'static str, passed: bool, detail: String) -> Self {
+        let outcome = if passed { Outcome::Passed } else { Outcome::Failed };
+        Step { check, outcome, detail }
+    }
+
+    fn info(check: &'static str, detail: String) -> Self {
+        Step {
+            check,
+            outcome: Outcome::Info,
+            detail,
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.outcome, self.check, self.detail)
+    }
+}
+
+/// Why a certificate listed by a service was selected or left out, see
+/// [`CertificateExtractor::explain`]
+#[derive(Debug, Clone)]
+pub struct Explanation<'a> {
+    pub provider: &'a TrustServiceProvider,
+    pub service: &'a TrustService,
+    /// DER encoded certificate
+    pub der: &'a [u8],
+    /// `None` if the certificate cannot be parsed
+    pub certificate: Option<Certificate>,
+    pub steps: Vec<Step>,
+}
+
+impl Explanation<'_> {
+    /// Whether no check failed
+    pub fn is_selected(&self) -> bool {
+        self.steps.iter().all(|step| step.outcome != Outcome::Failed)
+    }
+
+    /// SHA-256 fingerprint of the certificate in lowercase hex
+    pub fn fingerprint(&self) -> String {
+        digest::digest(&digest::SHA256, self.der).as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Whether `fingerprint`, hex with optional `:` or space separators, is
+    /// the SHA-256 fingerprint of the certificate or a prefix of it
+    pub fn has_fingerprint(&self, fingerprint: &str) -> bool {
+        let wanted: String = fingerprint
+            .chars()
+            .filter(|c| *c != ':' && !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        !wanted.is_empty() && self.fingerprint().starts_with(&wanted)
+    }
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let subject = self.certificate.as_ref().map_or("(unparseable certificate)", |c| c.subject.as_str());
+        writeln!(f, "{}: {}", if self.is_selected() { "SELECTED" } else { "EXCLUDED" }, subject)?;
+        writeln!(f, "  SHA-256: {}", self.fingerprint())?;
+        writeln!(f, "  TSP:     {}", self.provider.name().unwrap_or_default())?;
+        let information = &self.service.information;
+        let service_type = etsi::short_name(&information.service_type);
+        writeln!(f, "  Service: {} ({})", information.name().unwrap_or_default(), service_type)?;
+        for step in &self.steps {
+            writeln!(f, "  {}", step)?;
+        }
+        Ok(())
+    }
 }
 
 impl CertificateExtractor {
-    pub fn new(_service: ServiceType, country: &str) -> Result<Self, CaExtractorError> {
+    pub fn new(services: &[ServiceType], country: &str) -> Result<Self, CaExtractorError> {
         // Validate country code (simple check for now)
         if country.len() != 2 {
             return Err(CaExtractorError::InvalidCountryCode(
@@ -18,156 +203,569 @@ impl CertificateExtractor {
         }
 
         Ok(CertificateExtractor {
+            services: services.to_vec(),
+            qualification: QualificationFilter::default(),
+            required_qualifiers: Vec::new(),
+            excluded_qualifiers: Vec::new(),
+            exclude_taken_over: false,
+            certificate_filter: None,
+            condition: None,
+            time: Utc::now(),
             country: country.to_string(),
+            limits: ParseLimits::default(),
         })
     }
+
+    /// Selects services of the given qualification levels instead of
+    /// qualified services only
+    pub fn with_qualification(mut self, qualification: QualificationFilter) -> Self {
+        self.qualification = qualification;
+        self
+    }
+
+    /// Only selects services declaring every one of the given qualifier URIs
+    pub fn with_qualifiers(mut self, qualifiers: Vec<String>) -> Self {
+        self.required_qualifiers = qualifiers;
+        self
+    }
+
+    /// Skips services declaring any of the given qualifier URIs
+    pub fn without_qualifiers(mut self, qualifiers: Vec<String>) -> Self {
+        self.excluded_qualifiers = qualifiers;
+        self
+    }
+
+    /// Skips services that have been taken over by another TSP
+    pub fn without_taken_over(mut self, exclude: bool) -> Self {
+        self.exclude_taken_over = exclude;
+        self
+    }
+
+    /// Only selects certificates matching `filter`, on top of the service filters
+    pub fn with_certificate_filter(mut self, filter: CertificateFilter) -> Self {
+        self.certificate_filter = Some(filter);
+        self
+    }
+
+    /// Only selects certificates for which `condition` holds, evaluated with
+    /// the TSP and service listing them
+    pub fn with_condition(mut self, condition: Expression) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Takes the status of each service at `time`, from its status history,
+    /// instead of at the current time
+    pub fn with_time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Replaces the default download and parsing safety limits
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
     
     pub fn fetch_xml_content(&self) -> Result<String, CaExtractorError> {
-        let url = format!(
-            "https://eidas.ec.europa.eu/efda/tl-browser/api/v1/browser/download/{}",
-            self.country
-        );
+        fetch_trusted_list(&self.country, &self.limits)
+    }
 
-        println!("Fetching from URL: {}", url);
-        
-        let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(120))
-            .build()?;
-            
-        let response = client.get(&url).send()?;
-        let status = response.status();
-        
-        // Store the content type before consuming the response with text()
-        let content_type = response.headers()
-            .get(reqwest::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("unknown")
-            .to_string();
-            
-        if !status.is_success() {
-            return Err(CaExtractorError::ApiError {
-                status: status.as_u16(),
-                message: format!("API returned error status: {}", status)
-            });
-        }
-        
-        println!("Received content type: {}", content_type);
-        println!("Downloading XML content (this may take a while)...");
-        
-        // Now we can consume the response
-        let content = match response.text() {
-            Ok(text) => text,
-            Err(e) => {
-                eprintln!("Error reading response: {}", e);
-                return Err(CaExtractorError::RequestError(e));
+    /// Comma separated names of the requested service types
+    pub fn service_names(&self) -> String {
+        self.services.iter().map(ServiceType::to_string).collect::<Vec<_>>().join(",")
+    }
+
+    /// Selects the certificates of every service matching one of the
+    /// requested service types at an accepted qualification level.
+    ///
+    /// Qualified services match on their service type and additional service
+    /// information, national ones on the non-qualified counterpart of the
+    /// service type, each taken from the status history entry in force at the
+    /// selection time. The qualifier and `TakenOverBy` filters apply to that
+    /// entry too. Services not listed yet at the selection time, or not in
+    /// force then unless withdrawn services are accepted, are left out.
+    /// Certificates not matching the certificate filter or condition, or that
+    /// cannot be parsed while one is set, are left out. A certificate
+    /// listed by several matching services is returned once, for the first
+    /// service listing it.
+    pub fn select<'a>(&self, list: &'a TrustedList) -> Vec<ExtractedCertificate<'a>> {
+        let mut selected: Vec<ExtractedCertificate<'a>> = Vec::new();
+        let now = Utc::now();
+
+        for (provider, service, service_types) in self.matching_services(list) {
+            for id in &service.information.digital_ids {
+                let DigitalId::Certificate(der) = id else {
+                    continue;
+                };
+                if selected.iter().any(|c| c.der == der.as_slice()) {
+                    continue;
+                }
+                let accepts = |certificate: &Certificate| {
+                    let context = Context {
+                        list,
+                        provider,
+                        service,
+                        certificate,
+                        now,
+                    };
+                    self.accepts_certificate(certificate, &context)
+                };
+                if (self.certificate_filter.is_some() || self.condition.is_some())
+                    && !Certificate::from_der(der).is_ok_and(|certificate| accepts(&certificate))
+                {
+                    continue;
+                }
+                selected.push(ExtractedCertificate {
+                    provider,
+                    service,
+                    service_types: service_types.clone(),
+                    der,
+                    revocation: None,
+                });
             }
-        };
-        
-        // Check if it looks like XML
-        if !content.trim().starts_with("<?xml") && !content.trim().starts_with("<") {
-            println!("Warning: Response doesn't start with XML declaration");
-            println!("First 100 chars of response: {}", &content.chars().take(100).collect::<String>());
-        } else {
-            println!("Successfully downloaded XML content ({} bytes)", content.len());
         }
-        
-        Ok(content)
+
+        selected
     }
 
-    pub fn extract_certificates(&self, xml_content: &str) -> Result<Vec<String>, CaExtractorError> {
-        // First check if the content is valid XML
-        if !xml_content.trim().starts_with("<?xml") && !xml_content.trim().starts_with("<") {
-            if xml_content.trim().starts_with("{") || xml_content.trim().starts_with("[") {
-                return Err(CaExtractorError::InvalidResponseFormat(
-                    format!("API returned non-XML content: {}", 
-                            &xml_content.chars().take(100).collect::<String>())
-                ));
+    /// Selects those of `certificates`, e.g. intermediates supplied with an
+    /// end-entity certificate, that carry the `X509SKI` identity of a
+    /// matching service, see [`ServiceInformation::identifies`].
+    /// Certificates listed by a service are left to [`Self::select`]. The
+    /// result anchors certification paths of services listed without their
+    /// certificate.
+    pub fn select_identified<'a>(
+        &self,
+        list: &'a TrustedList,
+        certificates: &'a [Certificate],
+    ) -> Vec<ExtractedCertificate<'a>> {
+        let services = self.matching_services(list);
+        let listed = |certificate: &Certificate| {
+            services.iter().any(|(_, service, _)| {
+                service.information.digital_ids.contains(&DigitalId::Certificate(certificate.der.clone()))
+            })
+        };
+
+        let mut selected: Vec<ExtractedCertificate<'a>> = Vec::new();
+        for certificate in certificates.iter().filter(|certificate| !listed(certificate)) {
+            let identifying = services.iter().find(|(_, service, _)| service.information.identifies(certificate));
+            if let Some((provider, service, service_types)) = identifying {
+                if selected.iter().any(|c| c.der == certificate.der.as_slice()) {
+                    continue;
+                }
+                selected.push(ExtractedCertificate {
+                    provider,
+                    service,
+                    service_types: service_types.clone(),
+                    der: &certificate.der,
+                    revocation: None,
+                });
             }
-            
-            return Err(CaExtractorError::InvalidResponseFormat(
-                format!("API returned unrecognized content: {}", 
-                        &xml_content.chars().take(100).collect::<String>())
-            ));
         }
-        
-        let parser = EventReader::new(xml_content.as_bytes());
-        let mut current_element = String::new();
-        let mut elements = Vec::new();
-
-        for event in parser {
-            match event? {
-                XmlEvent::StartElement { name, .. } => {
-                    current_element = name.local_name;
-                }
-                XmlEvent::Characters(content) if current_element == "TSPService" => {
-                    elements.push(content);
+        selected
+    }
+
+    /// Trust anchors for certification paths through `certificates`: the
+    /// certificates of [`Self::select`] followed by those of
+    /// [`Self::select_identified`]
+    pub fn select_anchors<'a>(
+        &self,
+        list: &'a TrustedList,
+        certificates: &'a [Certificate],
+    ) -> Vec<ExtractedCertificate<'a>> {
+        let mut anchors = self.select(list);
+        anchors.extend(self.select_identified(list, certificates));
+        anchors
+    }
+
+    /// The decision trail of every certificate listed by a service of `list`,
+    /// in document order: each check [`Self::select`] applies and whether it
+    /// passed, including the entry of the status history in force at the
+    /// selection time. The certificates whose checks all pass are those
+    /// [`Self::select`] returns.
+    pub fn explain<'a>(&self, list: &'a TrustedList) -> Vec<Explanation<'a>> {
+        let now = Utc::now();
+        let mut explanations: Vec<Explanation<'a>> = Vec::new();
+
+        for (provider, service) in list.services() {
+            let service_steps = self.service_steps(service);
+            for id in &service.information.digital_ids {
+                let DigitalId::Certificate(der) = id else {
+                    continue;
+                };
+                let certificate = Certificate::from_der(der).ok();
+                let mut steps = service_steps.clone();
+                if let Some(certificate) = &certificate {
+                    let context = Context {
+                        list,
+                        provider,
+                        service,
+                        certificate,
+                        now,
+                    };
+                    steps.extend(self.certificate_steps(certificate, &context));
+                } else if self.certificate_filter.is_some() || self.condition.is_some() {
+                    steps.push(Step::new("certificate", false, "cannot be parsed".to_string()));
                 }
-                XmlEvent::EndElement { .. } => {
-                    current_element.clear();
+
+                if steps.iter().all(|step| step.outcome != Outcome::Failed) {
+                    let first = explanations.iter().find(|e| e.der == der.as_slice() && e.is_selected());
+                    steps.push(match first {
+                        Some(first) => Step::new(
+                            "duplicate",
+                            false,
+                            format!(
+                                "already selected from {} / {}",
+                                first.provider.name().unwrap_or_default(),
+                                first.service.information.name().unwrap_or_default()
+                            ),
+                        ),
+                        None => Step::new("duplicate", true, "first listing selected".to_string()),
+                    });
                 }
-                _ => {}
+                explanations.push(Explanation {
+                    provider,
+                    service,
+                    der,
+                    certificate,
+                    steps,
+                });
             }
         }
-        
-        // For tests, use direct certificate extraction
-        if xml_content.contains("<tsl:X509Certificate>") {
-            let mut certificates = Vec::new();
-            let parts: Vec<&str> = xml_content.split("<tsl:X509Certificate>").collect();
-            
-            for (i, part) in parts.iter().enumerate() {
-                if i == 0 { continue; } // Skip first part (before first certificate)
-                
-                if let Some(cert_end) = part.find("</tsl:X509Certificate>") {
-                    let cert_content = &part[0..cert_end].trim().replace([' ', '\n'], "");
-                    
-                    let wrapped_cert_str = format!(
-                        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
-                        cert_content
-                            .as_bytes()
-                            .chunks(64)
-                            .map(|chunk| std::str::from_utf8(chunk).unwrap())
-                            .collect::<Vec<&str>>()
-                            .join("\n")
-                    );
-                    
-                    certificates.push(wrapped_cert_str);
-                }
+        explanations
+    }
+
+    /// Checks of [`Self::explain`] that depend on the service only
+    fn service_steps(&self, service: &TrustService) -> Vec<Step> {
+        let information = &service.information;
+        // Level, service types and extensions as listed at the selection time;
+        // the history step fails if the service was not listed yet then
+        let listed = service.information_at(self.time).unwrap_or(information);
+        let mut steps = Vec::new();
+        let level = listed.qualification_level();
+        let accepted = self.qualification.accepts(level);
+        steps.push(Step::new(
+            "qualification",
+            accepted,
+            format!(
+                "{} makes the service {}, {} at qualification {}",
+                etsi::short_name(&listed.status),
+                level,
+                if accepted { "accepted" } else { "not accepted" },
+                self.qualification
+            ),
+        ));
+
+        let since = |information: &ServiceInformation| match information.status_starting_time {
+            Some(time) => format!("{} since {}", etsi::short_name(&information.status), time.to_rfc3339()),
+            None => etsi::short_name(&information.status).to_string(),
+        };
+        let mut history: Vec<&ServiceInformation> = service.history.iter().collect();
+        history.sort_by_key(|entry| std::cmp::Reverse(entry.status_starting_time));
+        let history: Vec<String> = std::iter::once(information).chain(history).map(since).collect();
+        let time = self.time.to_rfc3339();
+        match service.information_at(self.time) {
+            Some(entry) => {
+                let detail = format!("{} applies at {} (history: {})", since(entry), time, history.join(", "));
+                steps.push(Step::new("history", true, detail));
+
+                let status = etsi::short_name(&entry.status);
+                let withdrawn_accepted = self.qualification.accepts(QualificationLevel::Withdrawn);
+                steps.push(if etsi::is_in_force(&entry.status) {
+                    Step::new("status", true, format!("{} is in force", status))
+                } else if withdrawn_accepted {
+                    let detail =
+                        format!("{} is not in force, accepted at qualification {}", status, self.qualification);
+                    Step::new("status", true, detail)
+                } else {
+                    Step::new("status", false, format!("{} is not in force", status))
+                });
             }
-            
-            if !certificates.is_empty() {
-                return Ok(certificates);
+            None => {
+                let detail = format!("not listed yet at {} (history: {})", time, history.join(", "));
+                steps.push(Step::new("history", false, detail));
             }
         }
-        
-        let mut certificates = Vec::new();
-        
-        for element in elements {
-            if let Some(cert_start) = element.find("<tsl:X509Certificate>") {
-                if let Some(cert_end) = element.find("</tsl:X509Certificate>") {
-                    let cert_content = &element[cert_start + 19..cert_end].trim().replace([' ', '\n'], "");
-
-                    let wrapped_cert_str = format!(
-                        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
-                        cert_content
-                            .as_bytes()
-                            .chunks(64)
-                            .map(|chunk| std::str::from_utf8(chunk).unwrap())
-                            .collect::<Vec<&str>>()
-                            .join("\n")
-                    );
-                    
-                    certificates.push(wrapped_cert_str);
-                }
+
+        let service_types = self.service_types_of(listed);
+        let additional: Vec<&str> = listed.additional_information.iter().map(|uri| etsi::short_name(uri)).collect();
+        let described = if additional.is_empty() {
+            etsi::short_name(&listed.service_type).to_string()
+        } else {
+            format!("{} for {}", etsi::short_name(&listed.service_type), additional.join(", "))
+        };
+        steps.push(if service_types.is_empty() {
+            Step::new("service type", false, format!("{} matches none of {}", described, self.service_names()))
+        } else {
+            let names: Vec<String> = service_types.iter().map(ServiceType::to_string).collect();
+            Step::new("service type", true, format!("{} selected as {}", described, names.join(",")))
+        });
+
+        let declared: Vec<&str> = listed.qualifiers().collect();
+        let missing: Vec<&str> = self
+            .required_qualifiers
+            .iter()
+            .filter(|q| !declared.contains(&q.as_str()))
+            .map(|q| etsi::short_name(q))
+            .collect();
+        let excluded: Vec<&str> = self
+            .excluded_qualifiers
+            .iter()
+            .filter(|q| declared.contains(&q.as_str()))
+            .map(|q| etsi::short_name(q))
+            .collect();
+        let declared: Vec<&str> = declared.into_iter().map(etsi::short_name).collect();
+        let declared = if declared.is_empty() {
+            "no qualifiers".to_string()
+        } else {
+            format!("qualifiers {}", declared.join(", "))
+        };
+        steps.push(if !missing.is_empty() {
+            Step::new("qualifiers", false, format!("{}, missing required {}", declared, missing.join(", ")))
+        } else if !excluded.is_empty() {
+            Step::new("qualifiers", false, format!("{}, including excluded {}", declared, excluded.join(", ")))
+        } else {
+            Step::new("qualifiers", true, declared)
+        });
+
+        if let Some(taken_over_by) = &listed.taken_over_by {
+            let by = taken_over_by.tsp_names.first().map_or(taken_over_by.uri.as_str(), |name| name.value.as_str());
+            steps.push(if self.exclude_taken_over {
+                Step::new("taken over", false, format!("by {}, taken over services are excluded", by))
+            } else {
+                Step::info("taken over", format!("by {}", by))
+            });
+        }
+        steps
+    }
+
+    /// Checks of [`Self::explain`] on the certificate itself
+    fn certificate_steps(&self, certificate: &Certificate, context: &Context) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let filters = match &self.certificate_filter {
+            Some(CertificateFilter::All(filters)) => filters.iter().collect(),
+            Some(filter) => vec![filter],
+            None => Vec::new(),
+        };
+        for filter in filters {
+            steps.push(Step::new("certificate filter", filter.matches(certificate), filter.to_string()));
+        }
+        if let Some(condition) = &self.condition {
+            steps.push(Step::new("condition", condition.matches(context), condition.to_string()));
+        }
+        steps
+    }
+
+    /// Services matching one of the requested service types at an accepted
+    /// qualification level, with the service types they match
+    fn matching_services<'a>(
+        &self,
+        list: &'a TrustedList,
+    ) -> Vec<(&'a TrustServiceProvider, &'a TrustService, Vec<ServiceType>)> {
+        let mut matching = Vec::new();
+        for (provider, service) in list.services() {
+            // Level, service type, additional information and extensions as listed at the selection time
+            let Some(listed) = service.information_at(self.time) else {
+                continue;
+            };
+            let accepted = self.qualification.accepts(listed.qualification_level()) && self.accepts_status(service);
+            if !accepted || !self.accepts_extensions(listed) {
+                continue;
+            }
+
+            let service_types = self.service_types_of(listed);
+            if !service_types.is_empty() {
+                matching.push((provider, service, service_types));
             }
         }
-        
+        matching
+    }
+
+    /// The requested service types the service type and additional service
+    /// information of `information` match at its qualification level.
+    /// Withdrawn services match as qualified or as national ones.
+    fn service_types_of(&self, information: &ServiceInformation) -> Vec<ServiceType> {
+        let level = information.qualification_level();
+        self.services
+            .iter()
+            .filter(|s| {
+                let selector = s.selector();
+                match level {
+                    QualificationLevel::Qualified => {
+                        selector.matches(&information.service_type, &information.additional_information)
+                    }
+                    QualificationLevel::National | QualificationLevel::Withdrawn => {
+                        selector.matches_national(&information.service_type)
+                            || selector.matches(&information.service_type, &information.additional_information)
+                    }
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Whether the service was listed at the selection time, with a status in
+    /// force then or withdrawn services are accepted
+    fn accepts_status(&self, service: &TrustService) -> bool {
+        service.information_at(self.time).is_some_and(|information| {
+            etsi::is_in_force(&information.status) || self.qualification.accepts(QualificationLevel::Withdrawn)
+        })
+    }
+
+    /// Whether the service passes the qualifier and `TakenOverBy` filters
+    fn accepts_extensions(&self, information: &ServiceInformation) -> bool {
+        let declared: Vec<&str> = information.qualifiers().collect();
+        self.required_qualifiers.iter().all(|q| declared.contains(&q.as_str()))
+            && !self.excluded_qualifiers.iter().any(|q| declared.contains(&q.as_str()))
+            && !(self.exclude_taken_over && information.taken_over_by.is_some())
+    }
+
+    /// Whether the certificate passes the certificate filter and condition
+    fn accepts_certificate(&self, certificate: &Certificate, context: &Context) -> bool {
+        self.certificate_filter.as_ref().is_none_or(|filter| filter.matches(certificate))
+            && self.condition.as_ref().is_none_or(|condition| condition.matches(context))
+    }
+
+    /// Parses a downloaded trusted list, rejecting API responses that are not XML
+    pub fn parse_trusted_list(&self, xml_content: &str) -> Result<TrustedList, CaExtractorError> {
+        dom::check_xml_content(xml_content)
+            .map_err(|e| e.with_context(ErrorContext::source(format!("trusted list of {}", self.country))))?;
+
+        TrustedList::parse(xml_content, &self.limits)
+    }
+
+    /// Parses `xml_content` and returns the selected certificates as PEM.
+    ///
+    /// A document without trust service providers, such as an excerpt of
+    /// `TSPService` elements directly under the root, carries no service
+    /// types or statuses to select by: the certificates of those elements are
+    /// returned as they are.
+    pub fn extract_certificates(&self, xml_content: &str) -> Result<Vec<String>, CaExtractorError> {
+        let list = self.parse_trusted_list(xml_content)?;
+        let certificates: Vec<String> = if list.providers.is_empty() {
+            excerpt_certificates(xml_content, &self.limits)?
+        } else {
+            self.select(&list).iter().map(ExtractedCertificate::pem).collect()
+        };
+
         if certificates.is_empty() {
             return Err(CaExtractorError::NoCertificatesFound {
                 country: self.country.clone(),
-                service: "Certificate".to_string()
+                service: self.service_names(),
             });
         }
-        
+
         Ok(certificates)
     }
 }
+
+/// Extracts the certificates of the `service` services of the trusted list
+/// of `country` in `xml_content` as PEM, with the default limits, see
+/// [`CertificateExtractor::extract_certificates`]
+pub fn try_parse_xml(xml_content: &str, country: &str, service: &str) -> Result<Vec<String>, CaExtractorError> {
+    try_parse_xml_with_limits(xml_content, country, service, &ParseLimits::default())
+}
+
+/// [`try_parse_xml`] with the given parsing limits
+pub fn try_parse_xml_with_limits(
+    xml_content: &str,
+    country: &str,
+    service: &str,
+    limits: &ParseLimits,
+) -> Result<Vec<String>, CaExtractorError> {
+    let service: ServiceType = service.parse().map_err(CaExtractorError::InvalidServiceType)?;
+    CertificateExtractor::new(&[service], country)?.with_limits(*limits).extract_certificates(xml_content)
+}
+
+/// The certificates of `TSPService` elements directly under the root of a
+/// document without a `TrustServiceProviderList`, in document order
+fn excerpt_certificates(xml_content: &str, limits: &ParseLimits) -> Result<Vec<String>, CaExtractorError> {
+    fn collect<'a>(element: &'a dom::Element, found: &mut Vec<&'a dom::Element>) {
+        if element.name == "X509Certificate" {
+            found.push(element);
+        }
+        for child in &element.children {
+            collect(child, found);
+        }
+    }
+
+    let root = dom::parse_document(xml_content, limits)?;
+    let mut elements = Vec::new();
+    for service in root.children_named("TSPService") {
+        collect(service, &mut elements);
+    }
+    elements
+        .into_iter()
+        .map(|element| {
+            let compact: String = element.text.chars().filter(|c| !c.is_whitespace()).collect();
+            let der = base64::engine::general_purpose::STANDARD.decode(compact).map_err(|e| {
+                CaExtractorError::InvalidCertificateFormat(format!("<{}> is not valid base64: {}", element.name, e))
+                    .with_context(ErrorContext::at(element.line, element.column))
+            })?;
+            Ok(der_to_pem(&der))
+        })
+        .collect()
+}
+
+/// Downloads the trusted list of `country` from the eIDAS Trusted List API
+pub fn fetch_trusted_list(country: &str, limits: &ParseLimits) -> Result<String, CaExtractorError> {
+    let url = format!(
+        "https://eidas.ec.europa.eu/efda/tl-browser/api/v1/browser/download/{}",
+        country
+    );
+
+    download(&url, limits).map_err(|e| e.with_context(ErrorContext::source(url)))
+}
+
+/// Progress is reported on stderr, keeping stdout for reports
+fn download(url: &str, limits: &ParseLimits) -> Result<String, CaExtractorError> {
+    eprintln!("Fetching from URL: {}", url);
+    
+    let client = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(120))
+        .build()?;
+        
+    let response = client.get(url).send()?;
+    let status = response.status();
+    
+    // Store the content type before consuming the response with text()
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+        
+    if let Some(length) = response.content_length() {
+        if length > limits.max_download_size {
+            return Err(CaExtractorError::SafetyLimitExceeded(format!(
+                "server announced {} bytes, more than the maximum download size of {} bytes",
+                length, limits.max_download_size
+            )));
+        }
+    }
+
+    if !status.is_success() {
+        return Err(CaExtractorError::ApiError {
+            status: status.as_u16(),
+            message: format!("API returned error status: {}", status)
+        });
+    }
+    
+    eprintln!("Received content type: {}", content_type);
+    eprintln!("Downloading XML content (this may take a while)...");
+    
+    // Now we can consume the response
+    let content = match limits.read_limited(response) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error reading response: {}", e);
+            return Err(e);
+        }
+    };
+    
+    dom::check_xml_content(&content)?;
+    eprintln!("Successfully downloaded XML content ({} bytes)", content.len());
+    
+    Ok(content)
+}
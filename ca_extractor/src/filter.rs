@@ -0,0 +1,177 @@
+//! Certificate-level filters, applied to the certificates of the services
+//! the service type, qualification and qualifier filters selected. Each
+//! filter is a [`CertificateFilter`] predicate over an
+//! [`x509::Certificate`](crate::x509::Certificate); predicates compose with
+//! [`CertificateFilter::and`], [`CertificateFilter::or`] and `!`.
+
+use std::fmt;
+use std::ops::Not;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::chain::{self, OID_ANY_EXTENDED_KEY_USAGE};
+use crate::cli::CaKind;
+use crate::x509::Certificate;
+
+/// Certificate policies of ETSI EN 319 411-2 (qualified certificates) and
+/// EN 319 411-1, by name
+pub const POLICIES: &[(&str, &str)] = &[
+    ("QCP-n", "0.4.0.194112.1.0"),
+    ("QCP-l", "0.4.0.194112.1.1"),
+    ("QCP-n-qscd", "0.4.0.194112.1.2"),
+    ("QCP-l-qscd", "0.4.0.194112.1.3"),
+    ("QCP-w", "0.4.0.194112.1.4"),
+    ("QNCP-w", "0.4.0.194112.1.5"),
+    ("QNCP-w-gen", "0.4.0.194112.1.6"),
+    ("NCP", "0.4.0.2042.1.1"),
+    ("NCP+", "0.4.0.2042.1.2"),
+    ("LCP", "0.4.0.2042.1.3"),
+    ("EVCP", "0.4.0.2042.1.4"),
+    ("DVCP", "0.4.0.2042.1.6"),
+    ("OVCP", "0.4.0.2042.1.7"),
+    ("IVCP", "0.4.0.2042.1.8"),
+];
+
+/// Resolves a certificate policy given by name or OID
+pub fn policy_oid(value: &str) -> Option<String> {
+    if let Some((_, oid)) = POLICIES.iter().find(|(name, _)| name.eq_ignore_ascii_case(value)) {
+        return Some(oid.to_string());
+    }
+    let is_arc = |arc: &str| !arc.is_empty() && arc.bytes().all(|b| b.is_ascii_digit());
+    let is_oid = value.contains('.') && value.split('.').all(is_arc);
+    is_oid.then(|| value.to_string())
+}
+
+/// Name of a known certificate policy, or the OID itself
+pub fn policy_name(oid: &str) -> String {
+    POLICIES
+        .iter()
+        .find(|(_, policy)| *policy == oid)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| oid.to_string())
+}
+
+/// A predicate over certificates
+#[derive(Debug, Clone)]
+pub enum CertificateFilter {
+    /// Every named key usage bit (as in [`crate::x509::KEY_USAGE_BITS`]) is
+    /// asserted. Certificates without the extension do not match.
+    KeyUsage(Vec<String>),
+    /// The extended key usage allows one of the key purpose OIDs, directly or
+    /// through anyExtendedKeyUsage. With `allow_absent`, certificates without
+    /// the extension, which are not restricted to any purpose, match as well.
+    ExtendedKeyUsage { purposes: Vec<String>, allow_absent: bool },
+    /// One of the certificate policy OIDs is asserted
+    Policy(Vec<String>),
+    /// The subject distinguished name, formatted as in reports, matches
+    Subject(Regex),
+    /// The issuer distinguished name, formatted as in reports, matches
+    Issuer(Regex),
+    /// The certificate is self-signed, or not, as `CaKind` requires
+    Kind(CaKind),
+    /// The certificate is valid throughout `from` to `until`
+    ValidDuring { from: DateTime<Utc>, until: DateTime<Utc> },
+    /// Every filter matches; an empty list matches every certificate
+    All(Vec<CertificateFilter>),
+    /// At least one filter matches
+    Any(Vec<CertificateFilter>),
+    Not(Box<CertificateFilter>),
+}
+
+impl CertificateFilter {
+    pub fn matches(&self, certificate: &Certificate) -> bool {
+        match self {
+            CertificateFilter::KeyUsage(bits) => bits.iter().all(|bit| certificate.has_key_usage(bit)),
+            CertificateFilter::ExtendedKeyUsage { purposes, allow_absent } => match &certificate.extended_key_usage {
+                Some(allowed) => allowed
+                    .iter()
+                    .any(|oid| oid == OID_ANY_EXTENDED_KEY_USAGE || purposes.contains(oid)),
+                None => *allow_absent,
+            },
+            CertificateFilter::Policy(oids) => certificate.policies.iter().any(|oid| oids.contains(oid)),
+            CertificateFilter::Subject(pattern) => pattern.is_match(&certificate.subject),
+            CertificateFilter::Issuer(pattern) => pattern.is_match(&certificate.issuer),
+            CertificateFilter::Kind(kind) => {
+                let self_signed = certificate.is_self_issued() && certificate.is_signed_by(certificate);
+                self_signed == (*kind == CaKind::Root)
+            }
+            CertificateFilter::ValidDuring { from, until } => {
+                certificate.not_before <= *from && *until <= certificate.not_after
+            }
+            CertificateFilter::All(filters) => filters.iter().all(|filter| filter.matches(certificate)),
+            CertificateFilter::Any(filters) => filters.iter().any(|filter| filter.matches(certificate)),
+            CertificateFilter::Not(filter) => !filter.matches(certificate),
+        }
+    }
+
+    /// Matches certificates both filters match
+    pub fn and(self, other: CertificateFilter) -> CertificateFilter {
+        match self {
+            CertificateFilter::All(mut filters) => {
+                filters.push(other);
+                CertificateFilter::All(filters)
+            }
+            filter => CertificateFilter::All(vec![filter, other]),
+        }
+    }
+
+    /// Matches certificates either filter matches
+    pub fn or(self, other: CertificateFilter) -> CertificateFilter {
+        match self {
+            CertificateFilter::Any(mut filters) => {
+                filters.push(other);
+                CertificateFilter::Any(filters)
+            }
+            filter => CertificateFilter::Any(vec![filter, other]),
+        }
+    }
+
+    /// Certificates valid at `time`
+    pub fn valid_at(time: DateTime<Utc>) -> CertificateFilter {
+        CertificateFilter::ValidDuring { from: time, until: time }
+    }
+}
+
+impl Not for CertificateFilter {
+    type Output = CertificateFilter;
+
+    fn not(self) -> CertificateFilter {
+        CertificateFilter::Not(Box::new(self))
+    }
+}
+
+impl fmt::Display for CertificateFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |filters: &[CertificateFilter], separator: &str| {
+            filters.iter().map(|filter| format!("({})", filter)).collect::<Vec<_>>().join(separator)
+        };
+        match self {
+            CertificateFilter::KeyUsage(bits) => write!(f, "keyUsage {}", bits.join(",")),
+            CertificateFilter::ExtendedKeyUsage { purposes, allow_absent } => {
+                let mut names: Vec<String> = purposes.iter().map(|oid| chain::key_purpose_name(oid)).collect();
+                if *allow_absent {
+                    names.push("none".to_string());
+                }
+                write!(f, "extendedKeyUsage {}", names.join(","))
+            }
+            CertificateFilter::Policy(oids) => {
+                let names: Vec<String> = oids.iter().map(|oid| policy_name(oid)).collect();
+                write!(f, "policy {}", names.join(","))
+            }
+            CertificateFilter::Subject(pattern) => write!(f, "subject matches /{}/", pattern),
+            CertificateFilter::Issuer(pattern) => write!(f, "issuer matches /{}/", pattern),
+            CertificateFilter::Kind(kind) => write!(f, "{}", kind),
+            CertificateFilter::ValidDuring { from, until } if from == until => {
+                write!(f, "valid at {}", from.to_rfc3339())
+            }
+            CertificateFilter::ValidDuring { from, until } => {
+                write!(f, "valid from {} until {}", from.to_rfc3339(), until.to_rfc3339())
+            }
+            CertificateFilter::All(filters) if filters.is_empty() => write!(f, "any certificate"),
+            CertificateFilter::All(filters) => write!(f, "{}", join(filters, " and ")),
+            CertificateFilter::Any(filters) => write!(f, "{}", join(filters, " or ")),
+            CertificateFilter::Not(filter) => write!(f, "not ({})", filter),
+        }
+    }
+}
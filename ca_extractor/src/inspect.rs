@@ -0,0 +1,201 @@
+//! QcStatements and PSD2 attribute analysis of end-entity certificates,
+//! cross-checked against the QWAC and QSealC CAs selected from a trusted list.
+
+use serde_json::{json, Value};
+
+use crate::chain::{self, PathOptions};
+use crate::cli::ServiceType;
+use crate::etsi;
+use crate::extractor::ExtractedCertificate;
+use crate::model::QualificationLevel;
+use crate::x509::{Certificate, QcType};
+
+/// An `organizationIdentifier` in the ETSI EN 319 412-1 semantics:
+/// a three letter scheme, a country code, a hyphen and the reference,
+/// e.g. `PSDDE-BAFIN-123456` or `VATDE-123456789`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrganizationIdentifier {
+    pub scheme: String,
+    pub country: String,
+    pub reference: String,
+}
+
+impl OrganizationIdentifier {
+    pub fn parse(value: &str) -> Option<Self> {
+        let scheme = value.get(..3)?;
+        let country = value.get(3..5)?;
+        let reference = value.get(5..)?.strip_prefix('-')?;
+        let upper = |text: &str| text.chars().all(|c| c.is_ascii_uppercase());
+        if !upper(scheme) || !upper(country) || reference.is_empty() {
+            return None;
+        }
+        Some(OrganizationIdentifier {
+            scheme: scheme.to_string(),
+            country: country.to_string(),
+            reference: reference.to_string(),
+        })
+    }
+}
+
+/// Result of inspecting a certificate's QcStatements
+#[derive(Debug, Clone)]
+pub struct QcInspection<'a> {
+    pub certificate: Certificate,
+    pub organization_identifier: Option<OrganizationIdentifier>,
+    /// The QWAC or QSealC CA certificate the certificate chains to
+    pub issuer: Option<ExtractedCertificate<'a>>,
+    /// Certificates from the inspected one up to the one issued by `issuer`
+    pub chain: Vec<Certificate>,
+    /// Inconsistencies between the statements, the subject and the issuing CA
+    pub issues: Vec<String>,
+}
+
+impl QcInspection<'_> {
+    pub fn to_json(&self) -> Value {
+        let statements = &self.certificate.qc_statements;
+        json!({
+            "subject": self.certificate.subject,
+            "organization_identifier": self.certificate.organization_identifier.as_ref().map(|value| json!({
+                "value": value,
+                "scheme": self.organization_identifier.as_ref().map(|id| id.scheme.clone()),
+                "country": self.organization_identifier.as_ref().map(|id| id.country.clone()),
+                "reference": self.organization_identifier.as_ref().map(|id| id.reference.clone()),
+            })),
+            "qc_statements": {
+                "compliance": statements.compliance,
+                "types": statements.types.iter().map(QcType::to_string).collect::<Vec<_>>(),
+                "sscd": statements.sscd,
+                "retention_period": statements.retention_period,
+                "pds": statements
+                    .pds
+                    .iter()
+                    .map(|pds| json!({ "url": pds.url, "language": pds.language }))
+                    .collect::<Vec<_>>(),
+                "psd2": statements.psd2.as_ref().map(|psd2| json!({
+                    "roles": psd2
+                        .roles
+                        .iter()
+                        .map(|role| json!({ "oid": role.oid, "name": role.name }))
+                        .collect::<Vec<_>>(),
+                    "nca_name": psd2.nca_name,
+                    "nca_id": psd2.nca_id,
+                })),
+                "statement_ids": statements.statement_ids,
+            },
+            "issuer": self.issuer.as_ref().map(ExtractedCertificate::to_json),
+            "chain": self.chain.iter().map(|cert| cert.subject.clone()).collect::<Vec<_>>(),
+            "issues": self.issues,
+        })
+    }
+}
+
+/// Inspects the QcStatements of `certificate` and checks them against the
+/// QWAC and QSealC CA certificates in `cas`, chaining through `intermediates`.
+/// `cas` may include CAs of services that are not qualified, or no longer;
+/// a certificate issued under one is reported with the service status.
+pub fn inspect_qc<'a>(
+    certificate: &Certificate,
+    intermediates: &[Certificate],
+    cas: &[ExtractedCertificate<'a>],
+) -> QcInspection<'a> {
+    let statements = &certificate.qc_statements;
+    let organization_identifier = certificate
+        .organization_identifier
+        .as_deref()
+        .and_then(OrganizationIdentifier::parse);
+    let mut issues = Vec::new();
+
+    if !statements.compliance {
+        issues.push("no QcCompliance statement: the certificate does not claim to be qualified".to_string());
+    }
+
+    // The status of the issuing CA's service is reported below, not rejected
+    let options = PathOptions {
+        require_service_in_force: false,
+        ..PathOptions::default()
+    };
+    let (issuer, chain) = match chain::validate(certificate, intermediates, cas, &options) {
+        Ok(path) => (Some(path.anchor), path.chain),
+        Err(failures) => {
+            let reasons: Vec<String> = failures.iter().map(ToString::to_string).collect();
+            issues.push(format!(
+                "the certificate does not chain to a QWAC or QSealC CA of the trusted list: {}",
+                reasons.join("; ")
+            ));
+            (None, vec![certificate.clone()])
+        }
+    };
+
+    if let Some(ca) = issuer.as_ref().filter(|ca| ca.qualification() != QualificationLevel::Qualified) {
+        let information = &ca.service.information;
+        issues.push(format!(
+            "the service '{}' of the issuing CA is {} with status '{}'",
+            information.name().unwrap_or_default(),
+            ca.qualification(),
+            etsi::short_name(&information.status)
+        ));
+    }
+
+    let types: Vec<QcType> = if statements.types.is_empty() && statements.compliance {
+        // EN 319 412-5: a qualified certificate without QcType is for e-signatures
+        vec![QcType::ESign]
+    } else {
+        statements.types.clone()
+    };
+    if let Some(ca) = &issuer {
+        for qc_type in &types {
+            let expected = match qc_type {
+                QcType::Web => Some(ServiceType::QWAC),
+                QcType::ESeal => Some(ServiceType::QSealC),
+                QcType::ESign => None,
+            };
+            match expected {
+                Some(expected) if !ca.service_types.contains(&expected) => issues.push(format!(
+                    "QcType '{}' but the issuing CA is not a {} CA",
+                    qc_type, expected
+                )),
+                Some(_) => {}
+                None => issues.push(format!("QcType '{}' is neither a QWAC nor a QSealC", qc_type)),
+            }
+        }
+    }
+
+    match (&statements.psd2, &organization_identifier) {
+        (Some(psd2), id) => {
+            if psd2.roles.is_empty() {
+                issues.push("the PSD2 statement lists no role".to_string());
+            }
+            for role in psd2.roles.iter().filter(|role| !role.is_consistent()) {
+                issues.push(format!("PSD2 role '{}' ({}) is not defined by ETSI TS 119 495", role.name, role.oid));
+            }
+            match id {
+                Some(id) if id.scheme == "PSD" => {
+                    // PSD + country + '-' + NCA identifier without country + '-' + PSP reference
+                    let reference = format!("{}-{}", id.country, id.reference);
+                    if !reference.starts_with(&format!("{}-", psd2.nca_id)) {
+                        issues.push(format!(
+                            "organizationIdentifier '{}' does not match the NCA identifier '{}'",
+                            certificate.organization_identifier.as_deref().unwrap_or_default(),
+                            psd2.nca_id
+                        ));
+                    }
+                }
+                _ => issues.push(
+                    "the PSD2 statement requires an organizationIdentifier with the 'PSD' scheme".to_string(),
+                ),
+            }
+        }
+        (None, Some(id)) if id.scheme == "PSD" => {
+            issues.push("organizationIdentifier uses the 'PSD' scheme but there is no PSD2 statement".to_string())
+        }
+        (None, _) => {}
+    }
+
+    QcInspection {
+        certificate: certificate.clone(),
+        organization_identifier,
+        issuer,
+        chain,
+        issues,
+    }
+}
@@ -0,0 +1,93 @@
+//! Validation of JAdES baseline-B signatures (ETSI TS 119 182-1) in compact
+//! serialization, with trust anchors from a trusted list.
+//!
+//! The protected header must identify the signing certificate with
+//! `x5t#S256`, carry it in `x5c` and claim the signing time in a critical
+//! `sigT`. The signer is then validated as for CAdES signatures.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::cades::{self, SignerValidation};
+use crate::error::CaExtractorError;
+use crate::extractor::ExtractedCertificate;
+use crate::jws::CompactJws;
+use crate::model::{self, TrustedList};
+use crate::x509::Certificate;
+
+/// Critical header parameters this validation understands
+const UNDERSTOOD_CRITICAL: &[&str] = &["sigT"];
+
+/// A validated JAdES signature
+#[derive(Debug, Clone)]
+pub struct JadesValidation<'a> {
+    pub jws: CompactJws,
+    pub signer: SignerValidation<'a>,
+}
+
+impl JadesValidation<'_> {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "header": self.jws.header,
+            "payload_size": self.jws.payload.len(),
+            "signer": self.signer.to_json(),
+        })
+    }
+}
+
+/// Validates a compact JAdES signature. `detached_payload` is the payload
+/// of a signature with an empty payload part; `certificates` complement the
+/// `x5c` header; `anchors_at` selects the trusted list certificates the
+/// signer must chain to by the status of their services at `sigT`.
+pub fn validate<'a>(
+    text: &str,
+    detached_payload: Option<&[u8]>,
+    certificates: &[Certificate],
+    anchors_at: impl Fn(DateTime<Utc>) -> Vec<ExtractedCertificate<'a>>,
+    list: &'a TrustedList,
+) -> Result<JadesValidation<'a>, CaExtractorError> {
+    let mut jws = CompactJws::parse(text)?;
+    if let Some(payload) = detached_payload {
+        if !jws.payload.is_empty() {
+            return Err(invalid("the signature has a payload, it is not detached".to_string()));
+        }
+        jws = jws.with_detached_payload(payload);
+    }
+
+    let critical: Vec<&str> = match jws.header.get("crit") {
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        Some(_) => return Err(invalid("crit must be an array of header names".to_string())),
+        None => Vec::new(),
+    };
+    if let Some(name) = critical.iter().find(|name| !UNDERSTOOD_CRITICAL.contains(name)) {
+        return Err(invalid(format!("unsupported critical header parameter '{}'", name)));
+    }
+    let signing_time = match jws.header_str("sigT") {
+        Some(_) if !critical.contains(&"sigT") => {
+            return Err(invalid("sigT must be listed in the crit header".to_string()))
+        }
+        Some(value) => model::parse_date_time(value)
+            .ok_or_else(|| invalid(format!("sigT '{}' is not an RFC 3339 time", value)))?,
+        None => return Err(invalid("the baseline-B sigT header is missing".to_string())),
+    };
+    if jws.header_str("x5t#S256").is_none() {
+        return Err(invalid("the baseline-B x5t#S256 header is missing".to_string()));
+    }
+
+    let mut chain = jws.certificate_chain()?;
+    if chain.is_empty() {
+        return Err(invalid("the signature has no x5c header".to_string()));
+    }
+    let certificate = chain.remove(0);
+    jws.check_certificate_digest(&certificate)?;
+    jws.verify(&certificate)?;
+
+    chain.extend_from_slice(certificates);
+    let anchors = anchors_at(signing_time);
+    let signer = cades::validate_signer(certificate, &chain, &anchors, list, Some(signing_time), signing_time)?;
+    Ok(JadesValidation { jws, signer })
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
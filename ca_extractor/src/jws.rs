@@ -0,0 +1,138 @@
+//! JSON Web Signatures in compact serialization (RFC 7515), the form used by
+//! JAdES signatures (ETSI TS 119 182-1) and 2-QWAC TLS certificate bindings.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::{digest, signature};
+use serde_json::{Map, Value};
+
+use crate::error::CaExtractorError;
+use crate::x509::Certificate;
+
+/// A parsed compact JWS
+#[derive(Debug, Clone)]
+pub struct CompactJws {
+    /// Decoded protected header
+    pub header: Map<String, Value>,
+    /// Payload, empty for a detached signature
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+    header_b64: String,
+    payload_b64: String,
+}
+
+impl CompactJws {
+    pub fn parse(text: &str) -> Result<Self, CaExtractorError> {
+        let parts: Vec<&str> = text.trim().split('.').collect();
+        let [header_b64, payload_b64, signature_b64] = parts[..] else {
+            return Err(invalid(format!("a compact JWS has 3 parts, found {}", parts.len())));
+        };
+
+        let header_json = decode_url(header_b64, "protected header")?;
+        let header = match serde_json::from_slice(&header_json) {
+            Ok(Value::Object(header)) => header,
+            _ => return Err(invalid("the protected header is not a JSON object".to_string())),
+        };
+
+        Ok(CompactJws {
+            header,
+            payload: decode_url(payload_b64, "payload")?,
+            signature: decode_url(signature_b64, "signature")?,
+            header_b64: header_b64.to_string(),
+            payload_b64: payload_b64.to_string(),
+        })
+    }
+
+    /// Attaches the payload of a detached signature
+    pub fn with_detached_payload(mut self, payload: &[u8]) -> Self {
+        self.payload_b64 = URL_SAFE_NO_PAD.encode(payload);
+        self.payload = payload.to_vec();
+        self
+    }
+
+    pub fn header_str(&self, name: &str) -> Option<&str> {
+        self.header.get(name).and_then(Value::as_str)
+    }
+
+    /// The `alg` header parameter
+    pub fn algorithm(&self) -> Option<&str> {
+        self.header_str("alg")
+    }
+
+    /// Certificates of the `x5c` header, signer first
+    pub fn certificate_chain(&self) -> Result<Vec<Certificate>, CaExtractorError> {
+        let Some(entries) = self.header.get("x5c").and_then(Value::as_array) else {
+            return Ok(Vec::new());
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                let text = entry.as_str().ok_or_else(|| invalid("x5c entries must be strings".to_string()))?;
+                let der = STANDARD
+                    .decode(text)
+                    .map_err(|e| invalid(format!("x5c entry is not base64: {}", e)))?;
+                Certificate::from_der(&der)
+            })
+            .collect()
+    }
+
+    /// Checks the `x5t#S256` header, when present, against `certificate`
+    pub fn check_certificate_digest(&self, certificate: &Certificate) -> Result<(), CaExtractorError> {
+        match self.header_str("x5t#S256") {
+            Some(value) if value != URL_SAFE_NO_PAD.encode(certificate.sha256_fingerprint()) => Err(invalid(
+                format!("x5t#S256 does not match the certificate '{}'", certificate.subject),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies the signature with the key of `certificate`
+    pub fn verify(&self, certificate: &Certificate) -> Result<(), CaExtractorError> {
+        let algorithm = self.algorithm().unwrap_or_default();
+        let verification: &'static dyn signature::VerificationAlgorithm = match algorithm {
+            "ES256" => &signature::ECDSA_P256_SHA256_FIXED,
+            "ES384" => &signature::ECDSA_P384_SHA384_FIXED,
+            "RS256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+            "RS384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+            "RS512" => &signature::RSA_PKCS1_2048_8192_SHA512,
+            "PS256" => &signature::RSA_PSS_2048_8192_SHA256,
+            "PS384" => &signature::RSA_PSS_2048_8192_SHA384,
+            "PS512" => &signature::RSA_PSS_2048_8192_SHA512,
+            other => return Err(invalid(format!("unsupported JWS algorithm '{}'", other))),
+        };
+
+        let signing_input = format!("{}.{}", self.header_b64, self.payload_b64);
+        if certificate.verifies(verification, signing_input.as_bytes(), &self.signature) {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "the {} signature does not verify with the key of '{}'",
+                algorithm, certificate.subject
+            )))
+        }
+    }
+}
+
+/// Digest algorithm named as in JAdES `hashM` (`S256`, `S384`, `S512`)
+pub fn digest_algorithm(name: &str) -> Option<&'static digest::Algorithm> {
+    match name {
+        "S256" => Some(&digest::SHA256),
+        "S384" => Some(&digest::SHA384),
+        "S512" => Some(&digest::SHA512),
+        _ => None,
+    }
+}
+
+pub fn encode_url(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+fn decode_url(text: &str, what: &str) -> Result<Vec<u8>, CaExtractorError> {
+    URL_SAFE_NO_PAD
+        .decode(text)
+        .map_err(|e| invalid(format!("the {} is not base64url: {}", what, e)))
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
@@ -0,0 +1,32 @@
+pub mod cades;
+pub mod chain;
+pub mod cli;
+pub mod cms;
+pub mod conformance;
+pub mod dom;
+pub mod endpoints;
+pub mod error;
+pub mod etsi;
+pub mod expression;
+pub mod extractor;
+pub mod filter;
+pub mod inspect;
+pub mod jades;
+pub mod jws;
+pub mod limits;
+pub mod model;
+pub mod ocsp;
+pub mod qualify;
+pub mod revocation;
+pub mod schema;
+pub mod timestamp;
+pub mod tls_binding;
+pub mod x509;
+pub mod xsd;
+
+pub use extractor::{try_parse_xml, try_parse_xml_with_limits};
+
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+
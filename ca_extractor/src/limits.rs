@@ -0,0 +1,156 @@
+use std::io::{self, Read};
+
+use xml::common::Position;
+use xml::reader::{EventReader, ParserConfig2, XmlEvent};
+
+use crate::error::{CaExtractorError, ErrorContext};
+
+/// Default cap on the size of a downloaded trusted list (64 MiB)
+pub const DEFAULT_MAX_DOWNLOAD_SIZE: u64 = 64 * 1024 * 1024;
+/// Default cap on element nesting depth
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// Default cap on the size of a single text node (4 MiB)
+pub const DEFAULT_MAX_TEXT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Safety limits applied to untrusted trusted-list content.
+///
+/// Documents carrying a DTD are always rejected, which also rules out
+/// internal and external entity declarations.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Maximum number of bytes accepted from the network
+    pub max_download_size: u64,
+    /// Maximum element nesting depth
+    pub max_depth: usize,
+    /// Maximum number of bytes in a single text or CDATA node, and in the
+    /// text an element accumulates from several
+    pub max_text_size: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_download_size: DEFAULT_MAX_DOWNLOAD_SIZE,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_text_size: DEFAULT_MAX_TEXT_SIZE,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// Reads at most `max_download_size` bytes from `source` into a string
+    pub fn read_limited<R: Read>(&self, source: R) -> Result<String, CaExtractorError> {
+        let content = self.read_limited_bytes(source)?;
+        String::from_utf8(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    /// Reads at most `max_download_size` bytes from `source`
+    pub fn read_limited_bytes<R: Read>(&self, source: R) -> Result<Vec<u8>, CaExtractorError> {
+        let mut content = Vec::new();
+        source
+            .take(self.max_download_size.saturating_add(1))
+            .read_to_end(&mut content)?;
+
+        if content.len() as u64 > self.max_download_size {
+            return Err(CaExtractorError::SafetyLimitExceeded(format!(
+                "document is larger than the maximum download size of {} bytes",
+                self.max_download_size
+            )));
+        }
+
+        Ok(content)
+    }
+
+    /// Creates an XML event reader over `xml_content` that enforces these limits
+    pub fn event_reader<'a>(&self, xml_content: &'a str) -> LimitedEvents<'a> {
+        let config = ParserConfig2::new()
+            .max_entity_expansion_depth(1)
+            .max_entity_expansion_length(0)
+            .allow_multiple_root_elements(false);
+
+        LimitedEvents {
+            content: xml_content,
+            reader: config.create_reader(xml_content.as_bytes()),
+            limits: *self,
+            depth: 0,
+            finished: false,
+        }
+    }
+}
+
+/// Iterator over XML events that fails with `SafetyLimitExceeded` as soon as
+/// the document breaks one of the configured limits.
+///
+/// Errors carry the line and column of the offending event.
+pub struct LimitedEvents<'a> {
+    content: &'a str,
+    reader: EventReader<&'a [u8]>,
+    limits: ParseLimits,
+    depth: usize,
+    finished: bool,
+}
+
+impl LimitedEvents<'_> {
+    /// One-based line and column of the last event produced
+    pub fn location(&self) -> (u64, u64) {
+        let position = self.reader.position();
+        (position.row + 1, position.column + 1)
+    }
+
+    fn fail(&mut self, error: CaExtractorError, line: u64, column: u64) -> Option<Result<XmlEvent, CaExtractorError>> {
+        self.finished = true;
+        let context = ErrorContext::at(line, column).with_snippet_from(self.content);
+        Some(Err(error.with_context(context)))
+    }
+
+    fn violation(&mut self, message: String) -> Option<Result<XmlEvent, CaExtractorError>> {
+        let (line, column) = self.location();
+        self.fail(CaExtractorError::SafetyLimitExceeded(message), line, column)
+    }
+}
+
+impl Iterator for LimitedEvents<'_> {
+    type Item = Result<XmlEvent, CaExtractorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let event = match self.reader.next() {
+            Ok(event) => event,
+            Err(e) => {
+                let position = e.position();
+                return self.fail(e.into(), position.row + 1, position.column + 1);
+            }
+        };
+
+        match &event {
+            XmlEvent::StartElement { .. } => {
+                if self.depth == 0 && self.reader.doctype().is_some() {
+                    return self.violation("DTDs are not allowed".to_string());
+                }
+                self.depth += 1;
+                if self.depth > self.limits.max_depth {
+                    let max_depth = self.limits.max_depth;
+                    return self.violation(format!("element nesting deeper than {}", max_depth));
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                self.depth = self.depth.saturating_sub(1);
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) | XmlEvent::Whitespace(text)
+                if text.len() > self.limits.max_text_size =>
+            {
+                let max_text_size = self.limits.max_text_size;
+                return self.violation(format!("text node larger than {} bytes", max_text_size));
+            }
+            XmlEvent::EndDocument => {
+                self.finished = true;
+            }
+            _ => {}
+        }
+
+        Some(Ok(event))
+    }
+}
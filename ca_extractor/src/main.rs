@@ -1,218 +1,793 @@
-use std::env;
 use std::fs;
+use std::process::ExitCode;
 
-use clap::Parser;
+use ca_extractor::cades;
+use ca_extractor::chain;
+use ca_extractor::cli::{
+    Args, CertificateArgs, CmsArgs, Command, CommandLineError, EndpointFormat, EndpointsArgs, ErrorFormat, JadesArgs,
+    ListSourceArgs, OutputFormat, QualificationFilter, ReportFormat, RevokedAction, ServiceType, TimestampArgs, TspsArgs,
+    TwoQwacArgs, VerifyArgs,
+};
+use ca_extractor::conformance::{self, Severity};
+use ca_extractor::endpoints::{self, Endpoint};
+use ca_extractor::error::{self, CaExtractorError, ErrorContext};
+use ca_extractor::etsi;
+use ca_extractor::extractor::{fetch_trusted_list, CertificateExtractor};
+use ca_extractor::inspect;
+use ca_extractor::jades;
+use ca_extractor::limits::ParseLimits;
+use ca_extractor::model::{self, LocalizedText, TrustServiceProvider, TrustedList};
+use ca_extractor::qualify;
+use ca_extractor::revocation::{self, CrlCache, RevocationStatus};
+use ca_extractor::schema;
+use ca_extractor::timestamp::{self, TimeStampToken};
+use ca_extractor::tls_binding;
+use ca_extractor::x509;
 
-mod cli;
-mod error;
-mod extractor;
-#[cfg(test)]
-mod tests;
+fn main() -> ExitCode {
+    let args = match Args::try_parse_command_line(std::env::args_os()) {
+        Ok(args) => args,
+        Err(CommandLineError::Clap(e)) => e.exit(),
+        Err(CommandLineError::ServiceType(e, format)) => {
+            match format {
+                ErrorFormat::Text => report_error(&e, false),
+                ErrorFormat::Json => eprintln!("{}", e.to_json()),
+            }
+            return ExitCode::from(e.exit_code());
+        }
+    };
 
-use cli::{Args, ServiceType};
-use error::CaExtractorError;
-use extractor::CertificateExtractor;
+    let result = match &args.command {
+        Some(Command::Validate(source)) => run_validate(source, &args),
+        Some(Command::Check(source)) => run_check(source, &args),
+        Some(Command::Qualify(certificate_args)) => run_qualify(certificate_args, &args),
+        Some(Command::InspectQc(certificate_args)) => run_inspect_qc(certificate_args, &args),
+        Some(Command::Verify(verify_args)) => run_verify(verify_args, &args),
+        Some(Command::Verify2Qwac(two_qwac_args)) => run_verify_2qwac(two_qwac_args, &args),
+        Some(Command::VerifyTimestamp(timestamp_args)) => run_verify_timestamp(timestamp_args, &args),
+        Some(Command::VerifyCms(cms_args)) => run_verify_cms(cms_args, &args),
+        Some(Command::VerifyJades(jades_args)) => run_verify_jades(jades_args, &args),
+        Some(Command::ListEndpoints(endpoints_args)) => run_list_endpoints(endpoints_args, &args),
+        Some(Command::ListTsps(tsps_args)) => run_list_tsps(tsps_args, &args),
+        None => run_extract(&args),
+    };
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Use clap for argument parsing if --target_folder is provided with clap syntax
-    // Otherwise fall back to manual parsing for backward compatibility
-    let args_vec: Vec<String> = env::args().collect();
-    
-    // Check if we have at least the binary name
-    if args_vec.len() < 1 {
-        eprintln!("Usage: ca_extractor <service> <country> [--target_folder <target_folder>]");
-        return Ok(());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match args.error_format {
+                ErrorFormat::Text => report_error(&e, args.explain_errors),
+                ErrorFormat::Json => eprintln!("{}", e.to_json()),
+            }
+            ExitCode::from(e.exit_code())
+        }
     }
-    
-    let (service, country, target_folder) = if args_vec.len() > 1 && (args_vec[1] == "-h" || args_vec[1] == "--help") {
-        // If help is requested, use clap to show help and exit
-        let _args = Args::parse();
-        return Ok(());
-    } else if args_vec.len() >= 3 {
-        // Manual parsing for backward compatibility
-        let service_str = &args_vec[1];
-        let country = &args_vec[2];
-        
-        // Parse service type
-        let service = match service_str.as_str() {
-            "QWAC" => ServiceType::QWAC,
-            "QSealC" => ServiceType::QSealC,
-            _ => return Err(CaExtractorError::InvalidServiceType(service_str.clone()).into()),
-        };
-        
-        // Parse target folder
-        let target_folder = if args_vec.len() > 3 && args_vec[3] == "--target_folder" && args_vec.len() > 4 {
-            &args_vec[4]
-        } else {
-            "."
-        };
-        
-        (service, country.clone(), target_folder.to_string())
-    } else {
-        // Not enough arguments
-        eprintln!("Usage: ca_extractor <service> <country> [--target_folder <target_folder>]");
-        return Ok(());
-    };
+}
+
+/// Print an error with its cause chain; with `explain` also print the
+/// structured context and an excerpt of the offending XML
+fn report_error(error: &CaExtractorError, explain: bool) {
+    let mut messages = vec![error.to_string()];
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        let message = cause.to_string();
+        // Wrapping variants repeat their cause's message; print it only once
+        if !messages.last().is_some_and(|previous| previous.contains(&message)) {
+            messages.push(message);
+        }
+        source = cause.source();
+    }
+
+    eprintln!("Error: {}", messages[0]);
+    for message in &messages[1..] {
+        eprintln!("  caused by: {}", message);
+    }
+
+    if !explain {
+        return;
+    }
+
+    match error.context() {
+        Some(context) => {
+            if let Some(source) = &context.source {
+                eprintln!("  source:   {}", source);
+            }
+            if let (Some(line), Some(column)) = (context.line, context.column) {
+                eprintln!("  position: line {}, column {}", line, column);
+            }
+            if let Some(tsp) = &context.tsp {
+                eprintln!("  TSP:      {}", tsp);
+            }
+            if let Some(service) = &context.service {
+                eprintln!("  service:  {}", service);
+            }
+            if let Some(snippet) = &context.snippet {
+                eprintln!();
+                eprint!("{}", snippet);
+            }
+        }
+        None => eprintln!("  (no position information available for this error)"),
+    }
+}
+
+/// Extract CA certificates for the service and country given on the command line
+fn run_extract(args: &Args) -> Result<(), CaExtractorError> {
+    let limits = args.limits();
+
+    // Both positionals are required by clap whenever no subcommand is given
+    let country = args.country.clone().expect("country is a required argument");
+    let target_folder = args.target_folder.clone();
 
     // Create extractor and fetch certificates
-    let extractor = CertificateExtractor::new(service, &country)?;
+    let extractor = CertificateExtractor::new(&args.service, &country)?
+        .with_qualification(args.qualification)
+        .with_qualifiers(args.qualifiers.clone())
+        .without_qualifiers(args.excluded_qualifiers.clone())
+        .without_taken_over(args.exclude_taken_over)
+        .with_limits(limits);
+    let extractor = match args.at {
+        Some(time) => extractor.with_time(time),
+        None => extractor,
+    };
+    let extractor = args.certificate_filter.configure(extractor);
     
     // Fetch XML content from API
     println!("Fetching data from eIDAS Trusted List for country: {}", country);
-    let xml_content = match extractor.fetch_xml_content() {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error fetching data: {}", e);
-            return Err(e.into());
+    let xml_content = extractor.fetch_xml_content()?;
+    let source = format!("trusted list of {}", country);
+
+    if args.validate {
+        println!("Validating trusted list against the ETSI TS 119 612 schema...");
+        report_schema_result(schema::check(&xml_content, &limits), &xml_content, args.explain_errors)
+            .map_err(|e| e.with_context(ErrorContext::source(source.clone())))?;
+    }
+
+    println!("Attempting to extract certificates...");
+    let mut list = extractor
+        .parse_trusted_list(&xml_content)
+        .map_err(|e| e.with_context(ErrorContext::source(source)))?;
+    list.prefer_languages(&args.lang);
+
+    if args.explain || args.explain_fingerprint.is_some() {
+        return explain_selection(&extractor, &list, args.explain_fingerprint.as_deref());
+    }
+
+    let mut certs = extractor.select(&list);
+    let ocsp = args.ocsp.client(limits);
+    if args.check_revocation || ocsp.is_some() {
+        println!("Checking revocation of the extracted CA certificates...");
+        let cache = args.check_revocation.then(|| CrlCache::new(args.crl_cache_dir()).with_limits(limits));
+        revocation::check_extracted(&mut certs, &list, cache.as_ref(), ocsp.as_ref(), chrono::Utc::now());
+        for cert in &certs {
+            if let Some(status) = cert.revocation.as_ref().filter(|status| !matches!(status, RevocationStatus::Good)) {
+                let subject = x509::Certificate::from_der(cert.der).map(|c| c.subject).unwrap_or_default();
+                println!("{}: {}", subject, status);
+            }
+        }
+        if args.revoked == RevokedAction::Drop {
+            certs.retain(|cert| !cert.revocation.as_ref().is_some_and(RevocationStatus::is_revoked));
+        }
+    }
+    if certs.is_empty() {
+        return Err(CaExtractorError::NoCertificatesFound {
+            country: country.clone(),
+            service: extractor.service_names(),
+        });
+    }
+
+    fs::create_dir_all(&target_folder)?;
+
+    match args.output_format {
+        OutputFormat::Pem => {
+            for (i, cert) in certs.iter().enumerate() {
+                let filename = format!("{}/{}_{}.pem", target_folder, country, i);
+                fs::write(&filename, cert.annotated_pem())?;
+                println!("Wrote {} ({})", filename, cert.qualification());
+            }
+        }
+        OutputFormat::Json => {
+            let filename = format!("{}/{}.json", target_folder, country);
+            let document = serde_json::json!({
+                "country": country,
+                "sequence_number": list.sequence_number,
+                "certificates": certs.iter().map(|cert| cert.to_json()).collect::<Vec<_>>(),
+            });
+            fs::write(&filename, serde_json::to_string_pretty(&document).expect("JSON values serialize"))?;
+            println!("Wrote {}", filename);
+        }
+    }
+
+    println!("Successfully extracted {} certificates", certs.len());
+
+    Ok(())
+}
+
+/// Print the decision trail of every certificate of the trusted list, or of
+/// the one with the given fingerprint
+fn explain_selection(
+    extractor: &CertificateExtractor,
+    list: &TrustedList,
+    fingerprint: Option<&str>,
+) -> Result<(), CaExtractorError> {
+    let mut explanations = extractor.explain(list);
+    if let Some(fingerprint) = fingerprint {
+        explanations.retain(|explanation| explanation.has_fingerprint(fingerprint));
+        if explanations.is_empty() {
+            return Err(CaExtractorError::CertificateExtractionError(format!(
+                "no certificate with SHA-256 fingerprint {} is listed in the trusted list",
+                fingerprint
+            )));
+        }
+    }
+
+    for explanation in &explanations {
+        println!("{}", explanation);
+    }
+    let selected = explanations.iter().filter(|explanation| explanation.is_selected()).count();
+    println!("{} of {} listed certificate(s) selected", selected, explanations.len());
+    Ok(())
+}
+
+/// Read the trusted list named on the command line, from disk or the API.
+///
+/// Returns the document and a description of where it came from.
+fn load_trusted_list(source: &ListSourceArgs, limits: &ParseLimits) -> Result<(String, String), CaExtractorError> {
+    match (&source.file, &source.country) {
+        (Some(path), _) => {
+            let description = format!("file {}", path.display());
+            fs::File::open(path)
+                .map_err(CaExtractorError::from)
+                .and_then(|file| limits.read_limited(file))
+                .map(|content| (content, description.clone()))
+                .map_err(|e| e.with_context(ErrorContext::source(description)))
+        }
+        (None, Some(country)) => {
+            fetch_trusted_list(country, limits).map(|content| (content, format!("trusted list of {}", country)))
+        }
+        (None, None) => unreachable!("clap requires either a country or --file"),
+    }
+}
+
+/// Validate a downloaded or local trusted list and report every schema violation
+fn run_validate(source: &ListSourceArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let limits = args.limits();
+    let (xml_content, description) = load_trusted_list(source, &limits)?;
+
+    report_schema_result(schema::check(&xml_content, &limits), &xml_content, args.explain_errors)
+        .map_err(|e| e.with_context(ErrorContext::source(description)))?;
+    println!("Trusted list conforms to the ETSI TS 119 612 schema");
+    Ok(())
+}
+
+fn report_schema_result(
+    result: Result<(), CaExtractorError>,
+    xml_content: &str,
+    explain: bool,
+) -> Result<(), CaExtractorError> {
+    if let Err(CaExtractorError::SchemaValidationFailed(violations)) = &result {
+        for violation in violations {
+            eprintln!("Schema violation at {}", violation);
+            if explain {
+                eprint!("{}", error::snippet(xml_content, violation.line, violation.column));
+                eprintln!();
+            }
+        }
+    }
+    result
+}
+
+/// Run the semantic conformance rules and report every finding
+fn run_check(source: &ListSourceArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let limits = args.limits();
+    let (xml_content, description) = load_trusted_list(source, &limits)?;
+    let mut list = TrustedList::parse(&xml_content, &limits)
+        .map_err(|e| e.with_context(ErrorContext::source(description)))?;
+    list.prefer_languages(&args.lang);
+
+    let findings = conformance::check(&list);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    if errors > 0 {
+        return Err(CaExtractorError::ConformanceFailed { errors });
+    }
+
+    println!(
+        "Trusted list passes all {} conformance rules ({} warning(s))",
+        conformance::RULES.len(),
+        findings.len()
+    );
+    Ok(())
+}
+
+/// Read a local file, naming it in the error
+fn read_file(path: &std::path::Path) -> Result<Vec<u8>, CaExtractorError> {
+    let source = format!("file {}", path.display());
+    fs::read(path).map_err(|e| CaExtractorError::from(e).with_context(ErrorContext::source(source)))
+}
+
+/// Read a PEM bundle or DER certificate file
+fn read_certificates(path: &std::path::Path) -> Result<Vec<x509::Certificate>, CaExtractorError> {
+    let source = format!("file {}", path.display());
+    fs::read(path)
+        .map_err(CaExtractorError::from)
+        .and_then(|data| x509::parse_certificates(&data))
+        .map_err(|e| e.with_context(ErrorContext::source(source)))
+}
+
+/// Read and parse the trusted list named on the command line, ordering its
+/// names by the language preference
+fn load_list(source: &ListSourceArgs, args: &Args) -> Result<TrustedList, CaExtractorError> {
+    let limits = args.limits();
+    let (xml_content, description) = load_trusted_list(source, &limits)?;
+    let mut list =
+        TrustedList::parse(&xml_content, &limits).map_err(|e| e.with_context(ErrorContext::source(description)))?;
+    list.prefer_languages(&args.lang);
+    Ok(list)
+}
+
+/// Read the certificate, its intermediates and the trusted list named on the command line
+fn load_certificate_args(
+    certificate_args: &CertificateArgs,
+    args: &Args,
+) -> Result<(x509::Certificate, Vec<x509::Certificate>, TrustedList), CaExtractorError> {
+    let mut certificates = read_certificates(&certificate_args.cert)?;
+    let certificate = certificates.remove(0);
+    if let Some(chain) = &certificate_args.chain {
+        certificates.extend(read_certificates(chain)?);
+    }
+
+    let list = load_list(&certificate_args.source, args)?;
+    Ok((certificate, certificates, list))
+}
+
+/// Country of a loaded trusted list: the one requested, else its scheme territory
+fn list_country(source: &ListSourceArgs, list: &TrustedList) -> String {
+    source
+        .country
+        .clone()
+        .or_else(|| list.scheme_territory.clone())
+        .unwrap_or_else(|| "EU".to_string())
+}
+
+/// Determine the qualification of an end-entity certificate and print the reasoning
+fn run_qualify(certificate_args: &CertificateArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let (certificate, intermediates, list) = load_certificate_args(certificate_args, args)?;
+
+    let result = qualify::qualify(&list, &certificate, &intermediates);
+    match certificate_args.format {
+        ReportFormat::Text => {
+            println!("Certificate: {}", certificate.subject);
+            for (i, step) in result.steps.iter().enumerate() {
+                println!("  {}. {}", i + 1, step);
+            }
+            println!("Result: {}", result);
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&result.to_json()).expect("JSON values serialize"));
+        }
+    }
+    Ok(())
+}
+
+/// Print the QcStatements of a certificate and how they relate to the QWAC and QSealC CAs
+fn run_inspect_qc(certificate_args: &CertificateArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let (certificate, intermediates, list) = load_certificate_args(certificate_args, args)?;
+
+    let country = list_country(&certificate_args.source, &list);
+    // CAs of services no longer in force are included, to report their status
+    let extractor = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], &country)?;
+    let mut cas = extractor.clone().with_qualification(QualificationFilter::All).select_anchors(&list, &intermediates);
+    cas.extend(extractor.with_qualification(QualificationFilter::Withdrawn).select_anchors(&list, &intermediates));
+    let inspection = inspect::inspect_qc(&certificate, &intermediates, &cas);
+
+    if certificate_args.format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&inspection.to_json()).expect("JSON values serialize"));
+        return Ok(());
+    }
+
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let statements = &certificate.qc_statements;
+    println!("Certificate: {}", certificate.subject);
+    if let Some(value) = &certificate.organization_identifier {
+        match &inspection.organization_identifier {
+            Some(id) => println!(
+                "organizationIdentifier: {} (scheme {}, country {}, reference {})",
+                value, id.scheme, id.country, id.reference
+            ),
+            None => println!("organizationIdentifier: {} (not in ETSI EN 319 412-1 form)", value),
+        }
+    }
+    println!("QcStatements:");
+    println!("  QcCompliance:      {}", yes_no(statements.compliance));
+    let types: Vec<String> = statements.types.iter().map(|qc_type| qc_type.to_string()).collect();
+    println!("  QcType:            {}", if types.is_empty() { "-".to_string() } else { types.join(", ") });
+    println!("  QcSSCD:            {}", yes_no(statements.sscd));
+    match statements.retention_period {
+        Some(years) => println!("  QcRetentionPeriod: {} years", years),
+        None => println!("  QcRetentionPeriod: -"),
+    }
+    for pds in &statements.pds {
+        println!("  QcPDS:             {} ({})", pds.url, pds.language);
+    }
+    if let Some(psd2) = &statements.psd2 {
+        let roles: Vec<&str> = psd2.roles.iter().map(|role| role.name.as_str()).collect();
+        println!("  PSD2 roles:        {}", roles.join(", "));
+        println!("  PSD2 NCA:          {} ({})", psd2.nca_name, psd2.nca_id);
+    }
+
+    match &inspection.issuer {
+        Some(ca) => {
+            let types: Vec<String> = ca.service_types.iter().map(ServiceType::to_string).collect();
+            println!(
+                "Issued under {} CA '{}' of '{}' ({}, status {})",
+                types.join("/"),
+                ca.service.information.name().unwrap_or_default(),
+                ca.provider.name().unwrap_or_default(),
+                ca.qualification(),
+                etsi::short_name(&ca.service.information.status)
+            );
         }
+        None => println!("Issuing CA: not found among the QWAC and QSealC CAs of the trusted list"),
+    }
+    if inspection.issues.is_empty() {
+        println!("No inconsistencies found");
+    }
+    for issue in &inspection.issues {
+        println!("Issue: {}", issue);
+    }
+    Ok(())
+}
+
+/// Print the intermediates and the anchoring service of a validated path
+fn print_path(path: &chain::ValidPath) {
+    for cert in &path.chain[1..] {
+        println!("  issued by intermediate: {}", cert.subject);
+    }
+    let types: Vec<String> = path.anchor.service_types.iter().map(ServiceType::to_string).collect();
+    println!(
+        "  anchored by {} service '{}' of TSP '{}' ({}, {})",
+        types.join("/"),
+        path.anchor.service.information.name().unwrap_or_default(),
+        path.anchor.provider.name().unwrap_or_default(),
+        path.anchor.qualification(),
+        path.anchor_status.as_deref().map_or("not listed yet", etsi::short_name)
+    );
+}
+
+/// Report every rejected candidate path on stderr and turn them into an error
+fn path_failed(subject: &str, failures: Vec<chain::PathFailure>, args: &Args) -> CaExtractorError {
+    if args.error_format == ErrorFormat::Text {
+        eprintln!("Certificate: {}", subject);
+        for failure in &failures {
+            eprintln!("Path rejected: {}", failure);
+        }
+    }
+    CaExtractorError::PathValidationFailed(failures)
+}
+
+/// Validate the certification path of a certificate to a CA of the trusted list
+fn run_verify(verify_args: &VerifyArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let certificate_args = &verify_args.certificate;
+    let (certificate, intermediates, list) = load_certificate_args(certificate_args, args)?;
+
+    let country = list_country(&certificate_args.source, &list);
+    let options = verify_args.path_options();
+    let anchors = verify_args.anchor_extractor(&country, options.time)?.select_anchors(&list, &intermediates);
+
+    let path = match chain::validate(&certificate, &intermediates, &anchors, &options) {
+        Ok(path) => path,
+        Err(failures) => return Err(path_failed(&certificate.subject, failures, args)),
     };
 
-    // Try to parse XML and extract certificates
-    println!("Attempting to extract certificates...");
-    
-    // Convert service type to string for the error messages
-    let service_str: String = match service {
-        ServiceType::QWAC => "QWAC".to_string(),
-        ServiceType::QSealC => "QSealC".to_string(),
+    let statuses = match verify_args.ocsp.client(args.limits()) {
+        Some(client) => client.check_path(&path, options.time)?,
+        None => Vec::new(),
     };
-    
-    // Use safer try_parse method to avoid XML parsing failures
-    let certificates = try_parse_xml(&xml_content, &country, &service_str);
-    
-    // Write certificates to files
-    if let Ok(certs) = certificates {
-        if certs.is_empty() {
-            println!("No certificates found for {} in country {}", service_str, country);
-            return Ok(());
-        }
-        
-        fs::create_dir_all(&target_folder)?;
-        
-        for (i, cert) in certs.iter().enumerate() {
-            let filename = format!("{}/{}_{}.pem", target_folder, country, i);
-            fs::write(&filename, cert)?;
-            println!("Wrote {}", filename);
+
+    match certificate_args.format {
+        ReportFormat::Text => {
+            println!("Certificate: {}", certificate.subject);
+            print_path(&path);
+            for (cert, status) in path.chain.iter().zip(&statuses) {
+                println!("  OCSP status of {}: {}", cert.subject, status);
+            }
+            println!("Path valid at {}", options.time.to_rfc3339());
+        }
+        ReportFormat::Json => {
+            let mut report = path.to_json();
+            report["valid_at"] = serde_json::json!(options.time.to_rfc3339());
+            if verify_args.ocsp.ocsp {
+                report["ocsp"] = path
+                    .chain
+                    .iter()
+                    .zip(&statuses)
+                    .map(|(cert, status)| {
+                        let mut entry = status.to_json();
+                        entry["certificate"] = serde_json::json!(cert.subject);
+                        entry
+                    })
+                    .collect();
+            }
+            println!("{}", serde_json::to_string_pretty(&report).expect("JSON values serialize"));
         }
-        
-        println!("Successfully extracted {} certificates", certs.len());
-    } else if let Err(e) = certificates {
-        eprintln!("Failed to extract certificates: {}", e);
-        return Err(e.into());
     }
 
+    if let Some((cert, status)) = path.chain.iter().zip(&statuses).find(|(_, status)| status.is_revoked()) {
+        return Err(CaExtractorError::CertificateRevoked(format!("'{}' is {}", cert.subject, status)));
+    }
     Ok(())
 }
 
-/// Safely attempt to parse the XML content and extract certificates
-fn try_parse_xml(xml_content: &str, country: &str, service: &str) -> Result<Vec<String>, CaExtractorError> {
-    // If content is not XML, try to see if it's JSON indicating an error
-    if !xml_content.trim().starts_with("<?xml") && !xml_content.trim().starts_with("<") {
-        // Check if it's likely a JSON response (might be an error)
-        if xml_content.trim().starts_with("{") || xml_content.trim().starts_with("[") {
-            return Err(CaExtractorError::InvalidResponseFormat(
-                format!("API returned non-XML content, possibly JSON: {}", 
-                        &xml_content.chars().take(100).collect::<String>())
-            ));
-        }
-        
-        // Otherwise, it's some other format we don't understand
-        return Err(CaExtractorError::InvalidResponseFormat(
-            format!("API returned unrecognized content: {}", 
-                    &xml_content.chars().take(100).collect::<String>())
-        ));
+/// Validate a 2-QWAC TLS certificate binding for a TLS certificate
+fn run_verify_2qwac(two_qwac_args: &TwoQwacArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let source = format!("file {}", two_qwac_args.binding.display());
+    let binding = fs::read_to_string(&two_qwac_args.binding)
+        .map_err(|e| CaExtractorError::from(e).with_context(ErrorContext::source(source)))?;
+    let tls_certificate = read_certificates(&two_qwac_args.tls_cert)?.remove(0);
+    let intermediates = match &two_qwac_args.chain {
+        Some(chain) => read_certificates(chain)?,
+        None => Vec::new(),
+    };
+
+    let list = load_list(&two_qwac_args.source, args)?;
+    let options = two_qwac_args.path_options();
+    let anchors = two_qwac_args
+        .anchor_extractor(&list_country(&two_qwac_args.source, &list), options.time)?
+        .select_anchors(&list, &intermediates);
+
+    let validation = tls_binding::validate(&binding, &tls_certificate, &intermediates, &anchors, &options)
+        .map_err(|e| match e {
+            CaExtractorError::PathValidationFailed(failures) => path_failed("2-QWAC of the binding", failures, args),
+            other => other,
+        })?;
+
+    match two_qwac_args.format {
+        ReportFormat::Text => {
+            println!("TLS certificate: {}", tls_certificate.subject);
+            println!("  bound by the 2-QWAC: {}", validation.binding.qwac.subject);
+            print_path(&validation.path);
+            println!("2-QWAC binding valid at {}", options.time.to_rfc3339());
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&validation.to_json()).expect("JSON values serialize"));
+        }
     }
-    
-    // Use a closure to attempt the XML parsing
-    let parse_result = || -> Result<Vec<String>, CaExtractorError> {
-        let parser = xml::reader::EventReader::new(xml_content.as_bytes());
-        let mut current_element = String::new();
-        let mut elements = Vec::new();
-        
-        for event in parser {
-            match event? {
-                xml::reader::XmlEvent::StartElement { name, .. } => {
-                    current_element = name.local_name;
+    Ok(())
+}
+
+/// Validate a time-stamp token against the qualified time-stamping services of the trusted list
+fn run_verify_timestamp(timestamp_args: &TimestampArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let source = format!("file {}", timestamp_args.token.display());
+    let token = TimeStampToken::parse(&read_file(&timestamp_args.token)?)
+        .map_err(|e| e.with_context(ErrorContext::source(source)))?;
+    let data = timestamp_args.data.as_deref().map(read_file).transpose()?;
+    let certificates = match &timestamp_args.chain {
+        Some(chain) => read_certificates(chain)?,
+        None => Vec::new(),
+    };
+
+    let list = load_list(&timestamp_args.source, args)?;
+    let validation = timestamp::validate(token, data.as_deref(), &certificates, &list)?;
+
+    match timestamp_args.format {
+        ReportFormat::Text => {
+            let info = &validation.token.info;
+            println!("Time-stamp: {} (policy {})", info.gen_time.to_rfc3339(), info.policy);
+            println!("  signed by the TSA: {}", validation.tsa.subject);
+            if validation.imprint_verified {
+                println!("  message imprint matches the data");
+            }
+            println!(
+                "  QTST service '{}' of TSP '{}' is '{}' at the time-stamp time",
+                validation.information.name().unwrap_or_default(),
+                validation.provider.name().unwrap_or_default(),
+                ca_extractor::etsi::short_name(&validation.information.status)
+            );
+            println!("Qualified electronic time-stamp");
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&validation.to_json()).expect("JSON values serialize"));
+        }
+    }
+    Ok(())
+}
+
+/// Print the path and qualification of a validated signer
+fn print_signer(validation: &cades::SignerValidation) {
+    println!("Signer: {}", validation.certificate.subject);
+    match validation.signing_time {
+        Some(signing_time) => println!("  signing time: {}", signing_time.to_rfc3339()),
+        None => println!("  no signing time, validated at {}", validation.validation_time.to_rfc3339()),
+    }
+    print_path(&validation.path);
+    println!("  qualification at issuance: {}", validation.qualification);
+    if let Some(status) = &validation.service_status {
+        println!("  CA/QC service status at signing time: {}", ca_extractor::etsi::short_name(status));
+    }
+    println!(
+        "  {} at signing time",
+        if validation.qualified_at_signing_time() { "qualified" } else { "not qualified" }
+    );
+}
+
+/// Validate a detached CMS signature and report the qualification of each signer
+fn run_verify_cms(cms_args: &CmsArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let signature = read_file(&cms_args.signature)?;
+    let data = read_file(&cms_args.data)?;
+    let certificates = match &cms_args.chain {
+        Some(chain) => read_certificates(chain)?,
+        None => Vec::new(),
+    };
+
+    let list = load_list(&cms_args.source, args)?;
+    let country = list_country(&cms_args.source, &list);
+    let extractor = CertificateExtractor::new(&[ServiceType::QSealC, ServiceType::QESig], &country)?;
+    // The CA/QC services are selected by their status at each signing time
+    let anchors_at = |time| extractor.clone().with_time(time).select_anchors(&list, &certificates);
+    let time = cms_args.at.unwrap_or_else(chrono::Utc::now);
+
+    let source = format!("file {}", cms_args.signature.display());
+    let validations = cades::validate(&signature, &data, &certificates, anchors_at, &list, time).map_err(|e| match e {
+        CaExtractorError::PathValidationFailed(failures) => path_failed("signer", failures, args),
+        other => other.with_context(ErrorContext::source(source)),
+    })?;
+
+    match cms_args.format {
+        ReportFormat::Text => {
+            validations.iter().for_each(print_signer);
+            println!("Signature valid");
+        }
+        ReportFormat::Json => {
+            let report: Vec<_> = validations.iter().map(cades::SignerValidation::to_json).collect();
+            println!("{}", serde_json::to_string_pretty(&report).expect("JSON values serialize"));
+        }
+    }
+    Ok(())
+}
+
+/// Validate a JAdES signature and report the qualification of its signer
+fn run_verify_jades(jades_args: &JadesArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let source = format!("file {}", jades_args.signature.display());
+    let signature = String::from_utf8_lossy(&read_file(&jades_args.signature)?).into_owned();
+    let payload = jades_args.payload.as_deref().map(read_file).transpose()?;
+    let certificates = match &jades_args.chain {
+        Some(chain) => read_certificates(chain)?,
+        None => Vec::new(),
+    };
+
+    let list = load_list(&jades_args.source, args)?;
+    let country = list_country(&jades_args.source, &list);
+    let extractor = CertificateExtractor::new(&[ServiceType::QSealC, ServiceType::QESig], &country)?;
+    // The CA/QC services are selected by their status at sigT
+    let anchors_at = |time| extractor.clone().with_time(time).select_anchors(&list, &certificates);
+
+    let validation = jades::validate(&signature, payload.as_deref(), &certificates, anchors_at, &list).map_err(|e| {
+        match e {
+            CaExtractorError::PathValidationFailed(failures) => path_failed("signer", failures, args),
+            other => other.with_context(ErrorContext::source(source)),
+        }
+    })?;
+
+    match jades_args.format {
+        ReportFormat::Text => {
+            println!("Payload: {} bytes", validation.jws.payload.len());
+            print_signer(&validation.signer);
+            println!("Signature valid");
+        }
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&validation.to_json()).expect("JSON values serialize"));
+        }
+    }
+    Ok(())
+}
+
+/// List the endpoints of the services selected from a trusted list
+fn run_list_endpoints(endpoints_args: &EndpointsArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let list = load_list(&endpoints_args.source, args)?;
+    let country = list_country(&endpoints_args.source, &list);
+    let extractor =
+        CertificateExtractor::new(&endpoints_args.service, &country)?.with_qualification(endpoints_args.qualification);
+    let certificates = endpoints_args.certificate_filter.configure(extractor).select(&list);
+    let endpoints = endpoints::collect(&certificates);
+
+    match endpoints_args.format {
+        EndpointFormat::Text => {
+            let mut previous: Option<&Endpoint> = None;
+            for endpoint in &endpoints {
+                if !previous.is_some_and(|previous| std::ptr::eq(previous.service, endpoint.service)) {
+                    println!(
+                        "{} / {} ({})",
+                        endpoint.provider.name().unwrap_or_default(),
+                        endpoint.service.information.name().unwrap_or_default(),
+                        endpoint.service.information.service_type
+                    );
                 }
-                xml::reader::XmlEvent::Characters(content) if current_element == "TSPService" => {
-                    elements.push(content);
+                println!("  {:<18} {}", endpoint.kind.to_string(), endpoint.url);
+                previous = Some(endpoint);
+            }
+        }
+        EndpointFormat::Json => {
+            let document: Vec<_> = endpoints.iter().map(Endpoint::to_json).collect();
+            println!("{}", serde_json::to_string_pretty(&document).expect("JSON values serialize"));
+        }
+        EndpointFormat::Hosts => {
+            for host in endpoints::hosts(&endpoints) {
+                println!("{}", host);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List the TSPs of a trusted list matching the name and identifier filters
+fn run_list_tsps(tsps_args: &TspsArgs, args: &Args) -> Result<(), CaExtractorError> {
+    let list = load_list(&tsps_args.source, args)?;
+    let providers: Vec<&TrustServiceProvider> = list
+        .providers
+        .iter()
+        .filter(|provider| tsps_args.name.as_ref().is_none_or(|name| provider.matches_name(name)))
+        .filter(|provider| tsps_args.identifier.as_ref().is_none_or(|id| provider.has_identifier(id)))
+        .collect();
+
+    match tsps_args.format {
+        ReportFormat::Text => {
+            let langs = &args.lang;
+            let text = |texts: &[LocalizedText]| model::preferred(texts, langs).map(|text| text.value.clone());
+            for provider in providers {
+                println!("{}", text(&provider.names).unwrap_or_else(|| "<unnamed>".to_string()));
+                let identifiers: Vec<&str> = provider.registration_identifiers().collect();
+                let trade_names: Vec<LocalizedText> = provider
+                    .trade_names
+                    .iter()
+                    .filter(|name| !identifiers.contains(&name.value.as_str()))
+                    .cloned()
+                    .collect();
+                if let Some(trade_name) = text(&trade_names) {
+                    println!("  Trade name:         {}", trade_name);
                 }
-                xml::reader::XmlEvent::EndElement { .. } => {
-                    current_element.clear();
+                if !identifiers.is_empty() {
+                    println!("  Identifiers:        {}", identifiers.join(", "));
                 }
-                _ => {}
-            }
-        }
-        
-        // For tests, use direct certificate extraction
-        if xml_content.contains("<tsl:X509Certificate>") {
-            let mut certificates = Vec::new();
-            let parts: Vec<&str> = xml_content.split("<tsl:X509Certificate>").collect();
-            
-            for (i, part) in parts.iter().enumerate() {
-                if i == 0 { continue; } // Skip first part (before first certificate)
-                
-                if let Some(cert_end) = part.find("</tsl:X509Certificate>") {
-                    let cert_content = &part[0..cert_end].trim().replace([' ', '\n'], "");
-                    
-                    let wrapped_cert_str = format!(
-                        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
-                        cert_content
-                            .as_bytes()
-                            .chunks(64)
-                            .map(|chunk| std::str::from_utf8(chunk).unwrap())
-                            .collect::<Vec<&str>>()
-                            .join("\n")
-                    );
-                    
-                    certificates.push(wrapped_cert_str);
+                if let Some(address) = provider.postal_address(langs) {
+                    println!("  Postal address:     {}", address);
                 }
-            }
-            
-            if !certificates.is_empty() {
-                return Ok(certificates);
-            }
-        }
-        
-        // Regular processing of elements
-        let mut certificates = Vec::new();
-        
-        for element in elements {
-            if let Some(cert_start) = element.find("<tsl:X509Certificate>") {
-                if let Some(cert_end) = element.find("</tsl:X509Certificate>") {
-                    let cert_content = &element[cert_start + 19..cert_end].trim().replace([' ', '\n'], "");
-
-                    let wrapped_cert_str = format!(
-                        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
-                        cert_content
-                            .as_bytes()
-                            .chunks(64)
-                            .map(|chunk| std::str::from_utf8(chunk).unwrap())
-                            .collect::<Vec<&str>>()
-                            .join("\n")
-                    );
-                    
-                    certificates.push(wrapped_cert_str);
+                for address in &provider.electronic_addresses {
+                    println!("  Electronic address: {}", address.value);
+                }
+                if let Some(uri) = text(&provider.information_uris) {
+                    println!("  Information URI:    {}", uri);
+                }
+                for service in &provider.services {
+                    let information = &service.information;
+                    let name = text(&information.names).unwrap_or_else(|| "<unnamed>".to_string());
+                    println!("  Service:            {} ({})", name, information.service_type);
                 }
             }
         }
-        
-        if certificates.is_empty() {
-            return Err(CaExtractorError::NoCertificatesFound {
-                country: country.to_string(),
-                service: service.to_string(),
-            });
+        ReportFormat::Json => {
+            let document: Vec<_> = providers
+                .iter()
+                .map(|provider| {
+                    let mut tsp = provider.to_json();
+                    tsp["services"] = provider
+                        .services
+                        .iter()
+                        .map(|service| {
+                            serde_json::json!({
+                                "names": model::names_to_json(&service.information.names),
+                                "service_type": service.information.service_type,
+                                "status": service.information.status,
+                            })
+                        })
+                        .collect();
+                    tsp
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&document).expect("JSON values serialize"));
         }
-        
-        Ok(certificates)
-    };
-    
-    // Try to parse, and return a more user-friendly error if parsing fails
-    parse_result().map_err(|e| {
-        match e {
-            CaExtractorError::XmlError(_) => CaExtractorError::CertificateExtractionError(
-                "Failed to parse XML response. The API may have changed or returned invalid XML.".to_string()
-            ),
-            _ => e
-        }
-    })
+    }
+    Ok(())
 }
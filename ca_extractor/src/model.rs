@@ -0,0 +1,852 @@
+//! Typed model of an ETSI TS 119 612 trusted list.
+
+use std::fmt;
+
+use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use ring::digest;
+use serde_json::{json, Value};
+
+use crate::dom::{self, Element};
+use crate::error::{CaExtractorError, ErrorContext};
+use crate::etsi;
+use crate::inspect::OrganizationIdentifier;
+use crate::limits::ParseLimits;
+use crate::x509::Certificate;
+
+/// Position of an element in the source document
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    pub line: u64,
+    pub column: u64,
+}
+
+impl Location {
+    fn of(element: &Element) -> Self {
+        Location {
+            line: element.line,
+            column: element.column,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A text value tagged with its `xml:lang`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizedText {
+    pub lang: String,
+    pub value: String,
+}
+
+impl LocalizedText {
+    /// Whether the text is in `lang`, see [`lang_matches`]
+    pub fn is_in(&self, lang: &str) -> bool {
+        lang_matches(&self.lang, lang)
+    }
+}
+
+/// Whether the `xml:lang` value `tag` is the language `lang`, ignoring case;
+/// `en` also matches regional variants such as `en-GB`
+pub fn lang_matches(tag: &str, lang: &str) -> bool {
+    tag.eq_ignore_ascii_case(lang)
+        || tag.get(..lang.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(lang))
+            && tag[lang.len()..].starts_with('-')
+}
+
+/// The text in the first of `langs` available, else the first text
+pub fn preferred<'a>(texts: &'a [LocalizedText], langs: &[String]) -> Option<&'a LocalizedText> {
+    langs
+        .iter()
+        .find_map(|lang| texts.iter().find(|text| text.is_in(lang)))
+        .or_else(|| texts.first())
+}
+
+/// Stable-sorts `items` so that those in the first of `langs` come first,
+/// then those in the second, and so on; the others keep their order at the end
+fn sort_by_language<T>(items: &mut [T], lang: impl Fn(&T) -> &str, langs: &[String]) {
+    items.sort_by_key(|item| {
+        langs
+            .iter()
+            .position(|preferred| lang_matches(lang(item), preferred))
+            .unwrap_or(langs.len())
+    });
+}
+
+/// A `PostalAddress`, in the language given by its `xml:lang`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostalAddress {
+    pub lang: String,
+    pub street_address: String,
+    pub locality: String,
+    pub state_or_province: Option<String>,
+    pub postal_code: Option<String>,
+    pub country_name: String,
+}
+
+impl fmt::Display for PostalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, ", self.street_address)?;
+        if let Some(postal_code) = &self.postal_code {
+            write!(f, "{} ", postal_code)?;
+        }
+        write!(f, "{}", self.locality)?;
+        if let Some(state) = &self.state_or_province {
+            write!(f, ", {}", state)?;
+        }
+        write!(f, ", {}", self.country_name)
+    }
+}
+
+/// One `DigitalId` entry of a service digital identity. The entries of a
+/// service all describe the same public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DigitalId {
+    /// DER encoded X.509 certificate
+    Certificate(Vec<u8>),
+    /// `X509SubjectName`, a distinguished name in RFC 4514 form
+    SubjectName(String),
+    /// `X509SKI`, the subject key identifier of the service's certificates
+    Ski(Vec<u8>),
+    /// `ds:KeyValue`, a bare public key; not interpreted
+    KeyValue,
+    /// `Other` identity content; not interpreted
+    Other,
+}
+
+impl DigitalId {
+    /// PEM encoding of the certificate, if this identity is one
+    pub fn pem(&self) -> Option<String> {
+        match self {
+            DigitalId::Certificate(der) => Some(der_to_pem(der)),
+            _ => None,
+        }
+    }
+
+    /// JSON description: certificates by their SHA-256 fingerprint, subject
+    /// and subject key identifier, the other identities by their value.
+    /// Key identifiers are lowercase hex.
+    pub fn to_json(&self) -> Value {
+        match self {
+            DigitalId::Certificate(der) => {
+                let certificate = Certificate::from_der(der).ok();
+                json!({
+                    "type": "X509Certificate",
+                    "sha256": hex(digest::digest(&digest::SHA256, der).as_ref()),
+                    "subject": certificate.as_ref().map(|c| c.subject.clone()),
+                    "subject_key_id": certificate.and_then(|c| c.subject_key_id).map(|id| hex(&id)),
+                })
+            }
+            DigitalId::SubjectName(name) => json!({ "type": "X509SubjectName", "value": name }),
+            DigitalId::Ski(ski) => json!({ "type": "X509SKI", "value": hex(ski) }),
+            DigitalId::KeyValue => json!({ "type": "KeyValue" }),
+            DigitalId::Other => json!({ "type": "Other" }),
+        }
+    }
+}
+
+/// How the criteria of a [`CriteriaList`] combine
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum CriteriaAssert {
+    #[default]
+    All,
+    AtLeastOne,
+    None,
+}
+
+/// Certificate criteria of a `sie:QualificationElement`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CriteriaList {
+    pub assert: CriteriaAssert,
+    /// One entry per `KeyUsage` criterion: key usage bit names and their required value
+    pub key_usage: Vec<Vec<(String, bool)>>,
+    /// One entry per `PolicySet` criterion: policy OIDs that must all be present
+    pub policy_sets: Vec<Vec<String>>,
+    /// One entry per `tslx:ExtendedKeyUsage` criterion: key purpose OIDs that must all be present
+    pub extended_key_usage: Vec<Vec<String>>,
+    /// One entry per `tslx:CertSubjectDNAttribute` criterion: attribute OIDs that must all be present
+    pub subject_dn_attributes: Vec<Vec<String>>,
+    pub criteria_lists: Vec<CriteriaList>,
+}
+
+/// A `sie:QualificationElement`: qualifiers applying to the certificates
+/// matching the criteria
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Qualification {
+    pub qualifiers: Vec<String>,
+    pub criteria: CriteriaList,
+    pub location: Location,
+}
+
+/// A `tslx:TakenOverBy` extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TakenOverBy {
+    pub uri: String,
+    pub tsp_names: Vec<LocalizedText>,
+    pub scheme_operator_names: Vec<LocalizedText>,
+    pub scheme_territory: String,
+}
+
+/// Service information shared by the current entry and history instances
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceInformation {
+    pub service_type: String,
+    pub names: Vec<LocalizedText>,
+    pub digital_ids: Vec<DigitalId>,
+    pub status: String,
+    pub status_starting_time: Option<DateTime<Utc>>,
+    /// URIs of `ServiceSupplyPoints`, where CRLs, OCSP responses or other
+    /// outputs of the service are published
+    pub service_supply_points: Vec<String>,
+    /// `TSPServiceDefinitionURI`s, locating the practice statements (CPS)
+    /// of the service
+    pub service_definition_uris: Vec<LocalizedText>,
+    /// URIs of `AdditionalServiceInformation` extensions
+    pub additional_information: Vec<String>,
+    pub qualifications: Vec<Qualification>,
+    pub taken_over_by: Option<TakenOverBy>,
+    /// Date from which revocation information of expired certificates is kept
+    pub expired_certs_revocation_info: Option<DateTime<Utc>>,
+    pub location: Location,
+}
+
+/// Whether a service is a qualified trust service, a nationally recognised,
+/// non-qualified one, or one no longer in force
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QualificationLevel {
+    Qualified,
+    National,
+    /// The current status is neither granted nor recognised at national
+    /// level: `withdrawn`, `deprecatedatnationallevel` or a ceased or revoked
+    /// legacy status (see [`etsi::is_in_force`])
+    Withdrawn,
+}
+
+impl fmt::Display for QualificationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QualificationLevel::Qualified => write!(f, "qualified"),
+            QualificationLevel::National => write!(f, "national"),
+            QualificationLevel::Withdrawn => write!(f, "withdrawn"),
+        }
+    }
+}
+
+impl ServiceInformation {
+    /// The service name: the first `ServiceName`, in the preferred language
+    /// once [`TrustedList::prefer_languages`] has been applied
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(|name| name.value.as_str())
+    }
+
+    fn prefer_languages(&mut self, langs: &[String]) {
+        sort_by_language(&mut self.names, |name| &name.lang, langs);
+        sort_by_language(&mut self.service_definition_uris, |uri| &uri.lang, langs);
+        if let Some(taken_over_by) = &mut self.taken_over_by {
+            sort_by_language(&mut taken_over_by.tsp_names, |name| &name.lang, langs);
+            sort_by_language(&mut taken_over_by.scheme_operator_names, |name| &name.lang, langs);
+        }
+    }
+
+    /// Whether `certificate` carries the service's digital identity: it is
+    /// one of the listed certificates, or the service lists an `X509SKI`
+    /// and the certificate matches it and every `X509SubjectName` listed
+    /// alongside. The latter identifies the CA certificates of services
+    /// listed without one. The key identifier is computed from the
+    /// certificate's key, see [`Certificate::key_identifier`], rather than
+    /// taken from its extension, and a subject name alone never identifies a
+    /// certificate: neither binds the key.
+    pub fn identifies(&self, certificate: &Certificate) -> bool {
+        if self.digital_ids.contains(&DigitalId::Certificate(certificate.der.clone())) {
+            return true;
+        }
+        let key_identifier = certificate.key_identifier();
+        let mut key_bound = false;
+        for id in &self.digital_ids {
+            let matched = match id {
+                DigitalId::Ski(ski) => {
+                    key_bound = true;
+                    *ski == key_identifier
+                }
+                DigitalId::SubjectName(name) => certificate.has_subject_name(name),
+                _ => true,
+            };
+            if !matched {
+                return false;
+            }
+        }
+        key_bound
+    }
+
+    /// Every qualifier URI declared by the service's qualification extensions
+    pub fn qualifiers(&self) -> impl Iterator<Item = &str> {
+        self.qualifications
+            .iter()
+            .flat_map(|qualification| qualification.qualifiers.iter().map(String::as_str))
+    }
+
+    /// Qualification level given by the service type and status. Services of
+    /// a qualified type carrying a national status count as national, and
+    /// services whose status is not in force as withdrawn whatever their type.
+    pub fn qualification_level(&self) -> QualificationLevel {
+        if !etsi::is_in_force(&self.status) {
+            QualificationLevel::Withdrawn
+        } else if etsi::is_qualified_service_type(&self.service_type) && !etsi::is_national_status(&self.status) {
+            QualificationLevel::Qualified
+        } else {
+            QualificationLevel::National
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustService {
+    pub information: ServiceInformation,
+    /// History instances, in document order
+    pub history: Vec<ServiceInformation>,
+}
+
+impl TrustService {
+    /// Service information in force at `time`: the current entry or the
+    /// latest history instance starting no later than `time`. `None` if the
+    /// service was not yet listed then.
+    pub fn information_at(&self, time: DateTime<Utc>) -> Option<&ServiceInformation> {
+        std::iter::once(&self.information)
+            .chain(self.history.iter())
+            .filter(|information| information.status_starting_time.is_some_and(|start| start <= time))
+            .max_by_key(|information| information.status_starting_time)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustServiceProvider {
+    pub names: Vec<LocalizedText>,
+    /// `TSPTradeName`s, which include registration identifiers such as
+    /// `VATDE-123456789` or `NTRDE-HRB12345`
+    pub trade_names: Vec<LocalizedText>,
+    pub postal_addresses: Vec<PostalAddress>,
+    /// URIs of the `ElectronicAddress`, e.g. `mailto:` and `https:` URIs
+    pub electronic_addresses: Vec<LocalizedText>,
+    /// `TSPInformationURI`s, where the TSP publishes its terms and practices
+    pub information_uris: Vec<LocalizedText>,
+    pub services: Vec<TrustService>,
+    pub location: Location,
+}
+
+impl TrustServiceProvider {
+    /// The legal name: the first `TSPName`, in the preferred language once
+    /// [`TrustedList::prefer_languages`] has been applied
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(|name| name.value.as_str())
+    }
+
+    /// Trade names that are registration identifiers in the ETSI EN 319 412-1
+    /// semantics: a three letter scheme such as `VAT` or `NTR`, a country
+    /// code, a hyphen and the reference
+    pub fn registration_identifiers(&self) -> impl Iterator<Item = &str> {
+        self.trade_names
+            .iter()
+            .map(|name| name.value.as_str())
+            .filter(|value| OrganizationIdentifier::parse(value).is_some())
+    }
+
+    /// Whether a name or trade name in any language contains `pattern`, ignoring case
+    pub fn matches_name(&self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        self.names
+            .iter()
+            .chain(&self.trade_names)
+            .any(|name| name.value.to_lowercase().contains(&pattern))
+    }
+
+    /// Whether a registration identifier starts with `prefix`, ignoring case:
+    /// `VATDE-123456789` for one TSP, `VATDE` for every TSP with a German VAT number
+    pub fn has_identifier(&self, prefix: &str) -> bool {
+        self.registration_identifiers()
+            .any(|identifier| identifier.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix)))
+    }
+
+    /// The postal address in the first of `langs` available, else the first one
+    pub fn postal_address(&self, langs: &[String]) -> Option<&PostalAddress> {
+        langs
+            .iter()
+            .find_map(|lang| self.postal_addresses.iter().find(|address| lang_matches(&address.lang, lang)))
+            .or_else(|| self.postal_addresses.first())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedList {
+    pub version: Option<u32>,
+    pub sequence_number: Option<u64>,
+    pub tsl_type: String,
+    pub scheme_operator_names: Vec<LocalizedText>,
+    pub scheme_territory: Option<String>,
+    pub issue_date: Option<DateTime<Utc>>,
+    pub next_update: Option<DateTime<Utc>>,
+    pub providers: Vec<TrustServiceProvider>,
+    pub location: Location,
+}
+
+impl TrustedList {
+    /// Parses a trusted list document, enforcing the given limits
+    pub fn parse(xml_content: &str, limits: &ParseLimits) -> Result<Self, CaExtractorError> {
+        let root = dom::parse_document(xml_content, limits)?;
+        Self::from_element(&root).map_err(|e| e.with_snippet_from(xml_content))
+    }
+
+    /// Builds the model from a parsed `TrustServiceStatusList` element.
+    ///
+    /// Only the root element is required; missing optional or malformed
+    /// values are left empty so that structural problems can be reported by
+    /// the schema and conformance checks instead. Certificates that are not
+    /// valid base64 are an error, located at their `X509Certificate` element.
+    pub fn from_element(root: &Element) -> Result<Self, CaExtractorError> {
+        if root.name != "TrustServiceStatusList" {
+            return Err(CaExtractorError::InvalidResponseFormat(format!(
+                "Expected a TrustServiceStatusList root element, found <{}>",
+                root.name
+            )));
+        }
+
+        let scheme = root.child("SchemeInformation");
+        let scheme_text = |name: &str| scheme.and_then(|s| s.child(name)).map(|e| e.text().to_string());
+
+        Ok(TrustedList {
+            version: scheme_text("TSLVersionIdentifier").and_then(|v| v.parse().ok()),
+            sequence_number: scheme_text("TSLSequenceNumber").and_then(|v| v.parse().ok()),
+            tsl_type: scheme_text("TSLType").unwrap_or_default(),
+            scheme_operator_names: scheme
+                .and_then(|s| s.child("SchemeOperatorName"))
+                .map(localized_names)
+                .unwrap_or_default(),
+            scheme_territory: scheme_text("SchemeTerritory"),
+            issue_date: scheme_text("ListIssueDateTime").and_then(|v| parse_date_time(&v)),
+            next_update: scheme
+                .and_then(|s| s.find(&["NextUpdate", "dateTime"]))
+                .and_then(|e| parse_date_time(e.text())),
+            providers: match root.child("TrustServiceProviderList") {
+                Some(list) => list
+                    .children_named("TrustServiceProvider")
+                    .map(parse_provider)
+                    .collect::<Result<_, _>>()?,
+                None => Vec::new(),
+            },
+            location: Location::of(root),
+        })
+    }
+
+    /// The first `SchemeOperatorName`, in the preferred language once
+    /// [`TrustedList::prefer_languages`] has been applied
+    pub fn scheme_operator_name(&self) -> Option<&str> {
+        self.scheme_operator_names.first().map(|name| name.value.as_str())
+    }
+
+    /// Orders every multilingual value of the list by the language
+    /// preference `langs`, see [`lang_matches`]. The `name` accessors and
+    /// every report then pick the first preferred language available, or the
+    /// first entry of the document if none is. No variant is dropped.
+    pub fn prefer_languages(&mut self, langs: &[String]) {
+        if langs.is_empty() {
+            return;
+        }
+        sort_by_language(&mut self.scheme_operator_names, |name| &name.lang, langs);
+        for provider in &mut self.providers {
+            sort_by_language(&mut provider.names, |name| &name.lang, langs);
+            sort_by_language(&mut provider.trade_names, |name| &name.lang, langs);
+            sort_by_language(&mut provider.postal_addresses, |address| &address.lang, langs);
+            sort_by_language(&mut provider.electronic_addresses, |uri| &uri.lang, langs);
+            sort_by_language(&mut provider.information_uris, |uri| &uri.lang, langs);
+            for service in &mut provider.services {
+                service.information.prefer_languages(langs);
+                for instance in &mut service.history {
+                    instance.prefer_languages(langs);
+                }
+            }
+        }
+    }
+
+    /// Iterates over every service of every provider
+    pub fn services(&self) -> impl Iterator<Item = (&TrustServiceProvider, &TrustService)> {
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.services.iter().map(move |service| (provider, service)))
+    }
+}
+
+fn parse_provider(element: &Element) -> Result<TrustServiceProvider, CaExtractorError> {
+    let information = element.child("TSPInformation");
+    let address = information.and_then(|i| i.child("TSPAddress"));
+    let names = information
+        .and_then(|i| i.child("TSPName"))
+        .map(localized_names)
+        .unwrap_or_default();
+
+    let services = match element.child("TSPServices") {
+        Some(services) => services
+            .children_named("TSPService")
+            .map(parse_service)
+            .collect::<Result<_, _>>()
+            .map_err(|e| match names.first() {
+                Some(name) => e.with_context(ErrorContext::default().with_tsp(name.value.clone())),
+                None => e,
+            })?,
+        None => Vec::new(),
+    };
+
+    Ok(TrustServiceProvider {
+        names,
+        trade_names: information
+            .and_then(|i| i.child("TSPTradeName"))
+            .map(localized_names)
+            .unwrap_or_default(),
+        postal_addresses: address
+            .and_then(|a| a.child("PostalAddresses"))
+            .map(|addresses| addresses.children_named("PostalAddress").map(parse_postal_address).collect())
+            .unwrap_or_default(),
+        electronic_addresses: address
+            .and_then(|a| a.child("ElectronicAddress"))
+            .map(|uris| localized_texts(uris, "URI"))
+            .unwrap_or_default(),
+        information_uris: information
+            .and_then(|i| i.child("TSPInformationURI"))
+            .map(|uris| localized_texts(uris, "URI"))
+            .unwrap_or_default(),
+        services,
+        location: Location::of(element),
+    })
+}
+
+fn parse_postal_address(element: &Element) -> PostalAddress {
+    let optional = |name: &str| element.child(name).map(|e| e.text().to_string());
+    PostalAddress {
+        lang: element.attr("xml:lang").unwrap_or_default().to_string(),
+        street_address: optional("StreetAddress").unwrap_or_default(),
+        locality: optional("Locality").unwrap_or_default(),
+        state_or_province: optional("StateOrProvince"),
+        postal_code: optional("PostalCode"),
+        country_name: optional("CountryName").unwrap_or_default(),
+    }
+}
+
+fn parse_service(element: &Element) -> Result<TrustService, CaExtractorError> {
+    let information = match element.child("ServiceInformation") {
+        Some(information) => parse_service_information(information)?,
+        None => ServiceInformation {
+            location: Location::of(element),
+            ..ServiceInformation::default()
+        },
+    };
+
+    let history = match element.child("ServiceHistory") {
+        Some(history) => history
+            .children_named("ServiceHistoryInstance")
+            .map(parse_service_information)
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(TrustService { information, history })
+}
+
+fn parse_service_information(element: &Element) -> Result<ServiceInformation, CaExtractorError> {
+    let text = |name: &str| element.child(name).map(|e| e.text().to_string()).unwrap_or_default();
+    let names = element.child("ServiceName").map(localized_names).unwrap_or_default();
+
+    let digital_ids = match element.child("ServiceDigitalIdentity") {
+        Some(identity) => identity
+            .children_named("DigitalId")
+            .filter_map(|id| parse_digital_id(id).transpose())
+            .collect::<Result<_, _>>()
+            .map_err(|e| match names.first() {
+                Some(name) => e.with_context(ErrorContext::default().with_service(name.value.clone())),
+                None => e,
+            })?,
+        None => Vec::new(),
+    };
+
+    let extensions: Vec<&Element> = element
+        .child("ServiceInformationExtensions")
+        .map(|extensions| extensions.children_named("Extension").collect())
+        .unwrap_or_default();
+
+    Ok(ServiceInformation {
+        service_type: text("ServiceTypeIdentifier"),
+        names,
+        digital_ids,
+        status: text("ServiceStatus"),
+        status_starting_time: element
+            .child("StatusStartingTime")
+            .and_then(|e| parse_date_time(e.text())),
+        service_supply_points: element
+            .child("ServiceSupplyPoints")
+            .map(|points| points.children_named("ServiceSupplyPoint").map(|e| e.text().to_string()).collect())
+            .unwrap_or_default(),
+        service_definition_uris: element
+            .child("TSPServiceDefinitionURI")
+            .map(|uris| localized_texts(uris, "URI"))
+            .unwrap_or_default(),
+        additional_information: extensions
+            .iter()
+            .filter_map(|extension| extension.find(&["AdditionalServiceInformation", "URI"]))
+            .map(|uri| uri.text().to_string())
+            .collect(),
+        qualifications: extensions
+            .iter()
+            .filter_map(|extension| extension.child("Qualifications"))
+            .flat_map(|qualifications| qualifications.children_named("QualificationElement"))
+            .map(parse_qualification)
+            .collect(),
+        taken_over_by: extensions
+            .iter()
+            .find_map(|extension| extension.child("TakenOverBy"))
+            .map(parse_taken_over_by),
+        expired_certs_revocation_info: extensions
+            .iter()
+            .find_map(|extension| extension.child("ExpiredCertsRevocationInfo"))
+            .and_then(|e| parse_date_time(e.text())),
+        location: Location::of(element),
+    })
+}
+
+fn parse_qualification(element: &Element) -> Qualification {
+    Qualification {
+        qualifiers: element
+            .child("Qualifiers")
+            .map(|qualifiers| {
+                qualifiers
+                    .children_named("Qualifier")
+                    .filter_map(|qualifier| qualifier.attr("uri"))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        criteria: element.child("CriteriaList").map(parse_criteria_list).unwrap_or_default(),
+        location: Location::of(element),
+    }
+}
+
+fn parse_criteria_list(element: &Element) -> CriteriaList {
+    // OIDs are wrapped in an xades:Identifier below the named element
+    let identifiers = |element: &Element, name: &str| -> Vec<String> {
+        element
+            .children_named(name)
+            .filter_map(|child| child.child("Identifier"))
+            .map(|identifier| identifier.text().trim_start_matches("urn:oid:").to_string())
+            .collect()
+    };
+    let other = element.child("otherCriteriaList");
+
+    CriteriaList {
+        assert: match element.attr("assert") {
+            Some("atLeastOne") => CriteriaAssert::AtLeastOne,
+            Some("none") => CriteriaAssert::None,
+            _ => CriteriaAssert::All,
+        },
+        key_usage: element
+            .children_named("KeyUsage")
+            .map(|key_usage| {
+                key_usage
+                    .children_named("KeyUsageBit")
+                    .map(|bit| (bit.attr("name").unwrap_or_default().to_string(), bit.text() == "true"))
+                    .collect()
+            })
+            .collect(),
+        policy_sets: element
+            .children_named("PolicySet")
+            .map(|set| identifiers(set, "PolicyIdentifier"))
+            .collect(),
+        extended_key_usage: other
+            .into_iter()
+            .flat_map(|other| other.children_named("ExtendedKeyUsage"))
+            .map(|eku| identifiers(eku, "KeyPurposeId"))
+            .collect(),
+        subject_dn_attributes: other
+            .into_iter()
+            .flat_map(|other| other.children_named("CertSubjectDNAttribute"))
+            .map(|attributes| identifiers(attributes, "AttributeOID"))
+            .collect(),
+        criteria_lists: element.children_named("CriteriaList").map(parse_criteria_list).collect(),
+    }
+}
+
+fn parse_taken_over_by(element: &Element) -> TakenOverBy {
+    let text = |name: &str| element.child(name).map(|e| e.text().to_string()).unwrap_or_default();
+    TakenOverBy {
+        uri: text("URI"),
+        tsp_names: element.child("TSPName").map(localized_names).unwrap_or_default(),
+        scheme_operator_names: element.child("SchemeOperatorName").map(localized_names).unwrap_or_default(),
+        scheme_territory: text("SchemeTerritory"),
+    }
+}
+
+fn parse_digital_id(element: &Element) -> Result<Option<DigitalId>, CaExtractorError> {
+    let Some(value) = element.children.first() else {
+        return Ok(None);
+    };
+    let decode = |text: &str| {
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        base64::engine::general_purpose::STANDARD.decode(compact).map_err(|e| {
+            CaExtractorError::InvalidCertificateFormat(format!("<{}> is not valid base64: {}", value.name, e))
+                .with_context(ErrorContext::at(value.line, value.column))
+        })
+    };
+
+    Ok(Some(match value.name.as_str() {
+        "X509Certificate" => DigitalId::Certificate(decode(&value.text)?),
+        "X509SKI" => DigitalId::Ski(decode(&value.text)?),
+        "X509SubjectName" => DigitalId::SubjectName(value.text().to_string()),
+        "KeyValue" => DigitalId::KeyValue,
+        _ => DigitalId::Other,
+    }))
+}
+
+fn localized_names(element: &Element) -> Vec<LocalizedText> {
+    localized_texts(element, "Name")
+}
+
+/// The `name` children of `element` with their `xml:lang`
+fn localized_texts(element: &Element, name: &str) -> Vec<LocalizedText> {
+    element
+        .children_named(name)
+        .map(|name| LocalizedText {
+            lang: name.attr("xml:lang").unwrap_or_default().to_string(),
+            value: name.text().to_string(),
+        })
+        .collect()
+}
+
+impl LocalizedText {
+    pub fn to_json(&self) -> Value {
+        json!({ "lang": self.lang, "value": self.value })
+    }
+}
+
+/// JSON array of every language variant of a name
+pub fn names_to_json(names: &[LocalizedText]) -> Value {
+    names.iter().map(LocalizedText::to_json).collect()
+}
+
+fn date_time_to_json(value: Option<DateTime<Utc>>) -> Value {
+    value.map(|value| json!(value.to_rfc3339())).unwrap_or(Value::Null)
+}
+
+impl CriteriaList {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "assert": match self.assert {
+                CriteriaAssert::All => "all",
+                CriteriaAssert::AtLeastOne => "atLeastOne",
+                CriteriaAssert::None => "none",
+            },
+            "key_usage": self
+                .key_usage
+                .iter()
+                .map(|bits| bits.iter().map(|(name, value)| json!({ "name": name, "value": value })).collect())
+                .collect::<Vec<Value>>(),
+            "policy_sets": self.policy_sets,
+            "extended_key_usage": self.extended_key_usage,
+            "subject_dn_attributes": self.subject_dn_attributes,
+            "criteria_lists": self.criteria_lists.iter().map(CriteriaList::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Qualification {
+    pub fn to_json(&self) -> Value {
+        json!({ "qualifiers": self.qualifiers, "criteria": self.criteria.to_json() })
+    }
+}
+
+impl PostalAddress {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "lang": self.lang,
+            "street_address": self.street_address,
+            "locality": self.locality,
+            "state_or_province": self.state_or_province,
+            "postal_code": self.postal_code,
+            "country_name": self.country_name,
+        })
+    }
+}
+
+impl TrustServiceProvider {
+    /// JSON representation of the `TSPInformation`, without the services
+    pub fn to_json(&self) -> Value {
+        json!({
+            "names": names_to_json(&self.names),
+            "trade_names": names_to_json(&self.trade_names),
+            "registration_identifiers": self.registration_identifiers().collect::<Vec<_>>(),
+            "postal_addresses": self.postal_addresses.iter().map(PostalAddress::to_json).collect::<Vec<_>>(),
+            "electronic_addresses": names_to_json(&self.electronic_addresses),
+            "information_uris": names_to_json(&self.information_uris),
+        })
+    }
+}
+
+impl TakenOverBy {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "uri": self.uri,
+            "tsp_names": names_to_json(&self.tsp_names),
+            "scheme_operator_names": names_to_json(&self.scheme_operator_names),
+            "scheme_territory": self.scheme_territory,
+        })
+    }
+}
+
+impl ServiceInformation {
+    /// JSON representation; digital identities are described by
+    /// [`DigitalId::to_json`], without the certificates themselves
+    pub fn to_json(&self) -> Value {
+        json!({
+            "service_type": self.service_type,
+            "names": names_to_json(&self.names),
+            "digital_ids": self.digital_ids.iter().map(DigitalId::to_json).collect::<Vec<_>>(),
+            "status": self.status,
+            "status_starting_time": date_time_to_json(self.status_starting_time),
+            "qualification": self.qualification_level().to_string(),
+            "service_supply_points": self.service_supply_points,
+            "service_definition_uris": names_to_json(&self.service_definition_uris),
+            "additional_information": self.additional_information,
+            "qualifications": self.qualifications.iter().map(Qualification::to_json).collect::<Vec<_>>(),
+            "taken_over_by": self.taken_over_by.as_ref().map(TakenOverBy::to_json),
+            "expired_certs_revocation_info": date_time_to_json(self.expired_certs_revocation_info),
+        })
+    }
+}
+
+/// Parses an `xsd:dateTime`; values without a time zone are taken as UTC
+pub fn parse_date_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|value| value.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|value| value.and_utc())
+        })
+}
+
+/// Wraps DER bytes into a PEM certificate with 64 character lines
+pub fn der_to_pem(der: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+        encoded
+            .as_bytes()
+            .chunks(64)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,454 @@
+//! OCSP (RFC 6960) status checking of certificates.
+//!
+//! Responders are taken from the certificate's authority information access
+//! extension and from `ServiceSupplyPoints`. A response must be signed by
+//! the certificate's issuer or by a delegated responder the issuer certified
+//! for `OCSPSigning`, and must be current. Responses can be cached on disk
+//! until their `nextUpdate`.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use x509_parser::der_parser::asn1_rs::{Class, Tag};
+
+use crate::chain::{self, ValidPath};
+use crate::cli::OcspNonce;
+use crate::cms::{self, Element};
+use crate::error::{CaExtractorError, ErrorContext};
+use crate::limits::ParseLimits;
+use crate::revocation::RevocationStatus;
+use crate::x509::Certificate;
+
+/// `id-pkix-ocsp-basic` response type
+pub const OID_OCSP_BASIC: &str = "1.3.6.1.5.5.7.48.1.1";
+/// `id-pkix-ocsp-nonce` extension
+pub const OID_OCSP_NONCE: &str = "1.3.6.1.5.5.7.48.1.2";
+
+/// DER `AlgorithmIdentifier` of SHA-1, the hash of request `CertID`s
+const SHA1_ALGORITHM: &[u8] = &[0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00];
+/// DER encoding of `id-pkix-ocsp-nonce`
+const NONCE_OID: &[u8] = &[0x06, 0x09, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x02];
+
+/// Sends OCSP requests and validates the responses
+#[derive(Debug, Clone, Default)]
+pub struct OcspClient {
+    nonce: OcspNonce,
+    cache: Option<PathBuf>,
+    limits: ParseLimits,
+}
+
+impl OcspClient {
+    pub fn new() -> Self {
+        OcspClient::default()
+    }
+
+    /// Replaces the default nonce handling, `send`
+    pub fn with_nonce(mut self, nonce: OcspNonce) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Caches responses in `directory` until their `nextUpdate`. Cached
+    /// responses are not used when a nonce is required, as their nonce
+    /// answers an earlier request.
+    pub fn with_cache(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.cache = Some(directory.into());
+        self
+    }
+
+    /// Replaces the default response size limit
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Status of `certificate`, issued by `issuer`, from the responders in
+    /// its authority information access extension and at `supply_points`.
+    /// The first responder giving a definite answer decides.
+    pub fn check(
+        &self,
+        certificate: &Certificate,
+        issuer: &Certificate,
+        supply_points: &[String],
+        time: DateTime<Utc>,
+    ) -> RevocationStatus {
+        let mut urls: Vec<&str> = Vec::new();
+        for url in certificate.ocsp_responders.iter().chain(supply_points) {
+            if (url.starts_with("http://") || url.starts_with("https://")) && !urls.contains(&url.as_str()) {
+                urls.push(url);
+            }
+        }
+
+        let mut reasons = Vec::new();
+        for url in urls {
+            match self.query(url, certificate, issuer, time) {
+                Ok(RevocationStatus::Unknown(reason)) | Err(reason) => reasons.push(format!("{}: {}", url, reason)),
+                Ok(status) => return status,
+            }
+        }
+
+        if reasons.is_empty() {
+            RevocationStatus::Unknown("no OCSP responder or service supply point".to_string())
+        } else {
+            RevocationStatus::Unknown(reasons.join("; "))
+        }
+    }
+
+    /// Status of every certificate of `path` but the anchor, in chain order.
+    /// The certificate issued by the anchor is also looked up at the anchor
+    /// service's supply points.
+    pub fn check_path(&self, path: &ValidPath, time: DateTime<Utc>) -> Result<Vec<RevocationStatus>, CaExtractorError> {
+        let anchor = Certificate::from_der(path.anchor.der)?;
+        let supply_points = &path.anchor.service.information.service_supply_points;
+        let statuses = path
+            .chain
+            .iter()
+            .enumerate()
+            .map(|(index, certificate)| match path.chain.get(index + 1) {
+                Some(issuer) => self.check(certificate, issuer, &[], time),
+                None => self.check(certificate, &anchor, supply_points, time),
+            })
+            .collect();
+        Ok(statuses)
+    }
+
+    fn query(
+        &self,
+        url: &str,
+        certificate: &Certificate,
+        issuer: &Certificate,
+        time: DateTime<Utc>,
+    ) -> Result<RevocationStatus, String> {
+        let cert_id = cert_id(certificate, issuer);
+        let cached = self.cache.as_ref().map(|directory| {
+            let mut key = url.as_bytes().to_vec();
+            key.extend_from_slice(&cert_id);
+            directory.join(format!("{}.ocsp", hex(digest::digest(&digest::SHA256, &key).as_ref())))
+        });
+        if let Some(path) = cached.as_ref().filter(|_| self.nonce != OcspNonce::Require) {
+            if let Ok(response) = fs::read(path) {
+                if let Ok(validated) = validate_response(&response, certificate, issuer, false, time) {
+                    if validated.next_update.is_some_and(|next| next > time) {
+                        return Ok(validated.status);
+                    }
+                }
+            }
+        }
+
+        let nonce = match self.nonce {
+            OcspNonce::Off => None,
+            OcspNonce::Send | OcspNonce::Require => {
+                let mut nonce = [0u8; 16];
+                SystemRandom::new()
+                    .fill(&mut nonce)
+                    .map_err(|_| "no randomness for the nonce".to_string())?;
+                Some(nonce.to_vec())
+            }
+        };
+        let request = encode_request(&cert_id, nonce.as_deref());
+        let response = post(url, request, &self.limits)
+            .map_err(|e| e.with_context(ErrorContext::source(url.to_string())).to_string())?;
+
+        let validated = validate_response(&response, certificate, issuer, nonce.is_some(), time)?;
+        match (&validated.nonce, self.nonce) {
+            (None, OcspNonce::Require) => return Err("the response does not echo the request nonce".to_string()),
+            (Some(echoed), _) if Some(echoed) != nonce.as_ref() => {
+                return Err("the response nonce does not match the request".to_string())
+            }
+            _ => {}
+        }
+
+        if let (Some(path), Some(_)) = (&cached, validated.next_update) {
+            if let Some(directory) = path.parent() {
+                let _ = fs::create_dir_all(directory).and_then(|_| fs::write(path, &response));
+            }
+        }
+        Ok(validated.status)
+    }
+}
+
+/// A response that passed validation
+struct ValidatedResponse {
+    status: RevocationStatus,
+    next_update: Option<DateTime<Utc>>,
+    nonce: Option<Vec<u8>>,
+}
+
+/// DER `CertID` of `certificate` with SHA-1 hashes of the issuer's name and key
+fn cert_id(certificate: &Certificate, issuer: &Certificate) -> Vec<u8> {
+    let sha1 = |data: &[u8]| digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data).as_ref().to_vec();
+    let mut content = SHA1_ALGORITHM.to_vec();
+    content.extend(cms::encode(0x04, &sha1(&issuer.subject_raw)));
+    content.extend(cms::encode(0x04, &sha1(&issuer.public_key)));
+    content.extend(cms::encode(0x02, &certificate.serial));
+    cms::encode(0x30, &content)
+}
+
+/// DER `OCSPRequest` for one certificate, with the nonce extension if given
+fn encode_request(cert_id: &[u8], nonce: Option<&[u8]>) -> Vec<u8> {
+    let request_list = cms::encode(0x30, &cms::encode(0x30, cert_id));
+    let mut tbs_request = request_list;
+    if let Some(nonce) = nonce {
+        let mut extension = NONCE_OID.to_vec();
+        extension.extend(cms::encode(0x04, &cms::encode(0x04, nonce)));
+        let extensions = cms::encode(0x30, &cms::encode(0x30, &extension));
+        tbs_request.extend(cms::encode(0xa2, &extensions));
+    }
+    cms::encode(0x30, &cms::encode(0x30, &tbs_request))
+}
+
+/// Validates a response to a request for `certificate`. Without
+/// `expect_nonce` an echoed nonce is ignored.
+fn validate_response(
+    response: &[u8],
+    certificate: &Certificate,
+    issuer: &Certificate,
+    expect_nonce: bool,
+    time: DateTime<Utc>,
+) -> Result<ValidatedResponse, String> {
+    parse_and_validate(response, certificate, issuer, expect_nonce, time).map_err(|e| match e {
+        CaExtractorError::SignatureInvalid(message) => message,
+        e => e.to_string(),
+    })
+}
+
+fn parse_and_validate(
+    response: &[u8],
+    certificate: &Certificate,
+    issuer: &Certificate,
+    expect_nonce: bool,
+    time: DateTime<Utc>,
+) -> Result<ValidatedResponse, CaExtractorError> {
+    let malformed = || invalid("malformed OCSP response".to_string());
+
+    // OCSPResponse ::= SEQUENCE { responseStatus, responseBytes [0] EXPLICIT OPTIONAL }
+    let fields = Element::single(response)?.children()?;
+    let status = fields.first().filter(|status| status.any.tag() == Tag::Enumerated).ok_or_else(malformed)?;
+    if status.any.data != [0] {
+        let code = status.any.data.first().copied().unwrap_or_default();
+        return Err(invalid(format!("the responder answered with status {}", response_status_name(code))));
+    }
+    let bytes = fields.get(1).ok_or_else(malformed)?.explicit(0)?.children()?;
+    let [response_type, basic] = bytes.as_slice() else {
+        return Err(malformed());
+    };
+    if cms::oid(response_type)? != OID_OCSP_BASIC {
+        return Err(invalid(format!("unsupported response type {}", cms::oid(response_type)?)));
+    }
+
+    // BasicOCSPResponse ::= SEQUENCE { tbsResponseData, signatureAlgorithm, signature, certs [0] OPTIONAL }
+    let basic = cms::octets(basic)?;
+    let fields = Element::single(&basic)?.children()?;
+    let [tbs, signature_algorithm, signature, optional @ ..] = fields.as_slice() else {
+        return Err(malformed());
+    };
+    let mut certificates = Vec::new();
+    if let Some(certs) = optional.first() {
+        for cert in certs.explicit(0)?.children()? {
+            certificates.push(Certificate::from_der(cert.raw)?);
+        }
+    }
+
+    // ResponseData ::= SEQUENCE { version [0] DEFAULT v1, responderID, producedAt, responses, extensions [1] }
+    let mut data = tbs.children()?.into_iter().peekable();
+    if data.peek().is_some_and(|version| is_context(version, 0)) {
+        data.next();
+    }
+    let responder_id = data.next().ok_or_else(malformed)?;
+    let produced_at = data.next().and_then(|time| generalized_time(&time)).ok_or_else(malformed)?;
+    let responses = data.next().ok_or_else(malformed)?.children()?;
+    let extensions = data.next();
+
+    let signer = responder(&responder_id, issuer, &certificates, produced_at)?;
+    let signature_oid = cms::algorithm(signature_algorithm)?;
+    let algorithm = cms::verification_algorithm(&signature_oid, "", &signer)
+        .ok_or_else(|| invalid(format!("unsupported signature algorithm {}", signature_oid)))?;
+    let signature = signature.any.data.get(1..).ok_or_else(malformed)?;
+    if !signer.verifies(algorithm, tbs.raw, signature) {
+        return Err(invalid(format!("the response signature does not verify with the key of '{}'", signer.subject)));
+    }
+
+    let mut nonce = None;
+    if let Some(extensions) = extensions.filter(|extensions| is_context(extensions, 1)) {
+        for extension in extensions.explicit(1)?.children()? {
+            let fields = extension.children()?;
+            if fields.first().map(cms::oid).transpose()?.as_deref() == Some(OID_OCSP_NONCE) {
+                let value = cms::octets(fields.last().ok_or_else(malformed)?)?;
+                // RFC 8954 wraps the nonce in an OCTET STRING; older responders echo it bare
+                nonce = Some(Element::single(&value).and_then(|inner| cms::octets(&inner)).unwrap_or(value));
+            }
+        }
+    }
+    if !expect_nonce {
+        nonce = None;
+    }
+
+    // SingleResponse ::= SEQUENCE { certID, certStatus, thisUpdate, nextUpdate [0] OPTIONAL, ... }
+    for single in &responses {
+        let fields = single.children()?;
+        let [id, cert_status, this_update, optional @ ..] = fields.as_slice() else {
+            return Err(malformed());
+        };
+        if !matches_cert_id(id, certificate, issuer)? {
+            continue;
+        }
+
+        let this_update = generalized_time(this_update).ok_or_else(malformed)?;
+        let next_update = match optional.iter().find(|field| is_context(field, 0)) {
+            Some(next) => Some(generalized_time(&next.explicit(0)?).ok_or_else(malformed)?),
+            None => None,
+        };
+        if this_update > time {
+            return Err(invalid(format!("the response is only valid from {}", this_update.to_rfc3339())));
+        }
+        if let Some(next_update) = next_update.filter(|next| *next < time) {
+            return Err(invalid(format!("the response expired on {}", next_update.to_rfc3339())));
+        }
+
+        let status = match (cert_status.any.class(), cert_status.any.tag().0) {
+            (Class::ContextSpecific, 0) => RevocationStatus::Good,
+            (Class::ContextSpecific, 1) => {
+                // RevokedInfo ::= SEQUENCE { revocationTime, revocationReason [0] EXPLICIT CRLReason OPTIONAL }
+                let info = cert_status.children()?;
+                let date = info.first().and_then(generalized_time).ok_or_else(malformed)?;
+                let reason = match info.get(1) {
+                    Some(reason) => reason.explicit(0)?.any.data.first().map(|code| crl_reason_name(*code)),
+                    None => None,
+                };
+                RevocationStatus::Revoked { date, reason }
+            }
+            (Class::ContextSpecific, 2) => {
+                RevocationStatus::Unknown("the responder does not know the certificate".to_string())
+            }
+            _ => return Err(malformed()),
+        };
+        return Ok(ValidatedResponse {
+            status,
+            next_update,
+            nonce,
+        });
+    }
+    Err(invalid("the response has no status for the certificate".to_string()))
+}
+
+/// The certificate that signed the response: the issuer itself or a
+/// delegated responder it certified for `OCSPSigning`
+fn responder(
+    responder_id: &Element,
+    issuer: &Certificate,
+    certificates: &[Certificate],
+    produced_at: DateTime<Utc>,
+) -> Result<Certificate, CaExtractorError> {
+    // ResponderID ::= CHOICE { byName [1] Name, byKey [2] KeyHash }
+    let identifies = |candidate: &Certificate| -> Result<bool, CaExtractorError> {
+        if is_context(responder_id, 1) {
+            Ok(responder_id.explicit(1)?.raw == candidate.subject_raw.as_slice())
+        } else if is_context(responder_id, 2) {
+            let key_hash = cms::octets(&responder_id.explicit(2)?)?;
+            Ok(key_hash == digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &candidate.public_key).as_ref())
+        } else {
+            Err(invalid("malformed ResponderID".to_string()))
+        }
+    };
+
+    if identifies(issuer)? {
+        return Ok(issuer.clone());
+    }
+    let mut delegated = None;
+    for candidate in certificates {
+        if identifies(candidate)? {
+            delegated = Some(candidate);
+            break;
+        }
+    }
+    let delegated = delegated.ok_or_else(|| {
+        invalid("the response is signed neither by the issuer nor by a responder certificate it carries".to_string())
+    })?;
+
+    let ocsp_signing = chain::key_purpose_oid("OCSPSigning").expect("OCSPSigning is a known key purpose");
+    if !delegated.is_signed_by(issuer) {
+        return Err(invalid(format!("the responder '{}' is not certified by '{}'", delegated.subject, issuer.subject)));
+    }
+    if !delegated.extended_key_usage.as_ref().is_some_and(|usages| usages.contains(&ocsp_signing)) {
+        return Err(invalid(format!(
+            "the extended key usage of the responder '{}' does not allow OCSPSigning",
+            delegated.subject
+        )));
+    }
+    if !delegated.is_valid_at(produced_at) {
+        return Err(invalid(format!(
+            "the responder '{}' is not valid at {}",
+            delegated.subject,
+            produced_at.to_rfc3339()
+        )));
+    }
+    Ok(delegated.clone())
+}
+
+/// Whether a response `CertID`, hashed with any supported algorithm, names `certificate`
+fn matches_cert_id(id: &Element, certificate: &Certificate, issuer: &Certificate) -> Result<bool, CaExtractorError> {
+    let fields = id.children()?;
+    let [algorithm, name_hash, key_hash, serial] = fields.as_slice() else {
+        return Err(invalid("malformed CertID".to_string()));
+    };
+    let Some(algorithm) = cms::digest_algorithm(&cms::algorithm(algorithm)?) else {
+        return Ok(false);
+    };
+    Ok(serial.any.data == certificate.serial.as_slice()
+        && cms::octets(name_hash)? == digest::digest(algorithm, &issuer.subject_raw).as_ref()
+        && cms::octets(key_hash)? == digest::digest(algorithm, &issuer.public_key).as_ref())
+}
+
+fn is_context(element: &Element, tag: u32) -> bool {
+    element.any.class() == Class::ContextSpecific && element.any.tag() == Tag(tag)
+}
+
+fn generalized_time(element: &Element) -> Option<DateTime<Utc>> {
+    let time = element.any.as_generalizedtime().ok()?.utc_datetime().ok()?;
+    DateTime::from_timestamp(time.unix_timestamp(), time.nanosecond())
+}
+
+fn response_status_name(code: u8) -> String {
+    match code {
+        1 => "malformedRequest".to_string(),
+        2 => "internalError".to_string(),
+        3 => "tryLater".to_string(),
+        5 => "sigRequired".to_string(),
+        6 => "unauthorized".to_string(),
+        code => code.to_string(),
+    }
+}
+
+/// Name of a `CRLReason`, as x509-parser names CRL entry reasons
+fn crl_reason_name(code: u8) -> String {
+    x509_parser::x509::ReasonCode(code).to_string()
+}
+
+fn post(url: &str, request: Vec<u8>, limits: &ParseLimits) -> Result<Vec<u8>, CaExtractorError> {
+    let client = reqwest::blocking::Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/ocsp-request")
+        .body(request)
+        .send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CaExtractorError::ApiError {
+            status: status.as_u16(),
+            message: format!("OCSP responder returned status {}", status),
+        });
+    }
+    limits.read_limited_bytes(response)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
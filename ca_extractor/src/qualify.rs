@@ -0,0 +1,367 @@
+//! Qualification of an end-entity certificate against a trusted list,
+//! following the procedure of ETSI TS 119 615 (clause 4.4).
+//!
+//! The certificate is chained to the CA/QC service that issued it by a path
+//! valid when it was issued (see [`crate::chain`]), the service's status at
+//! the time of issuance is looked up, the qualifiers of the
+//! `sie:Qualifications` whose criteria match the certificate are applied and
+//! the remaining gaps are filled from the certificate's QcStatements.
+//! Every decision is recorded as a step of the result.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::chain::{self, PathOptions};
+use crate::etsi;
+use crate::extractor::ExtractedCertificate;
+use crate::model::{
+    CriteriaAssert, CriteriaList, DigitalId, ServiceInformation, TrustService, TrustServiceProvider, TrustedList,
+};
+use crate::x509::{Certificate, QcType};
+
+/// Certificate policies of pre-eIDAS qualified certificates (ETSI TS 101 456
+/// QCP-public+SSCD and QCP-public), accepted as a QcCompliance equivalent
+const LEGACY_QC_POLICIES: &[&str] = &["0.4.0.1456.1.1", "0.4.0.1456.1.2"];
+
+/// Outcome of the qualification procedure
+#[derive(Debug, Clone)]
+pub struct QualificationResult<'a> {
+    pub qualified: bool,
+    pub qc_type: Option<QcType>,
+    /// Whether the private key resides in a QSCD, `None` when undetermined
+    /// or not applicable
+    pub qscd: Option<bool>,
+    pub legal_person: bool,
+    /// The CA/QC service the certificate chains to, and its provider
+    pub provider: Option<&'a TrustServiceProvider>,
+    pub service: Option<&'a TrustService>,
+    /// Certificates from the end-entity certificate up to the one issued
+    /// by the service
+    pub chain: Vec<Certificate>,
+    /// Human readable trail of every decision taken
+    pub steps: Vec<String>,
+}
+
+impl QualificationResult<'_> {
+    fn not_qualified(chain: Vec<Certificate>, steps: Vec<String>) -> Self {
+        QualificationResult {
+            qualified: false,
+            qc_type: None,
+            qscd: None,
+            legal_person: false,
+            provider: None,
+            service: None,
+            chain,
+            steps,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "qualified": self.qualified,
+            "summary": self.to_string(),
+            "qc_type": self.qc_type.map(|qc_type| qc_type.to_string()),
+            "qscd": self.qscd,
+            "legal_person": self.legal_person,
+            "tsp": self.provider.and_then(TrustServiceProvider::name),
+            "service": self.service.and_then(|service| service.information.name()),
+            "chain": self.chain.iter().map(|cert| cert.subject.clone()).collect::<Vec<_>>(),
+            "steps": self.steps,
+        })
+    }
+}
+
+impl fmt::Display for QualificationResult<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.qualified {
+            return write!(f, "not qualified");
+        }
+        match self.qc_type {
+            Some(QcType::ESign) => write!(f, "qualified certificate for electronic signatures (QESig)")?,
+            Some(QcType::ESeal) => write!(f, "qualified certificate for electronic seals (QSealC)")?,
+            Some(QcType::Web) => write!(f, "qualified certificate for website authentication (QWAC)")?,
+            None => write!(f, "qualified certificate of undetermined type")?,
+        }
+        match self.qscd {
+            Some(true) => write!(f, ", private key in a QSCD")?,
+            Some(false) => write!(f, ", private key not in a QSCD")?,
+            None => {}
+        }
+        if self.legal_person {
+            write!(f, ", issued to a legal person")?;
+        }
+        Ok(())
+    }
+}
+
+/// Determines the qualification of `certificate`, using `intermediates` to
+/// build the chain up to a CA/QC service of `list`
+pub fn qualify<'a>(
+    list: &'a TrustedList,
+    certificate: &Certificate,
+    intermediates: &[Certificate],
+) -> QualificationResult<'a> {
+    let mut steps = Vec::new();
+
+    let Some((provider, service, chain)) = find_issuing_service(list, certificate, intermediates, &mut steps) else {
+        steps.push("no CA/QC service of the trusted list issued the certificate or its chain".to_string());
+        return QualificationResult::not_qualified(vec![certificate.clone()], steps);
+    };
+
+    // Status at the time of issuance
+    let issued = certificate.not_before;
+    let Some(information) = service.information_at(issued) else {
+        steps.push(format!(
+            "the service was not listed yet when the certificate was issued ({})",
+            issued.to_rfc3339()
+        ));
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    };
+    steps.push(format!(
+        "service status at issuance ({}) is '{}'",
+        issued.to_rfc3339(),
+        etsi::short_name(&information.status)
+    ));
+    if !etsi::GRANTED_STATUSES.contains(&information.status.as_str()) {
+        steps.push("the service was not granted at issuance".to_string());
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    }
+
+    // Qualifiers of every qualification whose criteria the certificate meets
+    let qualifiers = applicable_qualifiers(information, certificate, &mut steps);
+    let has = |uri: &str| qualifiers.contains(&uri);
+
+    // Qualified status
+    if has(etsi::QUALIFIER_NOT_QUALIFIED) {
+        steps.push("the NotQualified qualifier overrides the certificate content".to_string());
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    }
+    let statements = &certificate.qc_statements;
+    if has(etsi::QUALIFIER_QC_STATEMENT) {
+        steps.push("the QCStatement qualifier declares the certificate qualified".to_string());
+    } else if statements.compliance {
+        steps.push("the certificate asserts QcCompliance".to_string());
+    } else if let Some(policy) = certificate.policies.iter().find(|oid| LEGACY_QC_POLICIES.contains(&oid.as_str())) {
+        steps.push(format!("the certificate carries the qualified certificate policy {}", policy));
+    } else {
+        steps.push("neither a qualifier nor the certificate's QcStatements declare it qualified".to_string());
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    }
+
+    // Type: the service's qualifiers take precedence over the certificate
+    let qualifier_types: Vec<QcType> = [
+        (etsi::QUALIFIER_QC_FOR_ESIG, QcType::ESign),
+        (etsi::QUALIFIER_QC_FOR_ESEAL, QcType::ESeal),
+        (etsi::QUALIFIER_QC_FOR_WSA, QcType::Web),
+    ]
+    .into_iter()
+    .filter(|(uri, _)| has(uri))
+    .map(|(_, qc_type)| qc_type)
+    .collect();
+    let qc_type = match (qualifier_types.as_slice(), statements.types.as_slice()) {
+        ([qc_type], _) => {
+            steps.push(format!("type '{}' set by a qualifier", qc_type));
+            Some(*qc_type)
+        }
+        ([], []) => {
+            steps.push("no QcType in the certificate, which implies 'esign'".to_string());
+            Some(QcType::ESign)
+        }
+        ([], [qc_type]) => {
+            steps.push(format!("type '{}' taken from the certificate's QcType", qc_type));
+            Some(*qc_type)
+        }
+        _ => {
+            steps.push("conflicting types, the type cannot be determined".to_string());
+            None
+        }
+    };
+
+    let Some(qc_type) = qc_type else {
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    };
+    let purpose = match qc_type {
+        QcType::ESign => etsi::ASI_FOR_ESIGNATURES,
+        QcType::ESeal => etsi::ASI_FOR_ESEALS,
+        QcType::Web => etsi::ASI_FOR_WEB_SITE_AUTHENTICATION,
+    };
+    let purposes: Vec<&str> = information
+        .additional_information
+        .iter()
+        .map(String::as_str)
+        .filter(|uri| etsi::CA_QC_PURPOSES.contains(uri))
+        .collect();
+    if !purposes.is_empty() && !purposes.contains(&purpose) {
+        steps.push(format!(
+            "the service is not granted for '{}' certificates (additional service information: {})",
+            qc_type,
+            purposes.iter().map(|uri| etsi::short_name(uri)).collect::<Vec<_>>().join(", ")
+        ));
+        return anchored(QualificationResult::not_qualified(chain, steps), provider, service);
+    }
+
+    // QSCD, meaningful for signatures and seals only
+    let qscd = if qc_type == QcType::Web {
+        None
+    } else if has(etsi::QUALIFIER_QC_WITH_QSCD) || has(etsi::QUALIFIER_QC_WITH_SSCD) {
+        steps.push("a qualifier declares the private key to be in a QSCD".to_string());
+        Some(true)
+    } else if has(etsi::QUALIFIER_QC_QSCD_MANAGED_ON_BEHALF) {
+        steps.push("a qualifier declares the QSCD to be managed on behalf of the subject".to_string());
+        Some(true)
+    } else if has(etsi::QUALIFIER_QC_NO_QSCD) || has(etsi::QUALIFIER_QC_NO_SSCD) {
+        steps.push("a qualifier declares the private key not to be in a QSCD".to_string());
+        Some(false)
+    } else {
+        steps.push(format!(
+            "QSCD status taken from the certificate's QcSSCD statement ({})",
+            if statements.sscd { "present" } else { "absent" }
+        ));
+        Some(statements.sscd)
+    };
+
+    let legal_person = has(etsi::QUALIFIER_QC_FOR_LEGAL_PERSON) || qc_type == QcType::ESeal;
+    if has(etsi::QUALIFIER_QC_FOR_LEGAL_PERSON) {
+        steps.push("the QCForLegalPerson qualifier declares a legal person subject".to_string());
+    }
+
+    let result = QualificationResult {
+        qualified: true,
+        qc_type: Some(qc_type),
+        qscd,
+        legal_person,
+        provider: None,
+        service: None,
+        chain,
+        steps,
+    };
+    anchored(result, provider, service)
+}
+
+fn anchored<'a>(
+    mut result: QualificationResult<'a>,
+    provider: &'a TrustServiceProvider,
+    service: &'a TrustService,
+) -> QualificationResult<'a> {
+    result.provider = Some(provider);
+    result.service = Some(service);
+    result
+}
+
+/// Chains `certificate` through `intermediates` to the digital identity of
+/// a CA/QC service: one of its certificates or, for services listed by
+/// `X509SKI` (and `X509SubjectName`) only, an intermediate carrying that key
+fn find_issuing_service<'a>(
+    list: &'a TrustedList,
+    certificate: &Certificate,
+    intermediates: &[Certificate],
+    steps: &mut Vec<String>,
+) -> Option<(&'a TrustServiceProvider, &'a TrustService, Vec<Certificate>)> {
+    let (services, anchors): (Vec<_>, Vec<_>) = list
+        .services()
+        .filter(|(_, service)| service.information.service_type == etsi::SVCTYPE_CA_QC)
+        .flat_map(|(provider, service)| {
+            let listed = service.information.digital_ids.iter().filter_map(move |id| match id {
+                DigitalId::Certificate(der) => {
+                    Certificate::from_der(der).ok().map(|cert| ((provider, service), cert))
+                }
+                _ => None,
+            });
+            let identified = intermediates
+                .iter()
+                .filter(move |intermediate| service.information.identifies(intermediate))
+                .map(move |intermediate| ((provider, service), intermediate.clone()));
+            listed.chain(identified)
+        })
+        .unzip();
+
+    let listed: Vec<ExtractedCertificate> = services
+        .iter()
+        .zip(&anchors)
+        .map(|(&(provider, service), anchor)| ExtractedCertificate {
+            provider,
+            service,
+            service_types: Vec::new(),
+            der: &anchor.der,
+            revocation: None,
+        })
+        .collect();
+    // The chain must be valid when the certificate was issued; the status of
+    // the service at that time is checked by the caller
+    let options = PathOptions {
+        time: certificate.not_before,
+        purpose: None,
+        require_service_in_force: false,
+    };
+    let path = match chain::validate(certificate, intermediates, &listed, &options) {
+        Ok(path) => path,
+        Err(failures) => {
+            steps.extend(failures.iter().map(ToString::to_string));
+            return None;
+        }
+    };
+    let index = listed.iter().position(|anchor| std::ptr::eq(anchor.der, path.anchor.der))?;
+    let chain = path.chain;
+    for pair in chain.windows(2) {
+        steps.push(format!("'{}' is issued by the intermediate '{}'", pair[0].subject, pair[1].subject));
+    }
+    let (provider, service) = services[index];
+    steps.push(format!(
+        "'{}' is issued by the CA/QC service '{}' of '{}'",
+        chain.last().expect("the chain is never empty").subject,
+        name(&service.information),
+        provider.name().unwrap_or("<unnamed>")
+    ));
+    Some((provider, service, chain))
+}
+
+/// Qualifiers of the qualifications whose criteria `certificate` meets
+fn applicable_qualifiers<'a>(
+    information: &'a ServiceInformation,
+    certificate: &Certificate,
+    steps: &mut Vec<String>,
+) -> Vec<&'a str> {
+    let mut qualifiers = Vec::new();
+    for qualification in &information.qualifications {
+        let names: Vec<&str> = qualification.qualifiers.iter().map(|uri| etsi::short_name(uri)).collect();
+        if criteria_match(&qualification.criteria, certificate) {
+            steps.push(format!("the certificate meets the criteria of qualifiers {}", names.join(", ")));
+            qualifiers.extend(qualification.qualifiers.iter().map(String::as_str));
+        } else {
+            steps.push(format!("the certificate does not meet the criteria of qualifiers {}", names.join(", ")));
+        }
+    }
+    qualifiers
+}
+
+/// Evaluates a `CriteriaList` against a certificate
+pub fn criteria_match(criteria: &CriteriaList, certificate: &Certificate) -> bool {
+    let all_present = |required: &[String], present: &[String]| required.iter().all(|oid| present.contains(oid));
+    let extended_key_usage = certificate.extended_key_usage.as_deref().unwrap_or_default();
+
+    let results: Vec<bool> = criteria
+        .key_usage
+        .iter()
+        .map(|bits| bits.iter().all(|(bit, value)| certificate.has_key_usage(bit) == *value))
+        .chain(criteria.policy_sets.iter().map(|set| all_present(set, &certificate.policies)))
+        .chain(criteria.extended_key_usage.iter().map(|set| all_present(set, extended_key_usage)))
+        .chain(
+            criteria
+                .subject_dn_attributes
+                .iter()
+                .map(|set| all_present(set, &certificate.subject_attributes)),
+        )
+        .chain(criteria.criteria_lists.iter().map(|nested| criteria_match(nested, certificate)))
+        .collect();
+
+    match criteria.assert {
+        CriteriaAssert::All => results.iter().all(|matched| *matched),
+        CriteriaAssert::AtLeastOne => results.iter().any(|matched| *matched),
+        CriteriaAssert::None => !results.iter().any(|matched| *matched),
+    }
+}
+
+fn name(information: &ServiceInformation) -> &str {
+    information.name().unwrap_or("<unnamed>")
+}
@@ -0,0 +1,318 @@
+//! Revocation checking of extracted CA certificates against CRLs.
+//!
+//! CRLs are fetched from the certificate's CRL distribution points and from
+//! the `ServiceSupplyPoints` of its own service and of the services listing
+//! its issuer. A CRL counts only when its issuer issued the certificate, its
+//! signature verifies with that issuer's key and it is current. Downloaded
+//! CRLs are cached on disk until their `nextUpdate`, in a directory only its
+//! owner can write to. The pass over extracted certificates can ask OCSP
+//! responders first, see `ocsp`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ring::digest;
+use serde_json::{json, Value};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+use x509_parser::revocation_list::CertificateRevocationList;
+
+use crate::error::{CaExtractorError, ErrorContext};
+use crate::extractor::ExtractedCertificate;
+use crate::limits::ParseLimits;
+use crate::model::{DigitalId, TrustedList};
+use crate::ocsp::OcspClient;
+use crate::x509::{self, Certificate};
+
+/// Revocation status of a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevocationStatus {
+    /// A current CRL of the issuer does not list the certificate
+    Good,
+    Revoked {
+        date: DateTime<Utc>,
+        /// CRL reason code, e.g. `KeyCompromise`
+        reason: Option<String>,
+    },
+    /// No current CRL of the issuer could be obtained and verified
+    Unknown(String),
+}
+
+impl RevocationStatus {
+    pub fn is_revoked(&self) -> bool {
+        matches!(self, RevocationStatus::Revoked { .. })
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            RevocationStatus::Good => json!({ "status": "good" }),
+            RevocationStatus::Revoked { date, reason } => json!({
+                "status": "revoked",
+                "revocation_date": date.to_rfc3339(),
+                "reason": reason,
+            }),
+            RevocationStatus::Unknown(reason) => json!({ "status": "unknown", "reason": reason }),
+        }
+    }
+}
+
+impl fmt::Display for RevocationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevocationStatus::Good => write!(f, "good"),
+            RevocationStatus::Revoked { date, reason: Some(reason) } => {
+                write!(f, "revoked on {} ({})", date.to_rfc3339(), reason)
+            }
+            RevocationStatus::Revoked { date, reason: None } => write!(f, "revoked on {}", date.to_rfc3339()),
+            RevocationStatus::Unknown(reason) => write!(f, "unknown ({})", reason),
+        }
+    }
+}
+
+/// On-disk cache of downloaded CRLs, one DER file per URL
+#[derive(Debug, Clone)]
+pub struct CrlCache {
+    directory: PathBuf,
+    limits: ParseLimits,
+}
+
+impl CrlCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        CrlCache {
+            directory: directory.into(),
+            limits: ParseLimits::default(),
+        }
+    }
+
+    /// Replaces the default download size limit
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The DER CRL published at `url`: the cached copy while its
+    /// `nextUpdate` is after `time`, else a fresh download, which replaces
+    /// the cached copy
+    pub fn fetch(&self, url: &str, time: DateTime<Utc>) -> Result<Vec<u8>, CaExtractorError> {
+        let name = hex(digest::digest(&digest::SHA256, url.as_bytes()).as_ref());
+        let path = self.directory.join(format!("{}.crl", name));
+        create_private_directory(&self.directory).map_err(|e| {
+            CaExtractorError::from(e).with_context(ErrorContext::source(self.directory.display().to_string()))
+        })?;
+        if let Ok(cached) = fs::read(&path) {
+            let current = CertificateRevocationList::from_der(&cached)
+                .is_ok_and(|(_, crl)| crl.next_update().is_some_and(|next| x509::to_date_time(next) > time));
+            if current {
+                return Ok(cached);
+            }
+        }
+
+        let crl = download(url, &self.limits).map_err(|e| e.with_context(ErrorContext::source(url.to_string())))?;
+        fs::write(&path, &crl)?;
+        Ok(crl)
+    }
+}
+
+/// Creates `directory` accessible to its owner only, if missing. An existing
+/// directory owned by another user or writable by others is refused, as its
+/// CRLs could have been planted.
+#[cfg(unix)]
+fn create_private_directory(directory: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(directory)?;
+    let metadata = fs::metadata(directory)?;
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the CRL cache is owned by another user"));
+    }
+    if metadata.mode() & 0o022 != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the CRL cache is writable by other users"));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_directory(directory: &Path) -> io::Result<()> {
+    fs::create_dir_all(directory)
+}
+
+/// Checks `certificate` against the CRLs at its distribution points and at
+/// `supply_points`. A CRL is accepted when it is signed by one of `issuers`
+/// that issued the certificate and is current at `time`.
+pub fn check(
+    certificate: &Certificate,
+    issuers: &[Certificate],
+    supply_points: &[String],
+    cache: &CrlCache,
+    time: DateTime<Utc>,
+) -> RevocationStatus {
+    let mut urls: Vec<&str> = Vec::new();
+    for url in certificate.crl_distribution_points.iter().chain(supply_points) {
+        if (url.starts_with("http://") || url.starts_with("https://")) && !urls.contains(&url.as_str()) {
+            urls.push(url);
+        }
+    }
+
+    let mut good = false;
+    let mut reasons = Vec::new();
+    for url in urls {
+        match check_crl(certificate, issuers, url, cache, time) {
+            Ok(None) => good = true,
+            Ok(Some(revoked)) => return revoked,
+            Err(reason) => reasons.push(format!("{}: {}", url, reason)),
+        }
+    }
+
+    if good {
+        RevocationStatus::Good
+    } else if reasons.is_empty() {
+        RevocationStatus::Unknown("no CRL distribution point or service supply point".to_string())
+    } else {
+        RevocationStatus::Unknown(reasons.join("; "))
+    }
+}
+
+/// Looks `certificate` up on the CRL at `url`. Returns the revocation, if
+/// listed, or why the CRL cannot be relied on.
+fn check_crl(
+    certificate: &Certificate,
+    issuers: &[Certificate],
+    url: &str,
+    cache: &CrlCache,
+    time: DateTime<Utc>,
+) -> Result<Option<RevocationStatus>, String> {
+    let der = cache.fetch(url, time).map_err(|e| e.to_string())?;
+    let (_, crl) = CertificateRevocationList::from_der(&der).map_err(|e| format!("not a DER CRL: {}", e))?;
+    if crl.issuer().as_raw() != certificate.issuer_raw.as_slice() {
+        return Err(format!("the CRL is issued by '{}', not by '{}'", crl.issuer(), certificate.issuer));
+    }
+
+    let verified = issuers
+        .iter()
+        .filter(|issuer| {
+            certificate.is_signed_by(issuer) && (issuer.key_usage.is_none() || issuer.has_key_usage("crlSign"))
+        })
+        .filter_map(|issuer| X509Certificate::from_der(&issuer.der).ok())
+        .any(|(_, issuer)| crl.verify_signature(issuer.public_key()).is_ok());
+    if !verified {
+        return Err("the CRL signature does not verify with the key of a listed issuer".to_string());
+    }
+
+    let this_update = x509::to_date_time(crl.last_update());
+    if this_update > time {
+        return Err(format!("the CRL is only valid from {}", this_update.to_rfc3339()));
+    }
+    if let Some(next_update) = crl.next_update().map(x509::to_date_time) {
+        if next_update < time {
+            return Err(format!("the CRL expired on {}", next_update.to_rfc3339()));
+        }
+    }
+
+    let revoked = crl
+        .iter_revoked_certificates()
+        .find(|entry| entry.raw_serial() == certificate.serial.as_slice())
+        .map(|entry| RevocationStatus::Revoked {
+            date: x509::to_date_time(entry.revocation_date),
+            reason: entry.reason_code().map(|(_, reason)| reason.to_string()),
+        });
+    Ok(revoked)
+}
+
+/// Sets the revocation status of every extracted certificate that is not
+/// self-issued, with the certificates of `list` as candidate issuers. With
+/// `ocsp`, responders are asked first and CRLs only consulted when they give
+/// no answer; without `crls` only responders are asked. Self-issued
+/// certificates are trust anchors and keep no status.
+pub fn check_extracted(
+    certificates: &mut [ExtractedCertificate],
+    list: &TrustedList,
+    crls: Option<&CrlCache>,
+    ocsp: Option<&OcspClient>,
+    time: DateTime<Utc>,
+) {
+    let listed: Vec<(Certificate, &[String])> = list
+        .services()
+        .flat_map(|(_, service)| {
+            service.information.digital_ids.iter().filter_map(move |id| match id {
+                DigitalId::Certificate(der) => Certificate::from_der(der)
+                    .ok()
+                    .map(|certificate| (certificate, service.information.service_supply_points.as_slice())),
+                _ => None,
+            })
+        })
+        .collect();
+    let issuers: Vec<Certificate> = listed.iter().map(|(certificate, _)| certificate.clone()).collect();
+
+    for extracted in certificates.iter_mut() {
+        let certificate = match Certificate::from_der(extracted.der) {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                extracted.revocation = Some(RevocationStatus::Unknown(e.to_string()));
+                continue;
+            }
+        };
+        if certificate.is_self_issued() {
+            continue;
+        }
+
+        let mut supply_points = extracted.service.information.service_supply_points.clone();
+        let mut issuer = None;
+        for (candidate, points) in &listed {
+            if certificate.is_signed_by(candidate) {
+                supply_points.extend_from_slice(points);
+                issuer.get_or_insert(candidate);
+            }
+        }
+
+        let mut status = match (ocsp, issuer) {
+            (Some(client), Some(issuer)) => Some(client.check(&certificate, issuer, &supply_points, time)),
+            (Some(_), None) => Some(RevocationStatus::Unknown("the issuer is not in the trusted list".to_string())),
+            (None, _) => None,
+        };
+        if let Some(cache) = crls {
+            status = match status {
+                None => Some(check(&certificate, &issuers, &supply_points, cache, time)),
+                Some(RevocationStatus::Unknown(reason)) => {
+                    Some(match check(&certificate, &issuers, &supply_points, cache, time) {
+                        RevocationStatus::Unknown(crl_reason) => {
+                            RevocationStatus::Unknown(format!("{}; {}", reason, crl_reason))
+                        }
+                        decided => decided,
+                    })
+                }
+                decided => decided,
+            };
+        }
+        extracted.revocation = status;
+    }
+}
+
+fn download(url: &str, limits: &ParseLimits) -> Result<Vec<u8>, CaExtractorError> {
+    let client = reqwest::blocking::Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let response = client.get(url).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CaExtractorError::ApiError {
+            status: status.as_u16(),
+            message: format!("CRL download returned status {}", status),
+        });
+    }
+    if let Some(length) = response.content_length() {
+        if length > limits.max_download_size {
+            return Err(CaExtractorError::SafetyLimitExceeded(format!(
+                "server announced {} bytes, more than the maximum download size of {} bytes",
+                length, limits.max_download_size
+            )));
+        }
+    }
+    limits.read_limited_bytes(response)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
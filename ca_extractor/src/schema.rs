@@ -0,0 +1,86 @@
+//! Structural validation of trusted lists against the ETSI TS 119 612 v2.1.1
+//! content model.
+//!
+//! Lists of later versions (eIDAS 2.0, `TSLVersionIdentifier` 6) keep the
+//! v2.1.1 namespace and are checked against the v2.1.1 schema only: elements
+//! added by later versions of the specification are not validated.
+//!
+//! The TSL schema (`ts_119612v020101_xsd.xsd`) and the extension schemas for
+//! additional types (`ts_119612v020101_additionaltypes_xsd.xsd`, `tslx`) and
+//! service information extensions (`ts_119612v020101_sie_xsd.xsd`, `sie`) are
+//! shipped in `schemas/`, embedded in the binary and loaded by [`crate::xsd`]
+//! on first use. Validation covers element order and cardinality, attributes
+//! and the lexical form and facets of simple types. Signature internals and
+//! unknown extensions are accepted as-is, as the schemas declare them with
+//! lax processing.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::dom::{self, Element, TSL_NS};
+use crate::error::CaExtractorError;
+use crate::limits::ParseLimits;
+use crate::xsd::SchemaSet;
+
+/// The ETSI TS 119 612 v2.1.1 schemas, in the order they import each other.
+///
+/// Vendored unmodified from the ETSI TS 119 612 V2.1.1 (2015-07) release at
+/// <https://uri.etsi.org/19612/v2.1.1/>; when updating, replace the files
+/// with the published ones and keep their ETSI notice.
+const SCHEMAS: &[&str] = &[
+    include_str!("../schemas/ts_119612v020101_xsd.xsd"),
+    include_str!("../schemas/ts_119612v020101_additionaltypes_xsd.xsd"),
+    include_str!("../schemas/ts_119612v020101_sie_xsd.xsd"),
+];
+
+/// A single schema violation, located in the source document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub line: u64,
+    pub column: u64,
+    /// Local name of the element the violation was found in
+    pub element: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: <{}> {}",
+            self.line, self.column, self.element, self.message
+        )
+    }
+}
+
+/// Parses and validates a trusted list, failing with `SchemaValidationFailed`
+/// if it does not conform
+pub fn check(xml_content: &str, limits: &ParseLimits) -> Result<(), CaExtractorError> {
+    let root = dom::parse_document(xml_content, limits)?;
+    let violations = validate(&root);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(CaExtractorError::SchemaValidationFailed(violations))
+    }
+}
+
+/// The embedded schemas, loaded once
+pub fn schemas() -> &'static SchemaSet {
+    static LOADED: OnceLock<SchemaSet> = OnceLock::new();
+    LOADED.get_or_init(|| SchemaSet::load(SCHEMAS).expect("the embedded schemas load"))
+}
+
+/// Validates a parsed trusted list, returning every violation found
+pub fn validate(root: &Element) -> Vec<SchemaViolation> {
+    if root.name != "TrustServiceStatusList" || !root.in_namespace(TSL_NS) {
+        return vec![SchemaViolation {
+            line: root.line,
+            column: root.column,
+            element: root.name.clone(),
+            message: format!("is not a TrustServiceStatusList in namespace {}", TSL_NS),
+        }];
+    }
+
+    schemas().validate(root, &[])
+}
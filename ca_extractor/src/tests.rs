@@ -1,6 +1,35 @@
 #[cfg(test)]
 mod tests {
-    use crate::error::CaExtractorError;
+    use chrono::Utc;
+    use clap::Parser;
+
+    use crate::cades;
+    use crate::cms::{self, Element};
+    use crate::chain::{self, PathFailure, PathOptions};
+    use crate::cli::{Args, CaKind, CommandLineError, ErrorFormat, OcspNonce, QualificationFilter, ServiceType};
+    use crate::conformance;
+    use crate::dom;
+    use crate::endpoints::{self, EndpointKind};
+    use crate::error::{CaExtractorError, ErrorContext};
+    use crate::etsi;
+    use crate::expression::{Context, Expression};
+    use crate::extractor::{CertificateExtractor, Outcome};
+    use crate::filter::{self, CertificateFilter};
+    use crate::inspect::{self, OrganizationIdentifier};
+    use crate::jades;
+    use crate::jws;
+    use crate::limits::ParseLimits;
+    use crate::model::{
+        self, parse_date_time, CriteriaAssert, DigitalId, QualificationLevel, ServiceInformation, TakenOverBy,
+        TrustedList,
+    };
+    use crate::ocsp::OcspClient;
+    use crate::qualify;
+    use crate::revocation::{self, CrlCache, RevocationStatus};
+    use crate::schema::{self, SchemaViolation};
+    use crate::timestamp::{self, TimeStampToken};
+    use crate::tls_binding;
+    use crate::x509::{self, Certificate, QcType};
     
     // Mock XML with valid certificate
     const VALID_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -47,6 +76,10 @@ mod tests {
     // Mock JSON error response
     const JSON_RESPONSE: &str = r#"{"error": "Country not found", "code": 404}"#;
 
+    fn qwac_extractor() -> CertificateExtractor {
+        CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap()
+    }
+
     #[test]
     fn test_parse_valid_xml() {
         let result = crate::try_parse_xml(VALID_XML, "DE", "QWAC");
@@ -80,7 +113,7 @@ mod tests {
     fn test_parse_invalid_xml() {
         let result = crate::try_parse_xml(INVALID_XML, "DE", "QWAC");
         assert!(result.is_err());
-        match result {
+        match result.as_ref().map_err(CaExtractorError::root_cause) {
             Err(CaExtractorError::InvalidResponseFormat(_)) => {},
             _ => panic!("Expected InvalidResponseFormat error"),
         }
@@ -90,12 +123,34 @@ mod tests {
     fn test_parse_json_response() {
         let result = crate::try_parse_xml(JSON_RESPONSE, "DE", "QWAC");
         assert!(result.is_err());
-        match result {
+        match result.as_ref().map_err(CaExtractorError::root_cause) {
             Err(CaExtractorError::InvalidResponseFormat(_)) => {},
             _ => panic!("Expected InvalidResponseFormat error"),
         }
     }
 
+    #[test]
+    fn test_non_xml_response_context() {
+        let error = qwac_extractor().extract_certificates(INVALID_XML).unwrap_err();
+        let context = error.context().expect("response format errors carry context");
+        assert_eq!(context.source.as_deref(), Some("trusted list of DE"));
+        assert_eq!((context.line, context.column), (Some(1), Some(1)));
+        assert!(context.snippet.as_deref().unwrap().contains("This is not valid XML content"));
+
+        let error = qwac_extractor().extract_certificates(JSON_RESPONSE).unwrap_err();
+        match error.root_cause() {
+            CaExtractorError::InvalidResponseFormat(message) => assert!(message.contains("possibly JSON")),
+            _ => panic!("Expected InvalidResponseFormat error"),
+        }
+        assert!(!error.to_string().contains("Country not found"));
+
+        let error = qwac_extractor().parse_trusted_list(&format!("\n  {}", JSON_RESPONSE)).unwrap_err();
+        let context = error.context().expect("response format errors carry context");
+        assert_eq!(context.source.as_deref(), Some("trusted list of DE"));
+        assert_eq!((context.line, context.column), (Some(2), Some(3)));
+        assert!(context.snippet.as_deref().unwrap().contains(JSON_RESPONSE));
+    }
+
     // Mock the certificate extractor for testing API responses
     struct MockCertificateExtractor {
         response: String,
@@ -121,6 +176,45 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_extract_sample_trusted_list() {
+        let certs = qwac_extractor().extract_certificates(SAMPLE_TL).unwrap();
+        assert_eq!(certs.len(), 1);
+        assert!(certs[0].contains("-----BEGIN CERTIFICATE-----"));
+        assert!(certs[0].contains("-----END CERTIFICATE-----"));
+
+        let extractor = MockCertificateExtractor::new(SAMPLE_TL);
+        let xml_content = extractor.fetch_xml_content().unwrap();
+        assert!(qwac_extractor().extract_certificates(&xml_content).is_ok());
+    }
+
+    #[test]
+    fn test_try_parse_xml_enforces_limits() {
+        let result = crate::try_parse_xml(DTD_XML, "DE", "QWAC");
+        match result.as_ref().map_err(CaExtractorError::root_cause) {
+            Err(CaExtractorError::SafetyLimitExceeded(message)) => assert!(message.contains("DTD")),
+            other => panic!("Expected SafetyLimitExceeded error, got {:?}", other),
+        }
+
+        let limits = ParseLimits { max_depth: 2, ..ParseLimits::default() };
+        let result = crate::try_parse_xml_with_limits(VALID_XML, "DE", "QWAC", &limits);
+        assert!(matches!(
+            result.as_ref().map_err(CaExtractorError::root_cause),
+            Err(CaExtractorError::SafetyLimitExceeded(_))
+        ));
+        let limits = ParseLimits { max_depth: 3, ..limits };
+        assert_eq!(crate::try_parse_xml_with_limits(VALID_XML, "DE", "QWAC", &limits).unwrap().len(), 1);
+
+        match crate::try_parse_xml(NO_CERT_XML, "DE", "QWAC") {
+            Err(CaExtractorError::NoCertificatesFound { country, service }) => {
+                assert_eq!((country.as_str(), service.as_str()), ("DE", "QWAC"));
+            },
+            _ => panic!("Expected NoCertificatesFound error"),
+        }
+        let error = crate::try_parse_xml(JSON_RESPONSE, "DE", "QWAC").unwrap_err();
+        assert!(matches!(error.root_cause(), CaExtractorError::InvalidResponseFormat(_)));
+    }
+
     #[test]
     fn test_certificate_extraction_error_handling() {
         let extractor = MockCertificateExtractor::new(JSON_RESPONSE);
@@ -128,4 +222,2215 @@ mod tests {
         let result = crate::try_parse_xml(&xml_content, "DE", "QWAC");
         assert!(result.is_err());
     }
+
+    const DTD_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE lolz [<!ENTITY lol "lol"><!ENTITY lol2 "&lol;&lol;&lol;&lol;">]>
+<tsl:TrustServiceStatusList xmlns:tsl="http://uri.etsi.org/02231/v2#">
+    <TSPService>&lol2;</TSPService>
+</tsl:TrustServiceStatusList>"#;
+
+    /// The first error the limited event reader reports on `xml`, if any
+    fn first_limit_error(xml: &str, limits: ParseLimits) -> Option<CaExtractorError> {
+        limits.event_reader(xml).find_map(Result::err)
+    }
+
+    #[test]
+    fn test_dtd_is_rejected() {
+        let result = qwac_extractor().extract_certificates(DTD_XML);
+        match result.as_ref().map_err(CaExtractorError::root_cause) {
+            Err(CaExtractorError::SafetyLimitExceeded(message)) => assert!(message.contains("DTD")),
+            other => panic!("Expected SafetyLimitExceeded error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_max_depth_is_enforced() {
+        let limits = ParseLimits { max_depth: 2, ..ParseLimits::default() };
+        assert!(matches!(
+            first_limit_error(VALID_XML, limits).as_ref().map(CaExtractorError::root_cause),
+            Some(CaExtractorError::SafetyLimitExceeded(_))
+        ));
+        assert!(first_limit_error(VALID_XML, ParseLimits { max_depth: 3, ..limits }).is_none());
+
+        let result = qwac_extractor().with_limits(limits).extract_certificates(SAMPLE_TL);
+        assert!(matches!(
+            result.as_ref().map_err(CaExtractorError::root_cause),
+            Err(CaExtractorError::SafetyLimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_max_text_size_is_enforced() {
+        let limits = ParseLimits { max_text_size: 64, ..ParseLimits::default() };
+        let error = first_limit_error(VALID_XML, limits).expect("the certificate text is larger");
+        assert!(matches!(error.root_cause(), CaExtractorError::SafetyLimitExceeded(_)));
+        assert_eq!(error.context().and_then(|context| context.line), Some(4));
+
+        let result = qwac_extractor().with_limits(limits).extract_certificates(SAMPLE_TL);
+        assert!(matches!(
+            result.as_ref().map_err(CaExtractorError::root_cause),
+            Err(CaExtractorError::SafetyLimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_max_text_size_spans_cdata_sections() {
+        // Each node stays under the limit, the element text does not
+        let limits = ParseLimits { max_text_size: 64, ..ParseLimits::default() };
+        let chunk = "A".repeat(40);
+        let xml = format!("<root><text><![CDATA[{0}]]>{0}<![CDATA[{0}]]></text></root>", chunk);
+        assert!(first_limit_error(&xml, limits).is_none());
+
+        let error = dom::parse_document(&xml, &limits).unwrap_err();
+        match error.root_cause() {
+            CaExtractorError::SafetyLimitExceeded(message) => assert!(message.contains("<text>"), "{}", message),
+            other => panic!("Expected SafetyLimitExceeded error, got {:?}", other),
+        }
+        assert!(error.context().is_some_and(|context| context.line == Some(1)));
+        let limits = ParseLimits { max_text_size: 120, ..limits };
+        assert_eq!(dom::parse_document(&xml, &limits).unwrap().child("text").unwrap().text.len(), 120);
+    }
+
+    #[test]
+    fn test_max_download_size_is_enforced() {
+        let limits = ParseLimits { max_download_size: 16, ..ParseLimits::default() };
+        assert!(matches!(
+            limits.read_limited(VALID_XML.as_bytes()),
+            Err(CaExtractorError::SafetyLimitExceeded(_))
+        ));
+
+        let limits = ParseLimits { max_download_size: VALID_XML.len() as u64, ..ParseLimits::default() };
+        assert_eq!(limits.read_limited(VALID_XML.as_bytes()).unwrap(), VALID_XML);
+    }
+
+    const SAMPLE_TL: &str = include_str!("../tests/fixtures/sample_tl.xml");
+
+    fn schema_violations(xml: &str) -> Vec<SchemaViolation> {
+        match schema::check(xml, &ParseLimits::default()) {
+            Ok(()) => Vec::new(),
+            Err(CaExtractorError::SchemaValidationFailed(violations)) => violations,
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_sample_trusted_list_is_schema_valid() {
+        assert_eq!(schema_violations(SAMPLE_TL), Vec::new());
+    }
+
+    #[test]
+    fn test_schema_reports_missing_element_with_position() {
+        let xml = SAMPLE_TL.replace("<tsl:TSLSequenceNumber>42</tsl:TSLSequenceNumber>", "");
+        let violations = schema_violations(&xml);
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].line, violations[0].column), (6, 9));
+        assert!(violations[0].message.contains("TSLSequenceNumber"));
+    }
+
+    #[test]
+    fn test_schema_reports_invalid_values_and_attributes() {
+        let xml = SAMPLE_TL
+            .replace("<tsl:ListIssueDateTime>2025-06-01T00:00:00Z", "<tsl:ListIssueDateTime>June 2025")
+            .replace(r#"<tsl:Name xml:lang="de">"#, "<tsl:Name>");
+        let violations = schema_violations(&xml);
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().any(|v| v.element == "ListIssueDateTime"));
+        assert!(violations.iter().any(|v| v.element == "Name" && v.message.contains("xml:lang")));
+    }
+
+    #[test]
+    fn test_schema_integer_sign() {
+        let with_version = |version: &str| {
+            SAMPLE_TL.replace("<tsl:TSLVersionIdentifier>5<", &format!("<tsl:TSLVersionIdentifier>{}<", version))
+        };
+        for valid in ["5", "+5", "-5", "007"] {
+            assert_eq!(schema_violations(&with_version(valid)), Vec::new(), "{}", valid);
+        }
+        for invalid in ["+-5", "--1", "++1", "-", "5-", ""] {
+            let violations = schema_violations(&with_version(invalid));
+            assert_eq!(violations.len(), 1, "{}", invalid);
+            assert_eq!(violations[0].element, "TSLVersionIdentifier");
+        }
+
+        let with_sequence_number = |number: &str| {
+            SAMPLE_TL.replace("<tsl:TSLSequenceNumber>42<", &format!("<tsl:TSLSequenceNumber>{}<", number))
+        };
+        assert_eq!(schema_violations(&with_sequence_number("+42")), Vec::new());
+        for invalid in ["0", "+0", "++42", "-42"] {
+            assert_eq!(schema_violations(&with_sequence_number(invalid)).len(), 1, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_schema_reports_unexpected_element() {
+        let xml = SAMPLE_TL.replacen(
+            "<tsl:SchemeTerritory>DE</tsl:SchemeTerritory>",
+            "<tsl:SchemeTerritory>DE</tsl:SchemeTerritory><tsl:Bogus/>",
+            1,
+        );
+        let violations = schema_violations(&xml);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].element, "Bogus");
+    }
+
+    #[test]
+    fn test_schema_validates_extensions_against_their_schemas() {
+        let xml = SAMPLE_TL.replace("</tslx:TakenOverBy>", "<tsl:Bogus/></tslx:TakenOverBy>");
+        let violations = schema_violations(&xml);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].element, "Bogus");
+        assert!(violations[0].message.contains("<TakenOverBy>"));
+    }
+
+    fn sample_list() -> TrustedList {
+        TrustedList::parse(SAMPLE_TL, &ParseLimits::default()).unwrap()
+    }
+
+    fn rules_hit(list: &TrustedList) -> Vec<&'static str> {
+        conformance::check(list).iter().map(|finding| finding.rule).collect()
+    }
+
+    #[test]
+    fn test_sample_list_model() {
+        let list = sample_list();
+        assert_eq!(list.sequence_number, Some(42));
+        assert_eq!(list.scheme_territory.as_deref(), Some("DE"));
+        assert_eq!(list.providers.len(), 2);
+
+        let services = &list.providers[0].services;
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].information.service_type, etsi::SVCTYPE_CA_QC);
+        assert_eq!(services[0].information.additional_information, vec![etsi::ASI_FOR_WEB_SITE_AUTHENTICATION]);
+        assert!(matches!(services[0].information.digital_ids[0], DigitalId::Certificate(_)));
+        assert!(matches!(services[0].history[0].digital_ids[0], DigitalId::SubjectName(_)));
+    }
+
+    #[test]
+    fn test_sample_list_is_conformant() {
+        assert_eq!(rules_hit(&sample_list()), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_conformance_history_order() {
+        let mut list = sample_list();
+        list.providers[0].services[0].history[0].status_starting_time = parse_date_time("2024-06-01T00:00:00Z");
+        let findings = conformance::check(&list);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "TL-001");
+        assert_eq!(findings[0].location, list.providers[0].services[0].history[0].location);
+        assert!(findings[0].context.contains("Example QWAC Root CA"));
+    }
+
+    #[test]
+    fn test_conformance_status_and_additional_information() {
+        let mut list = sample_list();
+        list.providers[0].services[0].information.status = etsi::STATUS_RECOGNISED_AT_NATIONAL_LEVEL.to_string();
+        list.providers[0].services[1].information.service_type =
+            "http://uri.etsi.org/TrstSvc/Svctype/TSA/QTST".to_string();
+        assert_eq!(rules_hit(&list), vec!["TL-002", "TL-003"]);
+    }
+
+    #[test]
+    fn test_conformance_next_update_and_duplicates() {
+        let mut list = sample_list();
+        list.next_update = list.issue_date;
+        let qwac_id = list.providers[0].services[0].information.digital_ids[0].clone();
+        list.providers[0].services[1].information.digital_ids.push(qwac_id);
+        assert_eq!(rules_hit(&list), vec!["TL-004", "TL-005"]);
+    }
+
+    #[test]
+    fn test_xml_error_keeps_position_and_snippet() {
+        let xml = SAMPLE_TL.replace("<tsl:TSLType>", "<tsl:TSLType attr>");
+        let error = TrustedList::parse(&xml, &ParseLimits::default()).unwrap_err();
+        assert!(matches!(error.root_cause(), CaExtractorError::XmlError(_)));
+
+        let context = error.context().expect("XML errors carry context");
+        assert_eq!((context.line, context.column), (Some(6), Some(26)));
+        let snippet = context.snippet.as_deref().unwrap();
+        assert!(snippet.contains("> 6 |         <tsl:TSLType attr>"));
+        assert!(snippet.contains(&format!("{}^", " ".repeat(25))));
+    }
+
+    #[test]
+    fn test_invalid_certificate_error_names_tsp_and_service() {
+        let xml = SAMPLE_TL.replacen("<tsl:X509Certificate>MII", "<tsl:X509Certificate>!MII", 1);
+        let error = TrustedList::parse(&xml, &ParseLimits::default())
+            .unwrap_err()
+            .with_context(ErrorContext::source("file sample_tl.xml"));
+        assert!(matches!(error.root_cause(), CaExtractorError::InvalidCertificateFormat(_)));
+
+        let context = error.context().unwrap();
+        assert_eq!(context.source.as_deref(), Some("file sample_tl.xml"));
+        assert_eq!(context.tsp.as_deref(), Some("Example Trust GmbH"));
+        assert_eq!(context.service.as_deref(), Some("Example QWAC Root CA"));
+        assert_eq!(context.line, Some(82));
+        assert!(error.to_string().contains("TSP 'Example Trust GmbH', service 'Example QWAC Root CA'"));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_exit_codes_follow_root_cause() {
+        let no_certs = CaExtractorError::NoCertificatesFound {
+            country: "DE".to_string(),
+            service: "QWAC".to_string(),
+        };
+        assert_eq!(no_certs.exit_code(), 3);
+        assert_eq!(CaExtractorError::InvalidCountryCode("DEU".to_string()).exit_code(), 2);
+        assert_eq!(CaExtractorError::SafetyLimitExceeded("depth".to_string()).exit_code(), 7);
+
+        let wrapped = no_certs.with_context(ErrorContext::source("trusted list of DE"));
+        assert_eq!(wrapped.exit_code(), 3);
+        assert_eq!(wrapped.kind(), "no_certificates_found");
+    }
+
+    #[test]
+    fn test_json_error_output() {
+        let xml = SAMPLE_TL.replace("<tsl:TSLType>", "<tsl:TSLType attr>");
+        let error = TrustedList::parse(&xml, &ParseLimits::default())
+            .unwrap_err()
+            .with_context(ErrorContext::source("file sample_tl.xml"));
+        let json = error.to_json();
+
+        assert_eq!(json["error"]["kind"], "xml_error");
+        assert_eq!(json["error"]["exit_code"], 6);
+        assert_eq!(json["error"]["context"]["source"], "file sample_tl.xml");
+        assert_eq!(json["error"]["context"]["line"], 6);
+        assert_eq!(json["error"]["context"]["column"], 26);
+        assert!(json["error"]["causes"].as_array().is_some_and(|causes| !causes.is_empty()));
+
+        let invalid = SAMPLE_TL.replace("<tsl:TSLSequenceNumber>42", "<tsl:TSLSequenceNumber>x");
+        let schema_error = schema::check(&invalid, &ParseLimits::default()).unwrap_err();
+        let json = schema_error.to_json();
+        assert_eq!(json["error"]["exit_code"], 8);
+        assert!(json["error"]["violations"][0]["line"].is_u64());
+    }
+
+    fn selected_services(list: &TrustedList, services: &[ServiceType]) -> Vec<String> {
+        let extractor = CertificateExtractor::new(services, "DE").unwrap();
+        extractor
+            .select(list)
+            .iter()
+            .map(|c| c.service.information.names[0].value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_service_type_selection() {
+        let list = sample_list();
+        assert_eq!(selected_services(&list, &[ServiceType::QWAC]), vec!["Example QWAC Root CA"]);
+        assert_eq!(selected_services(&list, &[ServiceType::QESig]), vec!["Example QESig Root CA"]);
+        assert_eq!(selected_services(&list, &[ServiceType::QTST]), vec!["Example QTST Unit 1"]);
+        assert_eq!(
+            selected_services(&list, &[ServiceType::QTST, ServiceType::QSealC]),
+            vec!["Example QSealC Root CA", "Example QTST Unit 1"]
+        );
+        assert!(selected_services(&list, &[ServiceType::QERDS]).is_empty());
+    }
+
+    #[test]
+    fn test_selected_certificate_is_returned_once() {
+        let mut list = sample_list();
+        let information = &mut list.providers[0].services[0].information;
+        information.additional_information.push(etsi::ASI_FOR_ESEALS.to_string());
+        information.service_type = etsi::SVCTYPE_CA_QC.to_string();
+
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE").unwrap();
+        let selected = extractor.select(&list);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].service_types, vec![ServiceType::QWAC, ServiceType::QSealC]);
+        assert_eq!(selected[1].service_types, vec![ServiceType::QSealC]);
+    }
+
+    #[test]
+    fn test_service_type_uri_mapping() {
+        let selector = ServiceType::OcspQC.selector();
+        assert_eq!(selector.service_type, etsi::SVCTYPE_OCSP_QC);
+        assert!(selector.matches(etsi::SVCTYPE_OCSP_QC, &[] as &[&str]));
+        assert!(!ServiceType::QWAC.selector().matches(etsi::SVCTYPE_CA_QC, &[etsi::ASI_FOR_ESEALS]));
+        assert!(etsi::is_qualified_service_type(etsi::SVCTYPE_NATIONAL_ROOT_CA_QC));
+        assert!(!etsi::is_qualified_service_type(etsi::SVCTYPE_CA_PKC));
+        assert_eq!(ServiceType::NationalRootCaQC.to_string(), "NationalRootCA-QC");
+    }
+
+    #[test]
+    fn test_multiple_service_types_on_command_line() {
+        let args = Args::try_parse_from(["ca_extractor", "QWAC,QTST,OCSP-QC", "DE"]).unwrap();
+        assert_eq!(args.service, vec![ServiceType::QWAC, ServiceType::QTST, ServiceType::OcspQC]);
+        assert_eq!(args.country.as_deref(), Some("DE"));
+        assert!(Args::try_parse_from(["ca_extractor", "QWAC,Bogus", "DE"]).is_err());
+    }
+
+    #[test]
+    fn test_service_types_by_uri_on_command_line() {
+        let unknown = "http://uri.etsi.org/TrstSvc/Svctype/Foo";
+        let args = Args::try_parse_from([
+            "ca_extractor",
+            &format!("CA/QC+ForeSeals,{},ForWebSiteAuthentication,{}", etsi::SVCTYPE_TSA_QTST, unknown),
+            "DE",
+        ])
+        .unwrap();
+        let seals = ServiceType::Uri {
+            service_type: etsi::SVCTYPE_CA_QC.to_string(),
+            additional_information: Some(etsi::ASI_FOR_ESEALS.to_string()),
+        };
+        let web = ServiceType::Uri {
+            service_type: etsi::SVCTYPE_CA_QC.to_string(),
+            additional_information: Some(etsi::ASI_FOR_WEB_SITE_AUTHENTICATION.to_string()),
+        };
+        let tst = ServiceType::Uri {
+            service_type: etsi::SVCTYPE_TSA_QTST.to_string(),
+            additional_information: None,
+        };
+        let foo = ServiceType::Uri {
+            service_type: unknown.to_string(),
+            additional_information: None,
+        };
+        assert_eq!(args.service, vec![seals.clone(), tst.clone(), web, foo.clone()]);
+        assert_eq!(seals.to_string(), "CA/QC+ForeSeals");
+        assert_eq!(tst.to_string(), "TSA/QTST");
+
+        // Short names must be defined by ETSI, full URIs need not be
+        assert!(Args::try_parse_from(["ca_extractor", "CA/Bogus", "DE"]).is_err());
+        assert!(Args::try_parse_from(["ca_extractor", "CA/QC+Bogus", "DE"]).is_err());
+        assert!(Args::try_parse_from(["ca_extractor", "CA/QC+http://example.com/ForeSeals", "DE"]).is_err());
+
+        let list = sample_list();
+        assert_eq!(selected_services(&list, &[seals]), vec!["Example QSealC Root CA"]);
+        assert_eq!(selected_services(&list, &[tst]), vec!["Example QTST Unit 1"]);
+        assert!(selected_services(&list, &[foo]).is_empty());
+    }
+
+    /// The sample list in the eIDAS 2.0 format, with the QTST service turned into a QEAA issuer
+    fn eidas2_sample(version: &str) -> String {
+        SAMPLE_TL
+            .replace("<tsl:TSLVersionIdentifier>5<", &format!("<tsl:TSLVersionIdentifier>{}<", version))
+            .replace(etsi::SVCTYPE_TSA_QTST, etsi::SVCTYPE_QEAA)
+    }
+
+    #[test]
+    fn test_invalid_service_type_on_the_command_line() {
+        let error = |args: &[&str]| match Args::try_parse_command_line(args) {
+            Err(CommandLineError::ServiceType(e, format)) => (e, format),
+            other => panic!("Unexpected result: {:?}", other),
+        };
+
+        let (e, format) = error(&["ca_extractor", "QWAC,Bogus", "DE"]);
+        assert!(matches!(e, CaExtractorError::InvalidServiceType(ref message) if message.starts_with("'Bogus'")));
+        assert_eq!((e.exit_code(), e.kind()), (2, "invalid_service_type"));
+        assert_eq!(format, ErrorFormat::Text);
+
+        // --error-format is honoured wherever it is given
+        let (_, format) = error(&["ca_extractor", "QWAC,Bogus", "DE", "--error-format", "json"]);
+        assert_eq!(format, ErrorFormat::Json);
+        let (e, format) = error(&["ca_extractor", "verify", "--service", "CA/Bogus", "x.pem", "--error-format=json"]);
+        assert_eq!((e.kind(), format), ("invalid_service_type", ErrorFormat::Json));
+
+        // Other usage errors are left to clap
+        let other = Args::try_parse_command_line(["ca_extractor", "QWAC", "DE", "--qualification", "bogus"]);
+        assert!(matches!(other, Err(CommandLineError::Clap(_))));
+        assert!(Args::try_parse_command_line(["ca_extractor", "QWAC", "DE"]).is_ok());
+    }
+
+    #[test]
+    fn test_eidas2_list_is_parsed_and_validated() {
+        let xml = eidas2_sample("6");
+        assert_eq!(schema_violations(&xml), Vec::new());
+
+        let list = TrustedList::parse(&xml, &ParseLimits::default()).unwrap();
+        assert_eq!(list.version, Some(etsi::TSL_VERSION_EIDAS2));
+        assert_eq!(rules_hit(&list), Vec::<&str>::new());
+        assert_eq!(selected_services(&list, &[ServiceType::QEAA]), vec!["Example QTST Unit 1"]);
+        assert!(etsi::is_qualified_service_type(etsi::SVCTYPE_QEAA));
+
+        // No other namespace is validated against the v2.1.1 schema
+        let violations = schema_violations(&xml.replace(dom::TSL_NS, "http://uri.etsi.org/19612/v3#"));
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].message.starts_with("is not a TrustServiceStatusList in namespace"));
+    }
+
+    #[test]
+    fn test_eidas2_service_type_requires_version_6() {
+        let list = TrustedList::parse(&eidas2_sample("5"), &ParseLimits::default()).unwrap();
+        let findings = conformance::check(&list);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "TL-006");
+        assert!(findings[0].message.contains("EAA/Q"));
+
+        let list = TrustedList::parse(&eidas2_sample("7"), &ParseLimits::default()).unwrap();
+        assert_eq!(rules_hit(&list), vec!["TL-006"]);
+    }
+
+    #[test]
+    fn test_qualification_level_filter() {
+        let list = sample_list();
+        let ca_types = [ServiceType::QWAC, ServiceType::QSealC, ServiceType::QESig];
+        let names = |qualification| {
+            CertificateExtractor::new(&ca_types, "DE")
+                .unwrap()
+                .with_qualification(qualification)
+                .select(&list)
+                .iter()
+                .map(|c| (c.service.information.names[0].value.clone(), c.qualification()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(QualificationFilter::Qualified).len(), 3);
+        assert!(names(QualificationFilter::Qualified)
+            .iter()
+            .all(|(_, level)| *level == QualificationLevel::Qualified));
+        assert_eq!(
+            names(QualificationFilter::National),
+            vec![("Example National CA".to_string(), QualificationLevel::National)]
+        );
+        assert_eq!(names(QualificationFilter::All).len(), 4);
+    }
+
+    #[test]
+    fn test_withdrawn_services_are_not_qualified() {
+        let mut list = sample_list();
+        let qseal = service_listing(&mut list, &fixture_certificate("qseal_root"));
+        qseal.status = etsi::STATUS_WITHDRAWN.to_string();
+        assert_eq!(qseal.qualification_level(), QualificationLevel::Withdrawn);
+        let national = service_listing(&mut list, &fixture_certificate("national_ca"));
+        national.status = etsi::STATUS_DEPRECATED_AT_NATIONAL_LEVEL.to_string();
+        assert_eq!(national.qualification_level(), QualificationLevel::Withdrawn);
+
+        let ca_types = [ServiceType::QWAC, ServiceType::QSealC, ServiceType::QESig];
+        let extractor = |qualification| {
+            CertificateExtractor::new(&ca_types, "DE").unwrap().with_qualification(qualification)
+        };
+        let names = |qualification| -> Vec<String> {
+            let selected = extractor(qualification).select(&list);
+            selected.iter().map(|c| c.service.information.names[0].value.clone()).collect()
+        };
+        assert_eq!(names(QualificationFilter::Qualified), vec!["Example QWAC Root CA", "Example QESig Root CA"]);
+        assert!(names(QualificationFilter::National).is_empty());
+        assert_eq!(
+            names(QualificationFilter::Withdrawn),
+            vec!["Example QSealC Root CA", "Example National CA"]
+        );
+        assert_eq!(names(QualificationFilter::All), vec!["Example QWAC Root CA", "Example QESig Root CA"]);
+
+        let withdrawn = extractor(QualificationFilter::Withdrawn).select(&list);
+        assert!(withdrawn[0].annotated_pem().starts_with("Qualification: withdrawn\n"));
+        assert_eq!(withdrawn[0].to_json()["qualification"], "withdrawn");
+        assert_eq!(withdrawn[0].service_types, vec![ServiceType::QSealC]);
+    }
+
+    #[test]
+    fn test_all_qualification_levels_exclude_withdrawn_services() {
+        let mut list = sample_list();
+        service_listing(&mut list, &fixture_certificate("qseal_root")).status = etsi::STATUS_WITHDRAWN.to_string();
+
+        let ca_types = [ServiceType::QWAC, ServiceType::QSealC, ServiceType::QESig];
+        let extractor = CertificateExtractor::new(&ca_types, "DE").unwrap();
+        let selected = extractor.with_qualification(QualificationFilter::All).select(&list);
+        let names: Vec<&str> = selected.iter().map(|c| c.service.information.names[0].value.as_str()).collect();
+        assert_eq!(names, vec!["Example QWAC Root CA", "Example QESig Root CA", "Example National CA"]);
+        assert_eq!(selected[2].service.information.service_type, etsi::SVCTYPE_CA_PKC);
+    }
+
+    #[test]
+    fn test_extracted_pem_is_marked_with_qualification() {
+        let list = sample_list();
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC], "DE")
+            .unwrap()
+            .with_qualification(QualificationFilter::All);
+        let selected = extractor.select(&list);
+        let pems: Vec<String> = selected.iter().map(|c| c.annotated_pem()).collect();
+
+        assert_eq!(pems.len(), 2);
+        assert!(pems[0].starts_with("Qualification: qualified\n"));
+        assert!(pems[1].starts_with("Qualification: national\n"));
+        assert!(pems[1].contains("Service: Example National CA\n-----BEGIN CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn test_service_information_extensions_are_parsed() {
+        let list = sample_list();
+        let qseal = &list.providers[0].services[1].information;
+        assert_eq!(qseal.qualifiers().collect::<Vec<_>>(), vec![etsi::QUALIFIER_QC_FOR_LEGAL_PERSON]);
+        assert_eq!(
+            qseal.qualifications[0].criteria.key_usage,
+            vec![vec![("nonRepudiation".to_string(), true)]]
+        );
+        assert!(qseal.taken_over_by.is_none());
+
+        let qesig = &list.providers[1].services[0].information;
+        let criteria = &qesig.qualifications[0].criteria;
+        assert_eq!(criteria.assert, CriteriaAssert::AtLeastOne);
+        assert_eq!(criteria.policy_sets, vec![vec!["0.4.0.194112.1.2".to_string()]]);
+
+        let taken_over_by = qesig.taken_over_by.as_ref().unwrap();
+        assert_eq!(taken_over_by.tsp_names[0].value, "Example Trust GmbH");
+        assert_eq!(taken_over_by.scheme_territory, "DE");
+        assert_eq!(qesig.expired_certs_revocation_info, parse_date_time("2024-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_qualifier_and_taken_over_filters() {
+        let list = sample_list();
+        let extractor = || CertificateExtractor::new(&[ServiceType::QSealC, ServiceType::QESig], "DE").unwrap();
+        let names = |extractor: CertificateExtractor| -> Vec<String> {
+            extractor
+                .select(&list)
+                .iter()
+                .map(|c| c.service.information.names[0].value.clone())
+                .collect()
+        };
+
+        assert_eq!(names(extractor()).len(), 2);
+        assert_eq!(
+            names(extractor().with_qualifiers(vec![etsi::QUALIFIER_QC_WITH_QSCD.to_string()])),
+            vec!["Example QESig Root CA"]
+        );
+        assert_eq!(
+            names(extractor().without_qualifiers(vec![etsi::QUALIFIER_QC_FOR_LEGAL_PERSON.to_string()])),
+            vec!["Example QESig Root CA"]
+        );
+        assert_eq!(names(extractor().without_taken_over(true)), vec!["Example QSealC Root CA"]);
+    }
+
+    #[test]
+    fn test_extracted_certificate_json() {
+        let list = sample_list();
+        let extractor = CertificateExtractor::new(&[ServiceType::QESig], "DE").unwrap();
+        let json = extractor.select(&list)[0].to_json();
+
+        assert_eq!(json["qualification"], "qualified");
+        assert_eq!(json["selected_as"][0], "QESig");
+        assert_eq!(json["tsp"]["names"][0]["value"], "Beispiel Signatur AG");
+        assert_eq!(json["service"]["qualifications"][0]["qualifiers"][0], etsi::QUALIFIER_QC_WITH_QSCD);
+        assert_eq!(json["service"]["qualifications"][0]["criteria"]["assert"], "atLeastOne");
+        assert_eq!(json["service"]["taken_over_by"]["scheme_territory"], "DE");
+        assert_eq!(json["service"]["expired_certs_revocation_info"], "2024-01-01T00:00:00+00:00");
+        assert!(json["pem"].as_str().unwrap().starts_with("-----BEGIN CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn test_qualifier_names_on_command_line() {
+        let args = Args::try_parse_from(["ca_extractor", "QESig", "DE", "--qualifier", "QCWithQSCD"]).unwrap();
+        assert_eq!(args.qualifiers, vec![etsi::QUALIFIER_QC_WITH_QSCD]);
+        assert!(Args::try_parse_from(["ca_extractor", "QESig", "DE", "--qualifier", "QCWithWhatever"]).is_err());
+    }
+
+    fn fixture_certificate(name: &str) -> Certificate {
+        let pem = std::fs::read(format!("{}/tests/fixtures/pki/{}.pem", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        x509::parse_certificates(&pem).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_certificate_parsing() {
+        let leaf = fixture_certificate("qseal_leaf");
+        assert_eq!(leaf.subject, "C=DE, O=Example Bank AG, CN=Example Bank Seal");
+        assert_eq!(leaf.key_usage, Some(vec!["digitalSignature", "nonRepudiation"]));
+        assert_eq!(leaf.policies, vec!["0.4.0.194112.1.3"]);
+        assert!(leaf.qc_statements.compliance && leaf.qc_statements.sscd);
+        assert_eq!(leaf.qc_statements.types, vec![QcType::ESeal]);
+        assert!(leaf.is_signed_by(&fixture_certificate("qseal_root")));
+        assert!(!leaf.is_signed_by(&fixture_certificate("qwac_root")));
+
+        assert!(matches!(
+            x509::parse_certificates(b"not a certificate"),
+            Err(CaExtractorError::InvalidCertificateFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_qualify_qwac_through_intermediate() {
+        let list = sample_list();
+        let leaf = fixture_certificate("qwac_leaf");
+
+        let unchained = qualify::qualify(&list, &leaf, &[]);
+        assert!(!unchained.qualified);
+        assert!(unchained.service.is_none());
+
+        let result = qualify::qualify(&list, &leaf, &[fixture_certificate("qwac_issuing_ca")]);
+        assert!(result.qualified, "{:?}", result.steps);
+        assert_eq!(result.qc_type, Some(QcType::Web));
+        assert_eq!(result.qscd, None);
+        assert_eq!(result.chain.len(), 2);
+        assert_eq!(result.service.unwrap().information.names[0].value, "Example QWAC Root CA");
+
+        // An end-entity certificate does not issue certificates, whatever it signed
+        let issued_by_leaf = fixture_certificate("leaf_issued_leaf");
+        let intermediates = [leaf, fixture_certificate("qwac_issuing_ca")];
+        let result = qualify::qualify(&list, &issued_by_leaf, &intermediates);
+        assert!(!result.qualified);
+        assert!(result.service.is_none());
+        assert!(result.steps.iter().any(|step| step.contains("is not a CA (basicConstraints)")), "{:?}", result.steps);
+    }
+
+    #[test]
+    fn test_qualify_applies_service_qualifiers() {
+        let list = sample_list();
+
+        let seal = qualify::qualify(&list, &fixture_certificate("qseal_leaf"), &[]);
+        assert!(seal.qualified);
+        assert_eq!(seal.qc_type, Some(QcType::ESeal));
+        assert_eq!(seal.qscd, Some(true));
+        assert!(seal.legal_person);
+
+        // QCWithQSCD applies through the PolicySet criterion
+        let signature = qualify::qualify(&list, &fixture_certificate("qesig_leaf"), &[]);
+        assert!(signature.qualified);
+        assert_eq!(signature.qc_type, Some(QcType::ESign));
+        assert_eq!(signature.qscd, Some(true));
+        assert!(signature.steps.iter().any(|step| step.contains("meets the criteria of qualifiers QCWithQSCD")));
+        assert_eq!(signature.to_json()["tsp"], "Beispiel Signatur AG");
+    }
+
+    #[test]
+    fn test_qualify_uses_status_at_issuance() {
+        let leaf = fixture_certificate("qesig_leaf");
+        let mut list = sample_list();
+        let service = list
+            .providers
+            .iter_mut()
+            .flat_map(|provider| provider.services.iter_mut())
+            .find(|service| service.information.names[0].value == "Example QESig Root CA")
+            .unwrap();
+
+        // Withdrawn later: certificates issued while granted stay qualified
+        let mut withdrawn = service.information.clone();
+        withdrawn.status = etsi::STATUS_WITHDRAWN.to_string();
+        withdrawn.status_starting_time = parse_date_time("2030-01-01T00:00:00Z");
+        let granted = std::mem::replace(&mut service.information, withdrawn);
+        service.history.insert(0, granted);
+        assert!(qualify::qualify(&list, &leaf, &[]).qualified);
+
+        // Withdrawn before issuance
+        let service = &mut list.providers[1].services[0];
+        service.history[0].status = etsi::STATUS_WITHDRAWN.to_string();
+        let result = qualify::qualify(&list, &leaf, &[]);
+        assert!(!result.qualified);
+        assert!(result.steps.iter().any(|step| step.contains("not granted at issuance")));
+    }
+
+    #[test]
+    fn test_qualify_command_line() {
+        let args = Args::try_parse_from([
+            "ca_extractor", "qualify", "leaf.pem", "--file", "tl.xml", "--chain", "chain.pem", "--format", "json",
+        ])
+        .unwrap();
+        let Some(crate::cli::Command::Qualify(qualify_args)) = args.command else {
+            panic!("expected the qualify subcommand");
+        };
+        assert_eq!(qualify_args.cert.to_str(), Some("leaf.pem"));
+        assert!(qualify_args.chain.is_some());
+        assert!(Args::try_parse_from(["ca_extractor", "qualify", "--file", "tl.xml"]).is_err());
+    }
+
+    #[test]
+    fn test_psd2_statements() {
+        let leaf = fixture_certificate("psd2_qwac_leaf");
+        let statements = &leaf.qc_statements;
+        assert_eq!(leaf.organization_identifier.as_deref(), Some("PSDDE-BAFIN-123456"));
+        assert_eq!(statements.retention_period, Some(15));
+        assert_eq!(statements.pds[0].url, "https://pki.example.com/pds_en.pdf");
+        assert_eq!(statements.pds[0].language, "en");
+
+        let psd2 = statements.psd2.as_ref().unwrap();
+        assert_eq!(psd2.nca_id, "DE-BAFIN");
+        assert_eq!(psd2.roles.iter().map(|role| role.name.as_str()).collect::<Vec<_>>(), vec!["PSP_AS", "PSP_PI"]);
+        assert!(psd2.roles.iter().all(|role| role.is_consistent()));
+
+        let id = OrganizationIdentifier::parse("PSDDE-BAFIN-123456").unwrap();
+        assert_eq!((id.scheme.as_str(), id.country.as_str(), id.reference.as_str()), ("PSD", "DE", "BAFIN-123456"));
+        assert!(OrganizationIdentifier::parse("Example Bank").is_none());
+    }
+
+    #[test]
+    fn test_inspect_qc_cross_check() {
+        let list = sample_list();
+        let intermediates = [fixture_certificate("qwac_issuing_ca")];
+        let cas = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE").unwrap().select(&list);
+
+        let inspection = inspect::inspect_qc(&fixture_certificate("psd2_qwac_leaf"), &intermediates, &cas);
+        assert!(inspection.issues.is_empty(), "{:?}", inspection.issues);
+        assert_eq!(inspection.issuer.unwrap().service_types, vec![ServiceType::QWAC]);
+        assert_eq!(inspection.chain.len(), 2);
+
+        let mut tampered = fixture_certificate("psd2_qwac_leaf");
+        tampered.qc_statements.psd2.as_mut().unwrap().nca_id = "FR-ACPR".to_string();
+        let inspection = inspect::inspect_qc(&tampered, &intermediates, &cas);
+        assert!(inspection.issues.iter().any(|issue| issue.contains("does not match the NCA identifier")));
+
+        // A QWAC checked against the QSealC CAs only
+        let seal_cas = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap().select(&list);
+        let inspection = inspect::inspect_qc(&fixture_certificate("qwac_leaf"), &intermediates, &seal_cas);
+        assert!(inspection.issuer.is_none());
+        assert_eq!(inspection.to_json()["qc_statements"]["types"][0], "web");
+
+        // Chained through an end-entity certificate
+        let intermediates_with_leaf = [fixture_certificate("qwac_leaf"), fixture_certificate("qwac_issuing_ca")];
+        let inspection = inspect::inspect_qc(&fixture_certificate("leaf_issued_leaf"), &intermediates_with_leaf, &cas);
+        assert!(inspection.issuer.is_none());
+        assert!(inspection.issues.iter().any(|issue| issue.contains("is not a CA")), "{:?}", inspection.issues);
+
+        // Issued under a CA whose service has been withdrawn since
+        let mut list = sample_list();
+        let withdrawal = parse_date_time("2025-01-01T00:00:00Z").unwrap();
+        withdraw_service(&mut list, &fixture_certificate("qwac_root"), withdrawal);
+        let cas = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE")
+            .unwrap()
+            .with_qualification(QualificationFilter::Withdrawn)
+            .select(&list);
+        let inspection = inspect::inspect_qc(&fixture_certificate("psd2_qwac_leaf"), &intermediates, &cas);
+        assert!(inspection.issuer.is_some());
+        assert_eq!(
+            inspection.issues,
+            vec!["the service 'Example QWAC Root CA' of the issuing CA is withdrawn with status 'withdrawn'"]
+        );
+    }
+
+    #[test]
+    fn test_verify_path_to_qwac_ca() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let leaf = fixture_certificate("qwac_leaf");
+        let intermediate = fixture_certificate("qwac_issuing_ca");
+        let options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: chain::key_purpose_oid("serverAuth"),
+            require_service_in_force: true,
+        };
+
+        let path = chain::validate(&leaf, std::slice::from_ref(&intermediate), &anchors, &options).unwrap();
+        assert_eq!(path.chain.len(), 2);
+        assert_eq!(path.anchor.service.information.names[0].value, "Example QWAC Root CA");
+
+        let failures = chain::validate(&leaf, &[], &anchors, &options).unwrap_err();
+        assert!(matches!(
+            &failures[0],
+            PathFailure::IssuerNotFound { issuer, .. } if issuer.contains("QWAC Issuing CA")
+        ));
+
+        // Only QSealC CAs are trusted: the issuing CA's own issuer is unknown
+        let seal_anchors = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap().select(&list);
+        let failures = chain::validate(&leaf, &[intermediate], &seal_anchors, &options).unwrap_err();
+        assert!(matches!(
+            &failures[0],
+            PathFailure::IssuerNotFound { subject, .. } if subject.contains("QWAC Issuing CA")
+        ));
+    }
+
+    #[test]
+    fn test_verify_path_service_withdrawn() {
+        let mut list = sample_list();
+        let service = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        let granted = service.clone();
+        service.status = etsi::STATUS_WITHDRAWN.to_string();
+        service.status_starting_time = parse_date_time("2025-01-01T00:00:00Z");
+        list.providers[0].services[0].history.insert(0, granted);
+
+        let anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE")
+            .unwrap()
+            .with_qualification(QualificationFilter::Withdrawn)
+            .select(&list);
+        let leaf = fixture_certificate("qwac_leaf");
+        let intermediates = [fixture_certificate("qwac_issuing_ca")];
+        let mut options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: None,
+            require_service_in_force: true,
+        };
+        let failures = chain::validate(&leaf, &intermediates, &anchors, &options).unwrap_err();
+        assert!(matches!(
+            &failures[0],
+            PathFailure::ServiceNotInForce { service, status: Some(status), .. }
+                if service == "Example QWAC Root CA" && status == etsi::STATUS_WITHDRAWN
+        ));
+        assert!(failures[0].to_string().contains("has status withdrawn"), "{}", failures[0]);
+
+        // Before the withdrawal the service was granted
+        options.time = parse_date_time("2024-06-01T00:00:00Z").unwrap();
+        let path = chain::validate(&leaf, &intermediates, &anchors, &options).unwrap();
+        assert_eq!(path.anchor_status.as_deref(), Some(etsi::STATUS_GRANTED));
+        assert_eq!(path.to_json()["anchor_status"], etsi::STATUS_GRANTED);
+
+        // Callers reporting the status themselves still get the path
+        options.time = parse_date_time("2025-06-01T00:00:00Z").unwrap();
+        options.require_service_in_force = false;
+        let path = chain::validate(&leaf, &intermediates, &anchors, &options).unwrap();
+        assert_eq!(path.anchor_status.as_deref(), Some(etsi::STATUS_WITHDRAWN));
+    }
+
+    #[test]
+    fn test_verify_command_selects_anchors_at_validation_time() {
+        let mut list = sample_list();
+        let withdrawal = parse_date_time("2025-01-01T00:00:00Z").unwrap();
+        withdraw_service(&mut list, &fixture_certificate("qwac_root"), withdrawal);
+        let leaf = fixture_certificate("qwac_leaf");
+        let intermediates = [fixture_certificate("qwac_issuing_ca")];
+        let verify = |extra: &[&str]| {
+            let mut command = vec!["ca_extractor", "verify", "leaf.pem", "--file", "tl.xml"];
+            command.extend(extra);
+            let Some(crate::cli::Command::Verify(verify_args)) = Args::try_parse_from(command).unwrap().command else {
+                panic!("expected the verify subcommand");
+            };
+            let options = verify_args.path_options();
+            let extractor = verify_args.anchor_extractor("DE", options.time).unwrap();
+            let anchors = extractor.select_anchors(&list, &intermediates);
+            chain::validate(&leaf, &intermediates, &anchors, &options).map(|path| path.anchor_status)
+        };
+
+        // Granted at the validation time, withdrawn since
+        assert_eq!(verify(&["--at", "2024-06-01T00:00:00Z"]).unwrap().as_deref(), Some(etsi::STATUS_GRANTED));
+        let failures = verify(&["--at", "2025-06-01T00:00:00Z"]).unwrap_err();
+        assert!(matches!(&failures[0], PathFailure::IssuerNotFound { .. }));
+        let failures = verify(&["--at", "2025-06-01T00:00:00Z", "--qualification", "withdrawn"]).unwrap_err();
+        assert!(matches!(&failures[0], PathFailure::ServiceNotInForce { .. }));
+    }
+
+    #[test]
+    fn test_verify_path_constraints() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let leaf = fixture_certificate("qwac_leaf");
+        let intermediate = fixture_certificate("qwac_issuing_ca");
+        let at = |time: &str| PathOptions {
+            time: parse_date_time(time).unwrap(),
+            purpose: None,
+            require_service_in_force: true,
+        };
+        let first_failure = |intermediate: &Certificate, options: &PathOptions| {
+            chain::validate(&leaf, std::slice::from_ref(intermediate), &anchors, options).unwrap_err().remove(0)
+        };
+
+        assert!(matches!(first_failure(&intermediate, &at("2050-01-01T00:00:00Z")), PathFailure::Expired { .. }));
+        assert!(matches!(first_failure(&intermediate, &at("2023-01-01T00:00:00Z")), PathFailure::NotYetValid { .. }));
+
+        let options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: chain::key_purpose_oid("timeStamping"),
+            require_service_in_force: true,
+        };
+        assert!(matches!(
+            first_failure(&intermediate, &options),
+            PathFailure::ExtendedKeyUsage { purpose, .. } if purpose == "timeStamping"
+        ));
+
+        // Constraints are read from the parsed fields; the signatures still verify
+        let options = at("2025-06-01T00:00:00Z");
+        let mut not_ca = intermediate.clone();
+        not_ca.ca = Some(false);
+        assert!(matches!(first_failure(&not_ca, &options), PathFailure::NotACa { .. }));
+
+        let mut no_cert_sign = intermediate.clone();
+        no_cert_sign.key_usage = Some(vec!["digitalSignature"]);
+        assert!(matches!(first_failure(&no_cert_sign, &options), PathFailure::MissingKeyCertSign { .. }));
+    }
+
+    #[test]
+    fn test_path_failure_exit_code() {
+        let error = CaExtractorError::PathValidationFailed(vec![PathFailure::NotACa {
+            subject: "CN=Leaf".to_string(),
+        }]);
+        assert_eq!(error.exit_code(), 12);
+        assert_eq!(
+            error.to_json()["error"]["path_failures"][0],
+            "'CN=Leaf' issues certificates but is not a CA (basicConstraints)"
+        );
+    }
+
+    /// Signs `header` and `payload` as a compact ES256 JWS with a fixture key
+    fn sign_compact_jws(header: serde_json::Value, payload: &[u8], key: &str) -> String {
+        use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+
+        let pem = std::fs::read(format!("{}/tests/fixtures/pki/{}.key", env!("CARGO_MANIFEST_DIR"), key)).unwrap();
+        let (_, pkcs8) = x509_parser::pem::parse_x509_pem(&pem).unwrap();
+        let rng = ring::rand::SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8.contents, &rng).unwrap();
+
+        let signing_input = format!(
+            "{}.{}",
+            jws::encode_url(header.to_string().as_bytes()),
+            jws::encode_url(payload)
+        );
+        let signature = key_pair.sign(&rng, signing_input.as_bytes()).unwrap();
+        format!("{}.{}", signing_input, jws::encode_url(signature.as_ref()))
+    }
+
+    fn x5c(names: &[&str]) -> serde_json::Value {
+        use base64::Engine;
+        names
+            .iter()
+            .map(|name| base64::engine::general_purpose::STANDARD.encode(&fixture_certificate(name).der))
+            .collect()
+    }
+
+    fn tls_binding(signer: &str, bound: &Certificate, exp: i64) -> String {
+        let header = serde_json::json!({
+            "alg": "ES256",
+            "cty": tls_binding::CONTENT_TYPE,
+            "x5t#S256": jws::encode_url(&fixture_certificate(signer).sha256_fingerprint()),
+            "x5c": x5c(&[signer, "qwac_issuing_ca"]),
+            "iat": 1735689600,
+            "exp": exp,
+            "sigD": {
+                "mId": tls_binding::SIGD_OBJECT_ID_BY_URI_HASH,
+                "pars": [""],
+                "hashM": "S256",
+                "hashV": [jws::encode_url(&bound.sha256_fingerprint())],
+            },
+        });
+        // Detached payload
+        sign_compact_jws(header, b"", signer)
+    }
+
+    #[test]
+    fn test_two_qwac_binding() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let tls = fixture_certificate("tls_server");
+        let options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: None,
+            require_service_in_force: true,
+        };
+
+        let binding = tls_binding("two_qwac", &tls, 1893456000);
+        let validation = tls_binding::validate(&binding, &tls, &[], &anchors, &options).unwrap();
+        assert_eq!(validation.binding.qwac.subject, "C=DE, O=Example Bank AG, CN=www.example-bank.de");
+        assert_eq!(validation.path.anchor.service.information.names[0].value, "Example QWAC Root CA");
+        assert_eq!(validation.path.chain.len(), 2);
+
+        let other = fixture_certificate("qwac_leaf");
+        let error = tls_binding::validate(&binding, &other, &[], &anchors, &options).unwrap_err();
+        assert!(error.to_string().contains("is not listed in the binding"), "{}", error);
+
+        let expired = tls_binding("two_qwac", &tls, 1735689600);
+        let error = tls_binding::validate(&expired, &tls, &[], &anchors, &options).unwrap_err();
+        assert!(error.to_string().contains("binding expired"), "{}", error);
+
+        // Only QSealC CAs trusted: the 2-QWAC does not chain
+        let seal_anchors = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap().select(&list);
+        let error = tls_binding::validate(&binding, &tls, &[], &seal_anchors, &options).unwrap_err();
+        assert_eq!(error.exit_code(), 12);
+    }
+
+    #[test]
+    fn test_verify_2qwac_command_selects_anchors_at_validation_time() {
+        let mut list = sample_list();
+        let withdrawal = parse_date_time("2025-01-01T00:00:00Z").unwrap();
+        withdraw_service(&mut list, &fixture_certificate("qwac_root"), withdrawal);
+        let tls = fixture_certificate("tls_server");
+        let binding = tls_binding("two_qwac", &tls, 1893456000);
+        let verify = |at: &str| {
+            let command = ["ca_extractor", "verify-2qwac", "binding.jws", "tls.pem", "--file", "tl.xml", "--at", at];
+            let Some(crate::cli::Command::Verify2Qwac(two_qwac_args)) = Args::try_parse_from(command).unwrap().command
+            else {
+                panic!("expected the verify-2qwac subcommand");
+            };
+            let options = two_qwac_args.path_options();
+            let anchors = two_qwac_args.anchor_extractor("DE", options.time).unwrap().select_anchors(&list, &[]);
+            let validation = tls_binding::validate(&binding, &tls, &[], &anchors, &options);
+            validation.map(|validation| validation.path.anchor_status)
+        };
+
+        assert_eq!(verify("2024-06-01T00:00:00Z").unwrap().as_deref(), Some(etsi::STATUS_GRANTED));
+        assert_eq!(verify("2025-06-01T00:00:00Z").unwrap_err().exit_code(), 12);
+    }
+
+    #[test]
+    fn test_two_qwac_binding_signature() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let tls = fixture_certificate("tls_server");
+        let options = PathOptions::default();
+
+        // Signature replaced by one from another key
+        let binding = tls_binding("two_qwac", &tls, 1893456000);
+        let forged = tls_binding("qwac_leaf", &tls, 1893456000);
+        let tampered = format!(
+            "{}.{}",
+            binding.rsplit_once('.').unwrap().0,
+            forged.rsplit_once('.').unwrap().1
+        );
+        let error = tls_binding::validate(&tampered, &tls, &[], &anchors, &options).unwrap_err();
+        assert!(
+            matches!(error, CaExtractorError::SignatureInvalid(ref message) if message.contains("does not verify")),
+            "{}",
+            error
+        );
+
+        // A QSealC cannot sign TLS certificate bindings
+        let sealed = tls_binding("qseal_leaf", &tls, 1893456000);
+        let error = tls_binding::validate(&sealed, &tls, &[], &anchors, &options).unwrap_err();
+        assert!(error.to_string().contains("not a qualified website authentication certificate"), "{}", error);
+        assert_eq!(error.exit_code(), 13);
+    }
+
+    fn fixture_bytes(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/tests/fixtures/pki/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+    }
+
+    #[test]
+    fn test_qualified_timestamp() {
+        let list = sample_list();
+        let token = TimeStampToken::parse(&fixture_bytes("document.tsr")).unwrap();
+        assert_eq!(token.info.policy, "0.4.0.2023.1.1");
+        assert_eq!(token.signed_data.certificates.len(), 1);
+        assert!(token.info.nonce.is_some());
+
+        let document = fixture_bytes("document.txt");
+        let validation = timestamp::validate(token.clone(), Some(&document), &[], &list).unwrap();
+        assert!(validation.imprint_verified);
+        assert_eq!(validation.tsa.subject, "C=DE, O=Beispiel Signatur AG, CN=Example QTST Unit 1");
+        assert_eq!(validation.information.service_type, etsi::SVCTYPE_TSA_QTST);
+        assert_eq!(validation.to_json()["tsp"], "Beispiel Signatur AG");
+
+        let error = timestamp::validate(token, Some(b"another document"), &[], &list).unwrap_err();
+        assert!(error.to_string().contains("message imprint does not match"), "{}", error);
+        assert_eq!(error.exit_code(), 13);
+    }
+
+    #[test]
+    fn test_tampered_timestamp() {
+        let list = sample_list();
+        let mut tsr = fixture_bytes("document.tsr");
+        let imprint = ring::digest::digest(&ring::digest::SHA256, &fixture_bytes("document.txt"));
+        let offset = tsr.windows(32).position(|window| window == imprint.as_ref()).unwrap();
+        tsr[offset] ^= 1;
+
+        let token = TimeStampToken::parse(&tsr).unwrap();
+        let error = timestamp::validate(token, None, &[], &list).unwrap_err();
+        assert!(error.to_string().contains("message-digest attribute does not match"), "{}", error);
+    }
+
+    #[test]
+    fn test_timestamp_service_status() {
+        let token = TimeStampToken::parse(&fixture_bytes("document.tsr")).unwrap();
+        fn qtst_service(list: &mut TrustedList) -> &mut ServiceInformation {
+            list.providers
+                .iter_mut()
+                .flat_map(|provider| provider.services.iter_mut())
+                .find(|service| service.information.service_type == etsi::SVCTYPE_TSA_QTST)
+                .map(|service| &mut service.information)
+                .unwrap()
+        }
+
+        let mut withdrawn = sample_list();
+        qtst_service(&mut withdrawn).status = etsi::STATUS_WITHDRAWN.to_string();
+        let error = timestamp::validate(token.clone(), None, &[], &withdrawn).unwrap_err();
+        assert!(error.to_string().contains("has status 'withdrawn'"), "{}", error);
+        assert_eq!(error.exit_code(), 14);
+        assert_eq!(error.kind(), "not_qualified");
+
+        let mut later = sample_list();
+        qtst_service(&mut later).status_starting_time = Some(token.info.gen_time + chrono::Duration::days(1));
+        let error = timestamp::validate(token.clone(), None, &[], &later).unwrap_err();
+        assert!(error.to_string().contains("was not listed yet"), "{}", error);
+
+        let mut unlisted = sample_list();
+        qtst_service(&mut unlisted).digital_ids.clear();
+        let error = timestamp::validate(token, None, &[], &unlisted).unwrap_err();
+        assert!(error.to_string().contains("not listed under any service"), "{}", error);
+    }
+
+    #[test]
+    fn test_timestamp_unit_issued_by_a_listed_non_ca() {
+        // The listed TSU certificate has cA=false, so it cannot vouch for another unit
+        let list = sample_list();
+        let token = TimeStampToken::parse(&fixture_bytes("tsu_issued_tsu.tsr")).unwrap();
+        let tsa = fixture_certificate("tsu_issued_tsu");
+        assert!(tsa.is_signed_by(&fixture_certificate("qtst_tsu")));
+
+        let document = fixture_bytes("document.txt");
+        let error = timestamp::validate(token, Some(&document), &[], &list).unwrap_err();
+        assert!(error.to_string().contains("not listed under any service"), "{}", error);
+    }
+
+    #[test]
+    fn test_cades_detached_signature() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QSealC, ServiceType::QESig], "DE")
+            .unwrap()
+            .select(&list);
+        let signature = fixture_bytes("document.p7s");
+        let document = fixture_bytes("document.txt");
+
+        let validations =
+            cades::validate(&signature, &document, &[], |_| anchors.clone(), &list, Utc::now()).unwrap();
+        let [validation] = validations.as_slice() else {
+            panic!("one signer expected");
+        };
+        assert_eq!(validation.certificate.subject, "C=DE, O=Example Bank AG, CN=Example Bank Seal");
+        assert!(validation.signing_time.is_some());
+        assert_eq!(validation.path.anchor.service.information.names[0].value, "Example QSealC Root CA");
+        assert!(validation.qualification.qualified);
+        assert_eq!(validation.qualification.qc_type, Some(QcType::ESeal));
+        assert!(validation.qualified_at_signing_time());
+
+        let error =
+            cades::validate(&signature, b"tampered", &[], |_| anchors.clone(), &list, Utc::now()).unwrap_err();
+        assert!(error.to_string().contains("message-digest attribute does not match"), "{}", error);
+        assert_eq!(error.exit_code(), 13);
+
+        let qwac_anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let qwac_anchors_at = |_| qwac_anchors.clone();
+        let error = cades::validate(&signature, &document, &[], qwac_anchors_at, &list, Utc::now()).unwrap_err();
+        assert_eq!(error.exit_code(), 12);
+    }
+
+    #[test]
+    fn test_cades_service_withdrawn_before_signing() {
+        let mut list = sample_list();
+        let service = list
+            .providers
+            .iter_mut()
+            .flat_map(|provider| provider.services.iter_mut())
+            .find(|service| service.information.names[0].value == "Example QSealC Root CA")
+            .unwrap();
+        service.history.insert(0, service.information.clone());
+        service.information.status = etsi::STATUS_WITHDRAWN.to_string();
+        service.information.status_starting_time = parse_date_time("2025-01-01T00:00:00Z");
+
+        let anchors = CertificateExtractor::new(&[ServiceType::QSealC], "DE")
+            .unwrap()
+            .with_qualification(QualificationFilter::Withdrawn)
+            .select(&list);
+        let signature = fixture_bytes("document.p7s");
+        let document = fixture_bytes("document.txt");
+        let validations =
+            cades::validate(&signature, &document, &[], |_| anchors.clone(), &list, Utc::now()).unwrap();
+        assert!(validations[0].qualification.qualified);
+        assert_eq!(validations[0].service_status.as_deref(), Some(etsi::STATUS_WITHDRAWN));
+        assert!(!validations[0].qualified_at_signing_time());
+        assert_eq!(validations[0].to_json()["qualified_at_signing_time"], false);
+    }
+
+    #[test]
+    fn test_cades_anchors_selected_at_signing_time() {
+        let signature = fixture_bytes("document.p7s");
+        let document = fixture_bytes("document.txt");
+        let signing_time = cms::SignedData::parse(&signature).unwrap().signers[0].signing_time().unwrap();
+
+        // Withdrawn right after the seal was made
+        let mut list = sample_list();
+        withdraw_service(&mut list, &fixture_certificate("qseal_root"), signing_time + chrono::Duration::seconds(1));
+
+        let extractor = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap();
+        assert!(extractor.select(&list).is_empty());
+        let anchors_at = |time| extractor.clone().with_time(time).select(&list);
+        let validations = cades::validate(&signature, &document, &[], anchors_at, &list, Utc::now()).unwrap();
+        assert_eq!(validations[0].service_status.as_deref(), Some(etsi::STATUS_GRANTED));
+        assert!(validations[0].qualified_at_signing_time());
+    }
+
+    fn jades_header(signer: &str) -> serde_json::Value {
+        serde_json::json!({
+            "alg": "ES256",
+            "crit": ["sigT"],
+            "sigT": "2025-06-01T12:00:00Z",
+            "x5t#S256": jws::encode_url(&fixture_certificate(signer).sha256_fingerprint()),
+            "x5c": x5c(&[signer]),
+        })
+    }
+
+    #[test]
+    fn test_jades_baseline_b() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QSealC, ServiceType::QESig], "DE")
+            .unwrap()
+            .select(&list);
+        let payload = br#"{"amount":100,"currency":"EUR"}"#;
+
+        let signature = sign_compact_jws(jades_header("qseal_leaf"), payload, "qseal_leaf");
+        let validation = jades::validate(&signature, None, &[], |_| anchors.clone(), &list).unwrap();
+        assert_eq!(validation.jws.payload, payload);
+        assert_eq!(validation.signer.signing_time, parse_date_time("2025-06-01T12:00:00Z"));
+        assert_eq!(validation.signer.qualification.qc_type, Some(QcType::ESeal));
+        assert!(validation.signer.qualified_at_signing_time());
+
+        // Detached: the payload part is empty and the payload given separately
+        let (header, rest) = signature.split_once('.').unwrap();
+        let detached = format!("{}..{}", header, rest.split_once('.').unwrap().1);
+        assert!(jades::validate(&detached, Some(payload), &[], |_| anchors.clone(), &list).is_ok());
+        let error = jades::validate(&detached, Some(b"{}"), &[], |_| anchors.clone(), &list).unwrap_err();
+        assert!(error.to_string().contains("does not verify"), "{}", error);
+
+        // The QSealC does not chain to a QWAC CA
+        let qwac_anchors = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        let error = jades::validate(&signature, None, &[], |_| qwac_anchors.clone(), &list).unwrap_err();
+        assert_eq!(error.exit_code(), 12);
+    }
+
+    #[test]
+    fn test_jades_anchors_selected_at_sigt() {
+        let mut list = sample_list();
+        let withdrawal = parse_date_time("2025-07-01T00:00:00Z").unwrap();
+        withdraw_service(&mut list, &fixture_certificate("qseal_root"), withdrawal);
+        let extractor = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap();
+        assert!(extractor.select(&list).is_empty());
+
+        // Sealed on 2025-06-01, while the CA/QC service was granted
+        let signature = sign_compact_jws(jades_header("qseal_leaf"), b"payload", "qseal_leaf");
+        let anchors_at = |time| extractor.clone().with_time(time).select(&list);
+        let validation = jades::validate(&signature, None, &[], anchors_at, &list).unwrap();
+        assert_eq!(validation.signer.service_status.as_deref(), Some(etsi::STATUS_GRANTED));
+        assert!(validation.signer.qualified_at_signing_time());
+    }
+
+    #[test]
+    fn test_jades_header_requirements() {
+        let list = sample_list();
+        let anchors = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap().select(&list);
+        let rejected = |header: serde_json::Value| {
+            let signature = sign_compact_jws(header, b"payload", "qseal_leaf");
+            jades::validate(&signature, None, &[], |_| anchors.clone(), &list).unwrap_err().to_string()
+        };
+
+        let mut header = jades_header("qseal_leaf");
+        header.as_object_mut().unwrap().remove("sigT");
+        assert!(rejected(header).contains("sigT header is missing"));
+
+        let mut header = jades_header("qseal_leaf");
+        header["crit"] = serde_json::json!([]);
+        assert!(rejected(header).contains("sigT must be listed in the crit header"));
+
+        let mut header = jades_header("qseal_leaf");
+        header["crit"] = serde_json::json!(["sigT", "sigD"]);
+        assert!(rejected(header).contains("unsupported critical header parameter 'sigD'"));
+
+        let mut header = jades_header("qseal_leaf");
+        header.as_object_mut().unwrap().remove("x5t#S256");
+        assert!(rejected(header).contains("x5t#S256 header is missing"));
+
+        let mut header = jades_header("qseal_leaf");
+        header["x5t#S256"] = jws::encode_url(&fixture_certificate("qseal_root").sha256_fingerprint()).into();
+        assert!(rejected(header).contains("x5t#S256 does not match"));
+    }
+
+    /// Serves the fixture CRLs over HTTP until dropped
+    struct CrlServer {
+        url: String,
+        server: std::sync::Arc<tiny_http::Server>,
+    }
+
+    impl CrlServer {
+        fn start() -> Self {
+            let server = std::sync::Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+            let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+            let requests = std::sync::Arc::clone(&server);
+            std::thread::spawn(move || {
+                for request in requests.incoming_requests() {
+                    let name = request.url().trim_start_matches('/').to_string();
+                    let response = match name.strip_suffix(".crl").filter(|stem| !stem.contains('/')) {
+                        Some(_) => tiny_http::Response::from_data(fixture_bytes(&name)),
+                        None => tiny_http::Response::from_data(Vec::new()).with_status_code(404),
+                    };
+                    let _ = request.respond(response);
+                }
+            });
+            CrlServer { url, server }
+        }
+    }
+
+    impl Drop for CrlServer {
+        fn drop(&mut self) {
+            self.server.unblock();
+        }
+    }
+
+    fn service_listing<'a>(list: &'a mut TrustedList, certificate: &Certificate) -> &'a mut ServiceInformation {
+        list.providers
+            .iter_mut()
+            .flat_map(|provider| provider.services.iter_mut())
+            .map(|service| &mut service.information)
+            .find(|information| information.digital_ids.contains(&DigitalId::Certificate(certificate.der.clone())))
+            .unwrap()
+    }
+
+    /// Withdraws the service listing `certificate` at `since`, keeping its
+    /// granted entry in the status history
+    fn withdraw_service(list: &mut TrustedList, certificate: &Certificate, since: chrono::DateTime<Utc>) {
+        let information = service_listing(list, certificate);
+        let granted = information.clone();
+        information.status = etsi::STATUS_WITHDRAWN.to_string();
+        information.status_starting_time = Some(since);
+        let service = list
+            .providers
+            .iter_mut()
+            .flat_map(|provider| provider.services.iter_mut())
+            .find(|service| service.information.digital_ids.contains(&DigitalId::Certificate(certificate.der.clone())))
+            .unwrap();
+        service.history.insert(0, granted);
+    }
+
+    #[test]
+    fn test_crl_distribution_points() {
+        let revoked_ca = fixture_certificate("revoked_ca");
+        assert_eq!(revoked_ca.crl_distribution_points, vec!["http://crl.invalid/qseal_root.crl"]);
+        assert!(fixture_certificate("qwac_issuing_ca").crl_distribution_points.is_empty());
+    }
+
+    #[test]
+    fn test_revocation_of_extracted_cas() {
+        let server = CrlServer::start();
+        let cache_dir = std::env::temp_dir().join(format!("ca_extractor-test-crls-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        // Issuing CAs listed next to their roots; the roots' services publish their CRLs
+        let (revoked_ca, issuing_ca) = (fixture_certificate("revoked_ca"), fixture_certificate("qwac_issuing_ca"));
+        let mut list = sample_list();
+        let qseal = service_listing(&mut list, &fixture_certificate("qseal_root"));
+        qseal.digital_ids.push(DigitalId::Certificate(revoked_ca.der.clone()));
+        qseal.service_supply_points = vec![format!("{}/qseal_root.crl", server.url)];
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        qwac.digital_ids.push(DigitalId::Certificate(issuing_ca.der.clone()));
+        qwac.service_supply_points = vec![format!("{}/qwac_root.crl", server.url)];
+
+        let status_of = |certificates: &[crate::extractor::ExtractedCertificate], certificate: &Certificate| {
+            certificates.iter().find(|c| c.der == certificate.der.as_slice()).unwrap().revocation.clone()
+        };
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE").unwrap();
+        let cache = CrlCache::new(&cache_dir);
+        let mut certificates = extractor.select(&list);
+        revocation::check_extracted(&mut certificates, &list, Some(&cache), None, Utc::now());
+
+        // The unreachable distribution point is outweighed by the supply point
+        let Some(RevocationStatus::Revoked { reason, .. }) = status_of(&certificates, &revoked_ca) else {
+            panic!("revoked_ca is not revoked");
+        };
+        assert_eq!(reason.as_deref(), Some("KeyCompromise"));
+        assert_eq!(status_of(&certificates, &issuing_ca), Some(RevocationStatus::Good));
+        assert_eq!(status_of(&certificates, &fixture_certificate("qwac_root")), None);
+        let annotated = certificates.iter().find(|c| c.der == revoked_ca.der.as_slice()).unwrap().annotated_pem();
+        assert!(annotated.contains("Revocation: revoked on"), "{}", annotated);
+
+        // Cached CRLs are used until their nextUpdate, without the server
+        drop(server);
+        let mut certificates = extractor.select(&list);
+        revocation::check_extracted(&mut certificates, &list, Some(&cache), None, Utc::now());
+        assert!(status_of(&certificates, &revoked_ca).unwrap().is_revoked());
+        assert_eq!(status_of(&certificates, &issuing_ca), Some(RevocationStatus::Good));
+
+        let empty = CrlCache::new(cache_dir.join("empty"));
+        let mut certificates = extractor.select(&list);
+        revocation::check_extracted(&mut certificates, &list, Some(&empty), None, Utc::now());
+        let Some(RevocationStatus::Unknown(reason)) = status_of(&certificates, &revoked_ca) else {
+            panic!("revoked_ca has a status without CRLs");
+        };
+        assert!(reason.contains("http://crl.invalid/qseal_root.crl"), "{}", reason);
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_crl_cache_directory_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let server = CrlServer::start();
+        let cache_dir = std::env::temp_dir().join(format!("ca_extractor-test-crls-{}-private", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let cache = CrlCache::new(cache_dir.join("crls"));
+        let url = format!("{}/qwac_root.crl", server.url);
+
+        cache.fetch(&url, Utc::now()).unwrap();
+        let mode = std::fs::metadata(cache_dir.join("crls")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // CRLs planted in a directory others can write to are not served
+        std::fs::set_permissions(cache_dir.join("crls"), std::fs::Permissions::from_mode(0o777)).unwrap();
+        let error = cache.fetch(&url, Utc::now()).unwrap_err();
+        assert!(error.to_string().contains("writable by other users"), "{}", error);
+        let issuing_ca = fixture_certificate("qwac_issuing_ca");
+        let status = revocation::check(&issuing_ca, &[fixture_certificate("qwac_root")], &[url], &cache, Utc::now());
+        assert!(matches!(status, RevocationStatus::Unknown(_)), "{:?}", status);
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn test_crl_of_another_issuer() {
+        let server = CrlServer::start();
+        let cache_dir = std::env::temp_dir().join(format!("ca_extractor-test-crls-{}-other", std::process::id()));
+        let cache = CrlCache::new(&cache_dir);
+        let issuing_ca = fixture_certificate("qwac_issuing_ca");
+        let issuers = [fixture_certificate("qwac_root"), fixture_certificate("qseal_root")];
+        let crl = |name: &str| vec![format!("{}/{}.crl", server.url, name)];
+
+        let status = revocation::check(&issuing_ca, &issuers, &crl("qseal_root"), &cache, Utc::now());
+        let RevocationStatus::Unknown(reason) = status else {
+            panic!("a CRL of another issuer was accepted");
+        };
+        assert!(reason.contains("not by 'C=DE, O=Example Trust GmbH, CN=Example QWAC Root CA'"), "{}", reason);
+
+        // The CRL must verify with a listed issuer's key
+        let status = revocation::check(&issuing_ca, &issuers[1..], &crl("qwac_root"), &cache, Utc::now());
+        assert!(
+            matches!(&status, RevocationStatus::Unknown(reason) if reason.contains("does not verify")),
+            "{}",
+            status
+        );
+
+        let status = revocation::check(&issuing_ca, &issuers, &[], &cache, Utc::now());
+        assert!(matches!(status, RevocationStatus::Unknown(_)));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    /// How the OCSP responder stand-in answers
+    #[derive(Clone)]
+    struct ResponderSetup {
+        /// Fixture whose key signs the responses and whose name is the responder ID
+        signer: &'static str,
+        /// Fixture certificates carried in the responses
+        certs: Vec<&'static str>,
+        /// Serials answered as revoked for key compromise; all others are good
+        revoked: Vec<Vec<u8>>,
+        echo_nonce: bool,
+        /// Nonce echoed instead of the request's
+        nonce: Option<Vec<u8>>,
+    }
+
+    impl ResponderSetup {
+        fn signed_by(signer: &'static str) -> Self {
+            ResponderSetup {
+                signer,
+                certs: Vec::new(),
+                revoked: Vec::new(),
+                echo_nonce: true,
+                nonce: None,
+            }
+        }
+    }
+
+    /// Answers OCSP requests as configured until dropped
+    struct OcspResponder {
+        url: String,
+        server: std::sync::Arc<tiny_http::Server>,
+        requests: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl OcspResponder {
+        fn start(setup: ResponderSetup) -> Self {
+            let server = std::sync::Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+            let url = format!("http://{}/ocsp", server.server_addr().to_ip().unwrap());
+            let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let (incoming, counter) = (std::sync::Arc::clone(&server), std::sync::Arc::clone(&requests));
+            std::thread::spawn(move || {
+                for mut request in incoming.incoming_requests() {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let mut body = Vec::new();
+                    std::io::Read::read_to_end(request.as_reader(), &mut body).unwrap();
+                    let _ = request.respond(tiny_http::Response::from_data(ocsp_response(&body, &setup)));
+                }
+            });
+            OcspResponder { url, server, requests }
+        }
+
+        fn requests(&self) -> usize {
+            self.requests.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl Drop for OcspResponder {
+        fn drop(&mut self) {
+            self.server.unblock();
+        }
+    }
+
+    /// A signed `OCSPResponse` to the DER `OCSPRequest` `request`
+    fn ocsp_response(request: &[u8], setup: &ResponderSetup) -> Vec<u8> {
+        use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+        let sequence = |parts: &[&[u8]]| cms::encode(0x30, &parts.concat());
+        let time = |time: chrono::DateTime<Utc>| cms::encode(0x18, time.format("%Y%m%d%H%M%SZ").to_string().as_bytes());
+        const NONCE_OID: &[u8] = &[0x06, 0x09, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x02];
+        const BASIC_OID: &[u8] = &[0x06, 0x09, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+        const ECDSA_SHA256: &[u8] = &[0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+        // OCSPRequest { TBSRequest { requestList { Request { CertID } }, [2] extensions } }
+        let tbs_request = Element::single(request).unwrap().children().unwrap().remove(0).children().unwrap();
+        let cert_id = tbs_request[0].children().unwrap()[0].children().unwrap().remove(0);
+        let serial = cert_id.children().unwrap()[3].any.data.to_vec();
+        let nonce = tbs_request.get(1).map(|extensions| {
+            let extension = extensions.explicit(2).unwrap().children().unwrap().remove(0);
+            cms::octets(&extension.children().unwrap()[1]).unwrap()
+        });
+
+        let now = Utc::now() - chrono::Duration::minutes(1);
+        let status = if setup.revoked.contains(&serial) {
+            let reason = cms::encode(0xa0, &[0x0a, 0x01, 0x01]);
+            cms::encode(0xa1, &[time(now - chrono::Duration::days(1)), reason].concat())
+        } else {
+            cms::encode(0x80, &[])
+        };
+        let next_update = cms::encode(0xa0, &time(now + chrono::Duration::days(7)));
+        let single = sequence(&[cert_id.raw, &status, &time(now), &next_update]);
+
+        let signer = fixture_certificate(setup.signer);
+        let mut tbs = [cms::encode(0xa1, &signer.subject_raw), time(now), sequence(&[&single])].concat();
+        if let Some(nonce) = setup.nonce.clone().or(nonce).filter(|_| setup.echo_nonce) {
+            let extension = sequence(&[NONCE_OID, &cms::encode(0x04, &nonce)]);
+            tbs.extend(cms::encode(0xa1, &sequence(&[&extension])));
+        }
+        let tbs = sequence(&[&tbs]);
+
+        let pem = fixture_bytes(&format!("{}.key", setup.signer));
+        let (_, pkcs8) = x509_parser::pem::parse_x509_pem(&pem).unwrap();
+        let rng = ring::rand::SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8.contents, &rng).unwrap();
+        let signature = [&[0u8][..], key_pair.sign(&rng, &tbs).unwrap().as_ref()].concat();
+
+        let mut basic = [tbs, ECDSA_SHA256.to_vec(), cms::encode(0x03, &signature)].concat();
+        if !setup.certs.is_empty() {
+            let certs: Vec<Vec<u8>> = setup.certs.iter().map(|name| fixture_certificate(name).der).collect();
+            basic.extend(cms::encode(0xa0, &cms::encode(0x30, &certs.concat())));
+        }
+        let basic = cms::encode(0x30, &basic);
+        let bytes = sequence(&[BASIC_OID, &cms::encode(0x04, &basic)]);
+        sequence(&[&[0x0a, 0x01, 0x00], &cms::encode(0xa0, &bytes)])
+    }
+
+    #[test]
+    fn test_ocsp_responders_from_authority_information_access() {
+        let issuing_ca = fixture_certificate("qseal_issuing_ca");
+        assert_eq!(issuing_ca.ocsp_responders, vec!["http://ocsp.invalid/qseal_root"]);
+        assert!(fixture_certificate("revoked_ca").ocsp_responders.is_empty());
+    }
+
+    #[test]
+    fn test_ocsp_status() {
+        let client = OcspClient::new();
+        let (qwac_root, issuing_ca) = (fixture_certificate("qwac_root"), fixture_certificate("qwac_issuing_ca"));
+        let status_from = |setup: ResponderSetup| {
+            let responder = OcspResponder::start(setup);
+            client.check(&issuing_ca, &qwac_root, std::slice::from_ref(&responder.url), Utc::now())
+        };
+
+        assert_eq!(status_from(ResponderSetup::signed_by("qwac_root")), RevocationStatus::Good);
+
+        // Delegated responder certified by the issuer for OCSPSigning
+        let delegated = ResponderSetup {
+            certs: vec!["ocsp_responder"],
+            ..ResponderSetup::signed_by("ocsp_responder")
+        };
+        assert_eq!(status_from(delegated.clone()), RevocationStatus::Good);
+
+        let unknown = |status: RevocationStatus| match status {
+            RevocationStatus::Unknown(reason) => reason,
+            status => panic!("expected an unknown status, got {}", status),
+        };
+        let missing_certificate = ResponderSetup { certs: Vec::new(), ..delegated };
+        assert!(unknown(status_from(missing_certificate)).contains("signed neither by the issuer"));
+        let not_authorized = ResponderSetup {
+            certs: vec!["qwac_issuing_ca"],
+            ..ResponderSetup::signed_by("qwac_issuing_ca")
+        };
+        assert!(unknown(status_from(not_authorized)).contains("does not allow OCSPSigning"));
+        let other_issuer = ResponderSetup {
+            certs: vec!["qseal_root"],
+            ..ResponderSetup::signed_by("qseal_root")
+        };
+        assert!(unknown(status_from(other_issuer)).contains("is not certified by"));
+
+        // The AIA responder is unreachable; the service's supply point answers
+        let qseal_issuing_ca = fixture_certificate("qseal_issuing_ca");
+        let responder = OcspResponder::start(ResponderSetup {
+            revoked: vec![qseal_issuing_ca.serial.clone()],
+            ..ResponderSetup::signed_by("qseal_root")
+        });
+        let mut list = sample_list();
+        let qseal = service_listing(&mut list, &fixture_certificate("qseal_root"));
+        qseal.digital_ids.push(DigitalId::Certificate(qseal_issuing_ca.der.clone()));
+        qseal.service_supply_points = vec![responder.url.clone()];
+        let mut certificates = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap().select(&list);
+        revocation::check_extracted(&mut certificates, &list, None, Some(&client), Utc::now());
+        let status = certificates
+            .iter()
+            .find(|c| c.der == qseal_issuing_ca.der.as_slice())
+            .and_then(|c| c.revocation.clone());
+        let Some(RevocationStatus::Revoked { reason, .. }) = status else {
+            panic!("qseal_issuing_ca is not revoked");
+        };
+        assert_eq!(reason.as_deref(), Some("KeyCompromise"));
+    }
+
+    #[test]
+    fn test_ocsp_nonce_and_cache() {
+        let (qwac_root, issuing_ca) = (fixture_certificate("qwac_root"), fixture_certificate("qwac_issuing_ca"));
+        let status_of = |client: &OcspClient, responder: &OcspResponder| {
+            client.check(&issuing_ca, &qwac_root, std::slice::from_ref(&responder.url), Utc::now())
+        };
+        let send = OcspClient::new();
+        let require = OcspClient::new().with_nonce(OcspNonce::Require);
+
+        let silent = OcspResponder::start(ResponderSetup {
+            echo_nonce: false,
+            ..ResponderSetup::signed_by("qwac_root")
+        });
+        assert_eq!(status_of(&send, &silent), RevocationStatus::Good);
+        assert_eq!(status_of(&OcspClient::new().with_nonce(OcspNonce::Off), &silent), RevocationStatus::Good);
+        let status = status_of(&require, &silent);
+        assert!(matches!(&status, RevocationStatus::Unknown(reason) if reason.contains("does not echo")), "{}", status);
+
+        let replaying = OcspResponder::start(ResponderSetup {
+            nonce: Some(vec![0x04, 0x02, 0x00, 0x01]),
+            ..ResponderSetup::signed_by("qwac_root")
+        });
+        let status = status_of(&send, &replaying);
+        assert!(
+            matches!(&status, RevocationStatus::Unknown(reason) if reason.contains("does not match")),
+            "{}",
+            status
+        );
+
+        // Cached responses are reused until their nextUpdate, except when a nonce is required
+        let cache_dir = std::env::temp_dir().join(format!("ca_extractor-test-ocsp-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let responder = OcspResponder::start(ResponderSetup::signed_by("qwac_root"));
+        let cached = OcspClient::new().with_cache(&cache_dir);
+        assert_eq!(status_of(&cached, &responder), RevocationStatus::Good);
+        assert_eq!(status_of(&cached, &responder), RevocationStatus::Good);
+        assert_eq!(responder.requests(), 1);
+        let cached_require = OcspClient::new().with_nonce(OcspNonce::Require).with_cache(&cache_dir);
+        assert_eq!(status_of(&cached_require, &responder), RevocationStatus::Good);
+        assert_eq!(responder.requests(), 2);
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn test_list_endpoints() {
+        let mut list = sample_list();
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        assert_eq!(qwac.service_supply_points.len(), 2);
+        let langs: Vec<&str> = qwac.service_definition_uris.iter().map(|uri| uri.lang.as_str()).collect();
+        assert_eq!(langs, vec!["en", "de"]);
+        qwac.service_supply_points.push("http://OCSP.trust.example.com:8080/qwac-root".to_string());
+        qwac.service_supply_points.push("http://crl.trust.example.com/qwac-root.crl".to_string());
+
+        // CRL distribution points and OCSP responders of the extracted certificates count too
+        let qseal = service_listing(&mut list, &fixture_certificate("qseal_root"));
+        qseal.digital_ids.push(DigitalId::Certificate(fixture_certificate("revoked_ca").der));
+        qseal.digital_ids.push(DigitalId::Certificate(fixture_certificate("qseal_issuing_ca").der));
+
+        let selected = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE")
+            .unwrap()
+            .select(&list);
+        let found = endpoints::collect(&selected);
+        let listed: Vec<(&str, EndpointKind)> =
+            found.iter().map(|endpoint| (endpoint.url.as_str(), endpoint.kind)).collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("http://crl.trust.example.com/qwac-root.crl", EndpointKind::SupplyPoint),
+                ("http://ocsp.trust.example.com/qwac-root", EndpointKind::SupplyPoint),
+                ("http://OCSP.trust.example.com:8080/qwac-root", EndpointKind::SupplyPoint),
+                ("https://trust.example.com/repository/qwac-cps.pdf", EndpointKind::ServiceDefinition),
+                ("https://trust.example.com/repository/qwac-cps-de.pdf", EndpointKind::ServiceDefinition),
+                ("http://crl.invalid/qseal_root.crl", EndpointKind::CrlDistributionPoint),
+                ("http://ocsp.invalid/qseal_root", EndpointKind::OcspResponder),
+            ]
+        );
+        assert_eq!(found[5].to_json()["service"], "Example QSealC Root CA");
+        assert_eq!(
+            endpoints::hosts(&found),
+            vec![
+                "crl.invalid",
+                "crl.trust.example.com",
+                "ocsp.invalid",
+                "ocsp.trust.example.com",
+                "ocsp.trust.example.com:8080",
+                "trust.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tsp_information() {
+        let list = sample_list();
+        let provider = &list.providers[1];
+        assert_eq!(
+            provider.registration_identifiers().collect::<Vec<_>>(),
+            vec!["VATDE-987654321", "NTRDE-HRB654321"]
+        );
+        assert_eq!(provider.electronic_addresses[0].value, "mailto:info@signatur.example.de");
+        assert_eq!(provider.information_uris[0].value, "https://signatur.example.de/repository");
+        assert_eq!(provider.to_json()["postal_addresses"].as_array().unwrap().len(), 2);
+
+        // Preferred languages fall back to the first entry; `de` matches `de-DE`
+        let langs = |langs: &[&str]| langs.iter().map(|lang| lang.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            model::preferred(&provider.trade_names, &langs(&["fr", "de"])).unwrap().value,
+            "Beispiel Signatur"
+        );
+        assert_eq!(model::preferred(&provider.trade_names, &langs(&["fr"])).unwrap().value, "Example Signature");
+        let address = provider.postal_address(&langs(&["en"])).unwrap();
+        assert_eq!(address.to_string(), "Signature Road 3, 20095 Hamburg, Hamburg, DE");
+        assert_eq!(provider.postal_address(&langs(&["de", "en"])).unwrap().street_address, "Signaturweg 3");
+
+        assert!(provider.matches_name("beispiel signatur"));
+        assert!(provider.matches_name("EXAMPLE SIGNATURE"));
+        assert!(!provider.matches_name("Example Trust"));
+        assert!(provider.has_identifier("ntrde"));
+        assert!(provider.has_identifier("VATDE-987654321"));
+        assert!(!list.providers[0].has_identifier("NTRDE"));
+        assert!(list.providers[0].has_identifier("VATDE-123456789"));
+    }
+
+    #[test]
+    fn test_language_preference() {
+        let xml = SAMPLE_TL.replace(
+            r#"<tsl:Name xml:lang="en">Example QWAC Root CA</tsl:Name>"#,
+            concat!(
+                r#"<tsl:Name xml:lang="en">Example QWAC Root CA</tsl:Name>"#,
+                r#"<tsl:Name xml:lang="de-DE">Beispiel QWAC-Wurzel</tsl:Name>"#,
+            ),
+        );
+        let langs = |langs: &[&str]| langs.iter().map(|lang| lang.to_string()).collect::<Vec<_>>();
+        let parse = |preference: &[&str]| {
+            let mut list = TrustedList::parse(&xml, &ParseLimits::default()).unwrap();
+            list.prefer_languages(&langs(preference));
+            list
+        };
+
+        let list = parse(&["fr", "de"]);
+        let service = &list.providers[0].services[0];
+        assert_eq!(service.information.name(), Some("Beispiel QWAC-Wurzel"));
+        assert_eq!(service.history[0].name(), Some("Beispiel QWAC-Wurzel"));
+        assert_eq!(list.providers[0].name(), Some("Example Trust GmbH"));
+        assert_eq!(list.providers[1].trade_names[0].value, "Beispiel Signatur");
+        assert_eq!(list.providers[1].postal_addresses[0].street_address, "Signaturweg 3");
+        let selected = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().select(&list);
+        assert!(selected[0].annotated_pem().contains("Service: Beispiel QWAC-Wurzel\n"));
+        // JSON keeps every variant
+        assert_eq!(selected[0].to_json()["service"]["names"].as_array().unwrap().len(), 2);
+
+        // Without an available preferred language the document order is kept
+        for preference in [&[][..], &["fr"][..]] {
+            let list = parse(preference);
+            assert_eq!(list.providers[0].services[0].information.name(), Some("Example QWAC Root CA"));
+            assert_eq!(list.providers[1].postal_addresses[0].street_address, "Signaturweg 3");
+        }
+        assert_eq!(parse(&["en"]).providers[1].postal_addresses[0].street_address, "Signature Road 3");
+    }
+
+    #[test]
+    fn test_service_identity_without_certificate() {
+        let (leaf, issuing_ca) = (fixture_certificate("qwac_leaf"), fixture_certificate("qwac_issuing_ca"));
+        assert!(issuing_ca.has_subject_name("cn=Example QWAC Issuing CA, O=Example Trust GmbH,C=DE"));
+        assert!(!issuing_ca.has_subject_name("CN=Example QWAC Issuing CA,O=Example Trust GmbH"));
+
+        // The QWAC service identifies its issuing CA by key identifier and name only
+        let mut list = sample_list();
+        let ski = issuing_ca.key_identifier();
+        assert_eq!(issuing_ca.subject_key_id.as_ref(), Some(&ski));
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        qwac.digital_ids = vec![
+            DigitalId::Ski(ski.clone()),
+            DigitalId::SubjectName("CN=Example QWAC Issuing CA,O=Example Trust GmbH,C=DE".to_string()),
+        ];
+        assert!(qwac.identifies(&issuing_ca));
+        assert!(!qwac.identifies(&leaf));
+        let json = qwac.to_json();
+        assert_eq!(json["digital_ids"][0]["type"], "X509SKI");
+        assert_eq!(json["digital_ids"][0]["value"], "780dba79110dfc34f3ddfbe48282e4ee6319d201");
+
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap();
+        assert!(extractor.select(&list).is_empty());
+        let intermediates = [issuing_ca.clone()];
+        let anchors = extractor.select_anchors(&list, &intermediates);
+        assert_eq!(anchors.len(), 1);
+        let options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: None,
+            require_service_in_force: true,
+        };
+        let path = chain::validate(&leaf, &intermediates, &anchors, &options).unwrap();
+        assert_eq!(path.chain.len(), 1);
+        assert_eq!(path.anchor.der, issuing_ca.der.as_slice());
+        assert_eq!(path.anchor.service.information.name(), Some("Example QWAC Root CA"));
+
+        // Every key identity must match
+        let qwac = &mut list.providers[0].services[0].information;
+        qwac.digital_ids[1] = DigitalId::SubjectName("CN=Another CA,O=Example Trust GmbH,C=DE".to_string());
+        assert!(!qwac.identifies(&issuing_ca));
+        assert!(extractor.select_anchors(&list, &intermediates).is_empty());
+
+        // A subject name alone binds no key and identifies nothing
+        let qwac = &mut list.providers[0].services[0].information;
+        qwac.digital_ids = vec![DigitalId::SubjectName(issuing_ca.subject.clone())];
+        assert!(!qwac.identifies(&issuing_ca));
+        assert!(extractor.select_anchors(&list, &intermediates).is_empty());
+
+        // Certificates listed alongside key identities identify the service as before
+        let mut list = sample_list();
+        let qseal_root = fixture_certificate("qseal_root");
+        let qseal = service_listing(&mut list, &qseal_root);
+        qseal.digital_ids.push(DigitalId::Ski(vec![0; 20]));
+        assert!(qseal.identifies(&qseal_root));
+        let json = qseal.to_json();
+        assert_eq!(json["digital_ids"][0]["type"], "X509Certificate");
+        assert_eq!(json["digital_ids"][0]["subject"], qseal_root.subject.as_str());
+        assert_eq!(json["digital_ids"][0]["sha256"].as_str().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_service_identity_forged_certificate() {
+        // Same subject and subject key identifier extension as the issuing CA, another key
+        let (issuing_ca, forged_ca) = (fixture_certificate("qwac_issuing_ca"), fixture_certificate("forged_ca"));
+        assert_eq!(forged_ca.subject, issuing_ca.subject);
+        assert_eq!(forged_ca.subject_key_id, issuing_ca.subject_key_id);
+        assert_ne!(forged_ca.key_identifier(), issuing_ca.key_identifier());
+
+        let mut list = sample_list();
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        qwac.digital_ids = vec![
+            DigitalId::Ski(issuing_ca.key_identifier()),
+            DigitalId::SubjectName(issuing_ca.subject.clone()),
+        ];
+        assert!(qwac.identifies(&issuing_ca));
+        assert!(!qwac.identifies(&forged_ca));
+
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap();
+        let intermediates = [forged_ca];
+        let anchors = extractor.select_anchors(&list, &intermediates);
+        assert!(anchors.is_empty());
+        let options = PathOptions {
+            time: parse_date_time("2025-06-01T00:00:00Z").unwrap(),
+            purpose: None,
+            require_service_in_force: true,
+        };
+        let forged_leaf = fixture_certificate("forged_leaf");
+        assert!(chain::validate(&forged_leaf, &intermediates, &anchors, &options).is_err());
+
+        // Nor does the qualification of the leaf
+        let qualified = qualify::qualify(&list, &forged_leaf, &intermediates);
+        assert!(qualified.service.is_none());
+    }
+
+    #[test]
+    fn test_certificate_filters() {
+        let mut list = sample_list();
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        for name in ["qwac_issuing_ca", "qwac_leaf", "ocsp_responder"] {
+            qwac.digital_ids.push(DigitalId::Certificate(fixture_certificate(name).der));
+        }
+        let selected = |filter: Option<CertificateFilter>| -> Vec<String> {
+            let mut extractor = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap();
+            if let Some(filter) = filter {
+                extractor = extractor.with_certificate_filter(filter);
+            }
+            let subject = |der: &[u8]| Certificate::from_der(der).unwrap().subject;
+            let common_name = |subject: String| subject.rsplit("CN=").next().unwrap().to_string();
+            extractor.select(&list).iter().map(|c| common_name(subject(c.der))).collect()
+        };
+        assert_eq!(selected(None).len(), 4);
+
+        // TLS clients: serverAuth, or no extended key usage at all
+        let tls = CertificateFilter::ExtendedKeyUsage {
+            purposes: vec![chain::key_purpose_oid("serverAuth").unwrap()],
+            allow_absent: true,
+        };
+        assert_eq!(
+            selected(Some(tls)),
+            vec!["Example QWAC Root CA", "Example QWAC Issuing CA", "www.example-bank.de"]
+        );
+        let policy = CertificateFilter::Policy(vec![filter::policy_oid("qcp-w").unwrap()]);
+        assert_eq!(selected(Some(policy)), vec!["www.example-bank.de"]);
+        let ca = CertificateFilter::KeyUsage(vec!["keyCertSign".to_string(), "crlSign".to_string()]);
+        assert_eq!(selected(Some(ca.clone())), vec!["Example QWAC Root CA", "Example QWAC Issuing CA"]);
+        assert_eq!(selected(Some(CertificateFilter::Kind(CaKind::Root))), vec!["Example QWAC Root CA"]);
+        assert_eq!(
+            selected(Some((!CertificateFilter::Kind(CaKind::Root)).and(ca))),
+            vec!["Example QWAC Issuing CA"]
+        );
+        let issuer = CertificateFilter::Issuer(regex::Regex::new("CN=Example QWAC Root CA$").unwrap());
+        let subject = CertificateFilter::Subject(regex::Regex::new("(?i)responder").unwrap());
+        assert_eq!(
+            selected(Some(issuer.and(!subject))),
+            vec!["Example QWAC Root CA", "Example QWAC Issuing CA"]
+        );
+        let time = |value: &str| parse_date_time(value).unwrap();
+        assert_eq!(selected(Some(CertificateFilter::valid_at(time("2030-01-01T00:00:00Z")))).len(), 4);
+        let window = CertificateFilter::ValidDuring {
+            from: time("2023-06-01T00:00:00Z"),
+            until: time("2030-01-01T00:00:00Z"),
+        };
+        assert!(selected(Some(window.or(CertificateFilter::Any(Vec::new())))).is_empty());
+
+        // The command line options combine into one filter
+        let args = Args::try_parse_from([
+            "ca_extractor", "QWAC", "DE", "--eku", "serverAuth,none", "--policy", "QCP-w,0.4.0.2042.1.4",
+            "--ca-kind", "intermediate", "--valid-from", "2030-01-01T00:00:00Z",
+        ])
+        .unwrap();
+        let filter = args.certificate_filter.filter().unwrap();
+        assert_eq!(
+            filter.to_string(),
+            concat!(
+                "(extendedKeyUsage serverAuth,none) and (policy QCP-w,EVCP) and (intermediate)",
+                " and (valid at 2030-01-01T00:00:00+00:00)"
+            )
+        );
+        assert_eq!(selected(Some(filter)), vec!["www.example-bank.de"]);
+        assert!(Args::try_parse_from(["ca_extractor", "QWAC", "DE"]).unwrap().certificate_filter.filter().is_none());
+        assert!(Args::try_parse_from(["ca_extractor", "QWAC", "DE", "--subject", "CN=(unclosed"]).is_err());
+        assert!(Args::try_parse_from(["ca_extractor", "QWAC", "DE", "--key-usage", "certSign"]).is_err());
+    }
+
+    #[test]
+    fn test_where_expressions() {
+        let mut list = sample_list();
+        let qwac = service_listing(&mut list, &fixture_certificate("qwac_root"));
+        for name in ["qwac_issuing_ca", "qwac_leaf"] {
+            qwac.digital_ids.push(DigitalId::Certificate(fixture_certificate(name).der));
+        }
+        let selected = |source: &str| -> Vec<String> {
+            let services = [ServiceType::QWAC, ServiceType::QSealC, ServiceType::QESig];
+            let extractor = CertificateExtractor::new(&services, "DE")
+                .unwrap()
+                .with_condition(Expression::parse(source).unwrap());
+            let subject = |der: &[u8]| Certificate::from_der(der).unwrap().subject;
+            extractor.select(&list).iter().map(|c| subject(c.der).rsplit("CN=").next().unwrap().to_string()).collect()
+        };
+        assert_eq!(selected("true").len(), 5);
+        assert_eq!(
+            selected(concat!(
+                r#"service.type == "QC" && "ForWebSiteAuthentication" in service.additional"#,
+                r#" && cert.not_after > now()"#
+            )),
+            vec!["Example QWAC Root CA", "Example QWAC Issuing CA", "www.example-bank.de"]
+        );
+        assert_eq!(
+            selected(r#"("serverAuth" in cert.eku || !present(cert.eku)) && cert.ca && !cert.self_signed"#),
+            vec!["Example QWAC Issuing CA"]
+        );
+        assert_eq!(selected(r#""QCP-w" in cert.policies && cert.path_len == 0"#), Vec::<String>::new());
+        assert_eq!(
+            selected(r#"cert.path_len == 0 && cert.subject =~ "(?i)qwac""#),
+            vec!["Example QWAC Issuing CA"]
+        );
+        assert_eq!(
+            selected(r#"tsp.name in ["Beispiel Signatur AG"] || "VATDE-987654321" in tsp.identifiers"#),
+            vec!["Example QESig Root CA"]
+        );
+        assert_eq!(
+            selected(concat!(
+                r#"service.status == "granted" && list.country == "DE""#,
+                r#" && cert.not_before < date("2024-01-02")"#
+            ))
+            .len(),
+            5
+        );
+        assert_eq!(
+            selected(r#"cert.not_after >= date("2044-01-02T00:00:00Z") || service.taken_over"#),
+            vec!["Example QESig Root CA"]
+        );
+
+        // Expressions evaluate against any model context
+        let source = r#"service.name == "Example QSealC Root CA" && "QC" in service.type"#;
+        let condition: Expression = source.parse().unwrap();
+        let (provider, service) =
+            list.services().find(|(_, service)| service.information.name() == Some("Example QSealC Root CA")).unwrap();
+        let certificate = fixture_certificate("qseal_root");
+        let context = Context {
+            list: &list,
+            provider,
+            service,
+            certificate: &certificate,
+            now: Utc::now(),
+        };
+        assert!(condition.matches(&context));
+        assert_eq!(condition.to_string(), source);
+
+        // Names and types are checked when parsing
+        let rejected = |source: &str| Expression::parse(source).unwrap_err().to_string();
+        let unknown = rejected(r#"service.typ == "QC""#);
+        assert!(unknown.starts_with("unknown field `service.typ`, expected one of list.country, list.sequence_number"));
+        assert_eq!(rejected(r#"cert.not_after > "2030""#), "`>` cannot compare time and text at column 16");
+        assert_eq!(rejected("service.type"), "expected a condition, the expression is a text at column 1");
+        assert_eq!(rejected("cert.ca && cert.path_len"), "`&&` combines conditions, found integer at column 9");
+        assert_eq!(rejected(r#"cert.subject =~ "(""#).lines().next().unwrap(), "regex parse error:");
+        assert_eq!(rejected(r#"service.name == "open"#), "unterminated text at column 17");
+        assert_eq!(rejected("now() == now() )"), "unexpected `)` at column 16");
+        let args = Args::try_parse_from(["ca_extractor", "QWAC", "DE", "--where", "cert.ca"]).unwrap();
+        assert_eq!(args.certificate_filter.condition.unwrap().to_string(), "cert.ca");
+        assert!(Args::try_parse_from(["ca_extractor", "QWAC", "DE", "--where", "cert.ca &&"]).is_err());
+    }
+
+    #[test]
+    fn test_explain_selection() {
+        let mut list = sample_list();
+        let qwac_root = fixture_certificate("qwac_root");
+        service_listing(&mut list, &qwac_root)
+            .digital_ids
+            .push(DigitalId::Certificate(fixture_certificate("qwac_issuing_ca").der));
+        service_listing(&mut list, &fixture_certificate("qseal_root"))
+            .digital_ids
+            .push(DigitalId::Certificate(qwac_root.der.clone()));
+        let extractor = CertificateExtractor::new(&[ServiceType::QWAC, ServiceType::QSealC], "DE")
+            .unwrap()
+            .with_qualification(QualificationFilter::All)
+            .without_qualifiers(vec![etsi::QUALIFIER_NOT_QUALIFIED.to_string()])
+            .with_certificate_filter(CertificateFilter::Kind(CaKind::Root))
+            .with_condition(Expression::parse("cert.ca").unwrap());
+
+        let explanations = extractor.explain(&list);
+        assert_eq!(explanations.len(), 7);
+        let selected: Vec<&[u8]> = explanations.iter().filter(|e| e.is_selected()).map(|e| e.der).collect();
+        let expected: Vec<&[u8]> = extractor.select(&list).iter().map(|c| c.der).collect();
+        assert_eq!(selected, expected);
+
+        let failed = |index: usize| -> Vec<String> {
+            let steps = explanations[index].steps.iter().filter(|step| step.outcome == Outcome::Failed);
+            steps.map(ToString::to_string).collect()
+        };
+        assert!(failed(0).is_empty());
+        let history = &explanations[0].steps[1];
+        assert_eq!(history.check, "history");
+        assert_eq!(history.outcome, Outcome::Passed);
+        assert!(history.detail.starts_with("granted since 2024-01-01T00:00:00+00:00 applies at "));
+        assert!(history.detail.ends_with(
+            "(history: granted since 2024-01-01T00:00:00+00:00, undersupervision since 2023-06-01T00:00:00+00:00)"
+        ));
+        assert_eq!(explanations[0].steps[2].to_string(), "[pass] status: granted is in force");
+        assert_eq!(failed(1), vec!["[FAIL] certificate filter: self-signed"]);
+        assert_eq!(
+            failed(3),
+            vec!["[FAIL] duplicate: already selected from Example Trust GmbH / Example QWAC Root CA"]
+        );
+        assert_eq!(failed(4), vec!["[FAIL] service type: CA/QC for ForeSignatures matches none of QWAC,QSealC"]);
+        let taken_over = explanations[4].steps.iter().find(|step| step.check == "taken over").unwrap();
+        assert_eq!(taken_over.to_string(), "[info] taken over: by Example Trust GmbH");
+
+        // Lookup by fingerprint, as printed by OpenSSL
+        let fingerprint = concat!(
+            "EB:FD:75:5A:18:09:B5:AA:88:5E:60:76:5D:AC:5B:63:",
+            "10:CE:FB:50:D6:A6:ED:6E:A0:BC:64:F4:37:E3:53:D6"
+        );
+        let found: Vec<usize> =
+            (0..explanations.len()).filter(|&index| explanations[index].has_fingerprint(fingerprint)).collect();
+        assert_eq!(found, vec![0, 3]);
+        assert!(explanations[1].has_fingerprint("a6871403"));
+        assert!(!explanations[1].has_fingerprint(""));
+        let report = explanations[0].to_string();
+        assert!(report.starts_with(concat!(
+            "SELECTED: C=DE, O=Example Trust GmbH, CN=Example QWAC Root CA\n",
+            "  SHA-256: ebfd755a1809b5aa885e60765dac5b6310cefb50d6a6ed6ea0bc64f437e353d6\n",
+            "  TSP:     Example Trust GmbH\n",
+            "  Service: Example QWAC Root CA (CA/QC)\n",
+            "  [pass] qualification: granted makes the service qualified, accepted at qualification all\n",
+        )));
+
+        // The status history decides at the selection time
+        let at = |time: &str| {
+            let extractor = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap();
+            extractor.with_qualification(QualificationFilter::All).with_time(parse_date_time(time).unwrap())
+        };
+        let history = at("2023-09-01T00:00:00Z").explain(&list).swap_remove(0).steps.swap_remove(1);
+        assert!(history.to_string().starts_with("[pass] history: undersupervision since 2023-06-01"));
+        let explanation = at("2023-01-01T00:00:00Z").explain(&list).swap_remove(0);
+        assert!(!explanation.is_selected());
+        assert!(explanation.steps[1].to_string().starts_with("[FAIL] history: not listed yet at 2023-01-01"));
+        assert!(!explanation.steps.iter().any(|step| step.check == "status"));
+        assert!(!at("2023-01-01T00:00:00Z").select(&list).iter().any(|c| c.der == qwac_root.der.as_slice()));
+
+        // A service withdrawn at the selection time fails the status check
+        let qwac = service_listing(&mut list, &qwac_root);
+        qwac.status = etsi::STATUS_WITHDRAWN.to_string();
+        qwac.status_starting_time = parse_date_time("2025-01-01T00:00:00Z");
+        let qualified = CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap();
+        let steps = qualified.explain(&list).swap_remove(0).steps;
+        let failed: Vec<String> =
+            steps.iter().filter(|step| step.outcome == Outcome::Failed).map(ToString::to_string).collect();
+        assert_eq!(
+            failed,
+            vec![
+                "[FAIL] qualification: withdrawn makes the service withdrawn, not accepted at qualification qualified",
+                "[FAIL] status: withdrawn is not in force",
+            ]
+        );
+        let withdrawn = qualified.with_qualification(QualificationFilter::Withdrawn);
+        let status = withdrawn.explain(&list).swap_remove(0).steps.swap_remove(2);
+        assert_eq!(status.to_string(), "[pass] status: withdrawn is not in force, accepted at qualification withdrawn");
+        assert_eq!(withdrawn.select(&list).len(), 2);
+
+        // Required and excluded qualifiers are named
+        let extractor = CertificateExtractor::new(&[ServiceType::QSealC], "DE")
+            .unwrap()
+            .with_qualifiers(vec![etsi::QUALIFIER_QC_WITH_QSCD.to_string()])
+            .without_qualifiers(vec![etsi::QUALIFIER_QC_FOR_LEGAL_PERSON.to_string()]);
+        let qualifiers = extractor.explain(&list).swap_remove(2).steps.swap_remove(4);
+        assert_eq!(
+            qualifiers.to_string(),
+            "[FAIL] qualifiers: qualifiers QCForLegalPerson, missing required QCWithQSCD"
+        );
+        let args = Args::try_parse_from(["ca_extractor", "QWAC", "DE", "--explain-fingerprint", "ebfd"]).unwrap();
+        assert_eq!(args.explain_fingerprint.as_deref(), Some("ebfd"));
+    }
+
+    #[test]
+    fn test_select_service_granted_at_time_withdrawn_now() {
+        let mut list = sample_list();
+        let qwac_root = fixture_certificate("qwac_root");
+        withdraw_service(&mut list, &qwac_root, parse_date_time("2025-01-01T00:00:00Z").unwrap());
+        let at = |time: &str| {
+            CertificateExtractor::new(&[ServiceType::QWAC], "DE").unwrap().with_time(parse_date_time(time).unwrap())
+        };
+
+        let granted = at("2024-06-01T00:00:00Z");
+        let selected = granted.select(&list);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].der, qwac_root.der.as_slice());
+        let explanation = granted.explain(&list).swap_remove(0);
+        assert!(explanation.is_selected());
+        assert_eq!(
+            explanation.steps[0].to_string(),
+            "[pass] qualification: granted makes the service qualified, accepted at qualification qualified"
+        );
+        assert_eq!(explanation.steps[2].to_string(), "[pass] status: granted is in force");
+
+        let withdrawn = at("2025-06-01T00:00:00Z");
+        assert!(withdrawn.select(&list).is_empty());
+        let steps = withdrawn.explain(&list).swap_remove(0).steps;
+        assert!(steps[0].to_string().starts_with("[FAIL] qualification: withdrawn makes the service withdrawn"));
+        assert_eq!(steps[2].to_string(), "[FAIL] status: withdrawn is not in force");
+    }
+
+    #[test]
+    fn test_extension_filters_use_the_history_entry_at_time() {
+        // Qualifiers and TakenOverBy added to the QSealC service on 2025-01-01
+        let mut list = sample_list();
+        let information = service_listing(&mut list, &fixture_certificate("qseal_root"));
+        let mut earlier = information.clone();
+        earlier.qualifications.clear();
+        earlier.status_starting_time = parse_date_time("2024-01-01T00:00:00Z");
+        information.status_starting_time = parse_date_time("2025-01-01T00:00:00Z");
+        information.taken_over_by = Some(TakenOverBy {
+            uri: "http://tsp.example/takeover".to_string(),
+            tsp_names: Vec::new(),
+            scheme_operator_names: Vec::new(),
+            scheme_territory: "DE".to_string(),
+        });
+        let service = list
+            .providers
+            .iter_mut()
+            .flat_map(|provider| provider.services.iter_mut())
+            .find(|service| service.information.names[0].value == "Example QSealC Root CA")
+            .unwrap();
+        service.history.insert(0, earlier);
+
+        let extractor = CertificateExtractor::new(&[ServiceType::QSealC], "DE").unwrap();
+        let at = |extractor: &CertificateExtractor, time: &str| {
+            extractor.clone().with_time(parse_date_time(time).unwrap())
+        };
+        let excluding = extractor.clone().without_qualifiers(vec![etsi::QUALIFIER_QC_FOR_LEGAL_PERSON.to_string()]);
+        let before = at(&excluding, "2024-06-01T00:00:00Z");
+        assert_eq!(before.select(&list).len(), 1);
+        let qualifiers = before.explain(&list).swap_remove(0).steps.swap_remove(4);
+        assert_eq!(qualifiers.to_string(), "[pass] qualifiers: no qualifiers");
+        assert!(at(&excluding, "2025-06-01T00:00:00Z").select(&list).is_empty());
+
+        let not_taken_over = extractor.without_taken_over(true);
+        let before = at(&not_taken_over, "2024-06-01T00:00:00Z");
+        assert_eq!(before.select(&list).len(), 1);
+        assert!(!before.explain(&list).swap_remove(0).steps.iter().any(|step| step.check == "taken over"));
+        assert!(at(&not_taken_over, "2025-06-01T00:00:00Z").select(&list).is_empty());
+    }
 }
@@ -0,0 +1,289 @@
+//! Validation of RFC 3161 time-stamp tokens as qualified electronic
+//! time-stamps.
+//!
+//! The token's CMS signature and, when the time-stamped data is given, its
+//! message imprint are verified. The TSA certificate must then be the
+//! digital identity of a `TSA/QTST` service of the trusted list, or be
+//! issued by one, and that service must be granted at `genTime`.
+
+use std::slice;
+
+use chrono::{DateTime, Utc};
+use ring::digest;
+use serde_json::{json, Value};
+use x509_parser::der_parser::asn1_rs::{Class, Tag};
+
+use crate::chain::{self, PathOptions};
+use crate::cms::{self, Element, SignedData};
+use crate::error::CaExtractorError;
+use crate::etsi;
+use crate::extractor::ExtractedCertificate;
+use crate::model::{DigitalId, ServiceInformation, TrustService, TrustServiceProvider, TrustedList};
+use crate::x509::Certificate;
+
+/// `id-ct-TSTInfo`
+pub const OID_TST_INFO: &str = "1.2.840.113549.1.9.16.1.4";
+
+/// The `TSTInfo` of a token
+#[derive(Debug, Clone)]
+pub struct TstInfo {
+    pub policy: String,
+    /// OID of the message imprint's hash algorithm
+    pub hash_algorithm: String,
+    pub message_imprint: Vec<u8>,
+    pub serial: Vec<u8>,
+    pub gen_time: DateTime<Utc>,
+    pub nonce: Option<Vec<u8>>,
+}
+
+/// A parsed `TimeStampToken`
+#[derive(Debug, Clone)]
+pub struct TimeStampToken {
+    pub signed_data: SignedData,
+    pub info: TstInfo,
+}
+
+impl TimeStampToken {
+    /// Parses a DER `TimeStampResp`, as saved in `.tsr` files, or a bare
+    /// `TimeStampToken`
+    pub fn parse(data: &[u8]) -> Result<Self, CaExtractorError> {
+        let outer = Element::single(data)?;
+        let fields = outer.children()?;
+        let token = match fields.first() {
+            // TimeStampResp ::= SEQUENCE { status PKIStatusInfo, timeStampToken OPTIONAL }
+            Some(status) if status.any.header.is_constructed() => {
+                let code = status
+                    .children()?
+                    .first()
+                    .and_then(|code| code.any.as_u32().ok())
+                    .ok_or_else(|| invalid("malformed PKIStatusInfo".to_string()))?;
+                // granted (0) or grantedWithMods (1)
+                if code > 1 {
+                    return Err(invalid(format!("the time-stamp response has status {}, not granted", code)));
+                }
+                let token = fields
+                    .get(1)
+                    .ok_or_else(|| invalid("the time-stamp response carries no token".to_string()))?;
+                token.raw
+            }
+            _ => data,
+        };
+
+        let signed_data = SignedData::parse(token)?;
+        if signed_data.content_type != OID_TST_INFO {
+            return Err(invalid(format!("content type {} is not TSTInfo", signed_data.content_type)));
+        }
+        let content = signed_data
+            .content
+            .as_deref()
+            .ok_or_else(|| invalid("the token has no TSTInfo".to_string()))?;
+        let info = parse_tst_info(content)?;
+        Ok(TimeStampToken { signed_data, info })
+    }
+}
+
+fn parse_tst_info(content: &[u8]) -> Result<TstInfo, CaExtractorError> {
+    let malformed = || invalid("malformed TSTInfo".to_string());
+    let fields = Element::single(content)?.children()?;
+    // version, policy, messageImprint, serialNumber, genTime
+    let [_, policy, imprint, serial, gen_time, optional @ ..] = fields.as_slice() else {
+        return Err(malformed());
+    };
+    let imprint = imprint.children()?;
+    let [hash_algorithm, hashed_message] = imprint.as_slice() else {
+        return Err(malformed());
+    };
+    let gen_time = gen_time
+        .any
+        .as_generalizedtime()
+        .ok()
+        .and_then(|time| time.utc_datetime().ok())
+        .and_then(|time| DateTime::from_timestamp(time.unix_timestamp(), time.nanosecond()))
+        .ok_or_else(malformed)?;
+    let nonce = optional
+        .iter()
+        .find(|field| field.any.class() == Class::Universal && field.any.tag() == Tag::Integer)
+        .map(|nonce| nonce.any.data.to_vec());
+
+    Ok(TstInfo {
+        policy: cms::oid(policy)?,
+        hash_algorithm: cms::algorithm(hash_algorithm)?,
+        message_imprint: cms::octets(hashed_message)?,
+        serial: serial.any.data.to_vec(),
+        gen_time,
+        nonce,
+    })
+}
+
+/// A time-stamp token verified against a qualified time-stamping service
+#[derive(Debug, Clone)]
+pub struct TimestampValidation<'a> {
+    pub token: TimeStampToken,
+    pub tsa: Certificate,
+    /// Whether the message imprint was checked against the time-stamped data
+    pub imprint_verified: bool,
+    pub provider: &'a TrustServiceProvider,
+    pub service: &'a TrustService,
+    /// The service's information in effect at `genTime`
+    pub information: &'a ServiceInformation,
+}
+
+impl TimestampValidation<'_> {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "gen_time": self.token.info.gen_time.to_rfc3339(),
+            "serial": hex(&self.token.info.serial),
+            "policy": self.token.info.policy,
+            "imprint_verified": self.imprint_verified,
+            "tsa": self.tsa.subject,
+            "tsp": self.provider.name(),
+            "service": self.information.name(),
+            "service_type": self.information.service_type,
+            "status": self.information.status,
+        })
+    }
+}
+
+/// Verifies `token` and checks that its TSA is a qualified time-stamping
+/// service of `list` at `genTime`. `data` is the time-stamped data, if
+/// known; `certificates` complement those embedded in the token.
+pub fn validate<'a>(
+    token: TimeStampToken,
+    data: Option<&[u8]>,
+    certificates: &[Certificate],
+    list: &'a TrustedList,
+) -> Result<TimestampValidation<'a>, CaExtractorError> {
+    let signed_data = &token.signed_data;
+    let [signer] = signed_data.signers.as_slice() else {
+        return Err(invalid(format!(
+            "a time-stamp token has exactly one signer, found {}",
+            signed_data.signers.len()
+        )));
+    };
+    let tsa = signed_data
+        .signer_certificate(signer, certificates)
+        .ok_or_else(|| invalid("the TSA certificate is neither in the token nor given with --chain".to_string()))?;
+
+    let content = signed_data.content.as_deref().unwrap_or_default();
+    signer.verify(content, OID_TST_INFO, &tsa)?;
+    if !signer.check_signing_certificate(&tsa)? {
+        return Err(invalid("the token has no signing certificate attribute".to_string()));
+    }
+
+    let gen_time = token.info.gen_time;
+    let time_stamping = chain::key_purpose_oid("timeStamping").expect("timeStamping is a known key purpose");
+    if !tsa.extended_key_usage.as_ref().is_some_and(|usages| usages.contains(&time_stamping)) {
+        return Err(invalid(format!(
+            "the extended key usage of '{}' does not allow timeStamping",
+            tsa.subject
+        )));
+    }
+    if !tsa.is_valid_at(gen_time) {
+        return Err(invalid(format!(
+            "'{}' is not valid at the time-stamp time {}",
+            tsa.subject,
+            gen_time.to_rfc3339()
+        )));
+    }
+
+    if let Some(data) = data {
+        let algorithm = cms::digest_algorithm(&token.info.hash_algorithm).ok_or_else(|| {
+            invalid(format!("unsupported message imprint algorithm {}", token.info.hash_algorithm))
+        })?;
+        if digest::digest(algorithm, data).as_ref() != token.info.message_imprint {
+            return Err(invalid("the message imprint does not match the data".to_string()));
+        }
+    }
+
+    let mut intermediates = signed_data.certificates.clone();
+    intermediates.extend_from_slice(certificates);
+    let (provider, service, information) = find_qtst_service(list, &tsa, &intermediates, gen_time)?;
+
+    Ok(TimestampValidation {
+        imprint_verified: data.is_some(),
+        tsa,
+        provider,
+        service,
+        information,
+        token,
+    })
+}
+
+/// Finds a `TSA/QTST` service granted at `time` whose digital identity is
+/// `tsa`, by certificate or `X509SKI`, or issued it
+fn find_qtst_service<'a>(
+    list: &'a TrustedList,
+    tsa: &Certificate,
+    intermediates: &[Certificate],
+    time: DateTime<Utc>,
+) -> Result<(&'a TrustServiceProvider, &'a TrustService, &'a ServiceInformation), CaExtractorError> {
+    // Issued under a listed certificate: a path valid at `time`, through CAs
+    // allowed to sign certificates; the service status is checked below
+    let options = PathOptions {
+        time,
+        purpose: None,
+        require_service_in_force: false,
+    };
+    let issued = |provider, service, der: &'a [u8]| {
+        let anchor = ExtractedCertificate {
+            provider,
+            service,
+            service_types: Vec::new(),
+            der,
+            revocation: None,
+        };
+        chain::validate(tsa, intermediates, slice::from_ref(&anchor), &options).is_ok()
+    };
+    let listed: Vec<(&TrustServiceProvider, &TrustService)> = list
+        .services()
+        .filter(|&(provider, service)| {
+            service.information.identifies(tsa)
+                || service.information.digital_ids.iter().any(|id| match id {
+                    DigitalId::Certificate(der) => issued(provider, service, der),
+                    _ => false,
+                })
+        })
+        .collect();
+
+    let mut reasons = Vec::new();
+    for (provider, service) in listed {
+        let name = service.information.name().unwrap_or("<unnamed>");
+        match service.information_at(time) {
+            Some(information)
+                if information.service_type == etsi::SVCTYPE_TSA_QTST
+                    && etsi::GRANTED_STATUSES.contains(&information.status.as_str()) =>
+            {
+                return Ok((provider, service, information));
+            }
+            Some(information) if information.service_type != etsi::SVCTYPE_TSA_QTST => reasons.push(format!(
+                "service '{}' is of type '{}', not TSA/QTST",
+                name,
+                etsi::short_name(&information.service_type)
+            )),
+            Some(information) => reasons.push(format!(
+                "service '{}' has status '{}' at {}",
+                name,
+                etsi::short_name(&information.status),
+                time.to_rfc3339()
+            )),
+            None => reasons.push(format!("service '{}' was not listed yet at {}", name, time.to_rfc3339())),
+        }
+    }
+
+    if reasons.is_empty() {
+        reasons.push("it is not listed under any service of the trusted list".to_string());
+    }
+    Err(CaExtractorError::NotQualified(format!(
+        "the TSA '{}' is not a granted qualified time-stamping service: {}",
+        tsa.subject,
+        reasons.join("; ")
+    )))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
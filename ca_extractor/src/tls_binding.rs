@@ -0,0 +1,162 @@
+//! Validation of two-certificate QWACs (ETSI TS 119 411-5).
+//!
+//! A website using a 2-QWAC publishes a TLS certificate binding: a JAdES
+//! signature made with the 2-QWAC key whose `sigD` header lists digests of
+//! the TLS certificates it vouches for. The binding is valid when its
+//! signature verifies, it lists the TLS certificate presented, and the
+//! 2-QWAC chains to a `ForWebSiteAuthentication` CA/QC service.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::chain::{self, PathOptions, ValidPath};
+use crate::error::CaExtractorError;
+use crate::extractor::ExtractedCertificate;
+use crate::jws::{self, CompactJws};
+use crate::x509::{Certificate, QcType};
+
+/// `cty` of a TLS certificate binding
+pub const CONTENT_TYPE: &str = "TLS-Certificate-Binding-v1";
+/// `sigD` mechanism referencing detached objects by the digest of their content
+pub const SIGD_OBJECT_ID_BY_URI_HASH: &str = "http://uri.etsi.org/19182/ObjectIdByURIHash";
+
+/// A parsed TLS certificate binding
+#[derive(Debug, Clone)]
+pub struct TlsBinding {
+    pub jws: CompactJws,
+    /// The 2-QWAC, first certificate of `x5c`
+    pub qwac: Certificate,
+    /// Remaining `x5c` certificates
+    pub intermediates: Vec<Certificate>,
+    /// `hashM` of `sigD`
+    pub hash_algorithm: String,
+    /// `hashV` of `sigD`: digests of the bound TLS certificates
+    pub bound_digests: Vec<String>,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl TlsBinding {
+    pub fn parse(text: &str) -> Result<Self, CaExtractorError> {
+        let jws = CompactJws::parse(text)?;
+
+        if let Some(content_type) = jws.header_str("cty") {
+            if content_type != CONTENT_TYPE {
+                return Err(invalid(format!("content type '{}' is not {}", content_type, CONTENT_TYPE)));
+            }
+        }
+
+        let sig_d = jws
+            .header
+            .get("sigD")
+            .and_then(Value::as_object)
+            .ok_or_else(|| invalid("the binding has no sigD header".to_string()))?;
+        let mechanism = sig_d.get("mId").and_then(Value::as_str).unwrap_or_default();
+        if mechanism != SIGD_OBJECT_ID_BY_URI_HASH {
+            return Err(invalid(format!("unsupported sigD mechanism '{}'", mechanism)));
+        }
+        let hash_algorithm = sig_d.get("hashM").and_then(Value::as_str).unwrap_or_default().to_string();
+        if jws::digest_algorithm(&hash_algorithm).is_none() {
+            return Err(invalid(format!("unsupported sigD hashM '{}'", hash_algorithm)));
+        }
+        let bound_digests = sig_d
+            .get("hashV")
+            .and_then(Value::as_array)
+            .map(|values| values.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+
+        let mut certificates = jws.certificate_chain()?;
+        if certificates.is_empty() {
+            return Err(invalid("the binding has no x5c header".to_string()));
+        }
+        let qwac = certificates.remove(0);
+        jws.check_certificate_digest(&qwac)?;
+
+        let time = |name: &str| {
+            jws.header
+                .get(name)
+                .and_then(Value::as_i64)
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        };
+        Ok(TlsBinding {
+            issued_at: time("iat"),
+            expires: time("exp"),
+            qwac,
+            intermediates: certificates,
+            hash_algorithm,
+            bound_digests,
+            jws,
+        })
+    }
+
+    /// Whether the binding lists `tls_certificate`
+    pub fn binds(&self, tls_certificate: &Certificate) -> bool {
+        let algorithm = jws::digest_algorithm(&self.hash_algorithm).expect("hashM is checked by parse");
+        let value = jws::encode_url(ring::digest::digest(algorithm, &tls_certificate.der).as_ref());
+        self.bound_digests.contains(&value)
+    }
+}
+
+/// A validated 2-QWAC binding
+#[derive(Debug, Clone)]
+pub struct TwoQwacValidation<'a> {
+    pub binding: TlsBinding,
+    /// Path of the 2-QWAC to its QWAC CA
+    pub path: ValidPath<'a>,
+}
+
+impl TwoQwacValidation<'_> {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "qwac": self.binding.qwac.subject,
+            "issued_at": self.binding.issued_at.map(|time| time.to_rfc3339()),
+            "expires": self.binding.expires.map(|time| time.to_rfc3339()),
+            "path": self.path.to_json(),
+        })
+    }
+}
+
+/// Validates a TLS certificate binding for `tls_certificate`. `anchors`
+/// should be the QWAC CA certificates of the trusted list; `intermediates`
+/// complement the binding's own `x5c`.
+pub fn validate<'a>(
+    binding_text: &str,
+    tls_certificate: &Certificate,
+    intermediates: &[Certificate],
+    anchors: &[ExtractedCertificate<'a>],
+    options: &PathOptions,
+) -> Result<TwoQwacValidation<'a>, CaExtractorError> {
+    let binding = TlsBinding::parse(binding_text)?;
+    binding.jws.verify(&binding.qwac)?;
+
+    if let Some(expires) = binding.expires {
+        if options.time > expires {
+            return Err(invalid(format!("the binding expired on {}", expires.to_rfc3339())));
+        }
+    }
+    if !binding.binds(tls_certificate) {
+        return Err(invalid(format!(
+            "the TLS certificate '{}' is not listed in the binding",
+            tls_certificate.subject
+        )));
+    }
+
+    let statements = &binding.qwac.qc_statements;
+    if !statements.compliance || !statements.types.contains(&QcType::Web) {
+        return Err(invalid(format!(
+            "the binding is signed by '{}', which is not a qualified website authentication certificate",
+            binding.qwac.subject
+        )));
+    }
+
+    let mut candidates = binding.intermediates.clone();
+    candidates.extend_from_slice(intermediates);
+    let path = chain::validate(&binding.qwac, &candidates, anchors, options)
+        .map_err(CaExtractorError::PathValidationFailed)?;
+
+    Ok(TwoQwacValidation { binding, path })
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::SignatureInvalid(message)
+}
@@ -0,0 +1,497 @@
+//! The parts of X.509 certificates needed to relate them to trusted list
+//! services: names, key identifiers, key usage, policies and the ETSI
+//! EN 319 412-5 QcStatements.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use ring::{digest, signature};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::der_parser::ber::BerObject;
+use x509_parser::der_parser::der::parse_der;
+use x509_parser::extensions::{DistributionPointName, GeneralName, ParsedExtension};
+use x509_parser::pem::Pem;
+use x509_parser::prelude::FromDer;
+use x509_parser::time::ASN1Time;
+
+use crate::error::CaExtractorError;
+use crate::model;
+
+/// `id-ad-ocsp` access method of the authority information access extension
+pub const OID_AD_OCSP: &str = "1.3.6.1.5.5.7.48.1";
+/// `id-pe-qcStatements` certificate extension
+pub const OID_QC_STATEMENTS: &str = "1.3.6.1.5.5.7.1.3";
+/// `esi4-qcStatement-1`: the certificate is an EU qualified certificate
+pub const OID_QCS_COMPLIANCE: &str = "0.4.0.1862.1.1";
+/// `esi4-qcStatement-4`: the private key resides in a QSCD
+pub const OID_QCS_SSCD: &str = "0.4.0.1862.1.4";
+/// `esi4-qcStatement-6`: type of qualified certificate
+pub const OID_QCS_TYPE: &str = "0.4.0.1862.1.6";
+pub const OID_QCT_ESIGN: &str = "0.4.0.1862.1.6.1";
+pub const OID_QCT_ESEAL: &str = "0.4.0.1862.1.6.2";
+pub const OID_QCT_WEB: &str = "0.4.0.1862.1.6.3";
+/// `esi4-qcStatement-3`: retention period of registration information
+pub const OID_QCS_RETENTION_PERIOD: &str = "0.4.0.1862.1.3";
+/// `esi4-qcStatement-5`: locations of the PKI disclosure statements
+pub const OID_QCS_PDS: &str = "0.4.0.1862.1.5";
+/// `etsi-psd2-qcStatement` of ETSI TS 119 495
+pub const OID_QCS_PSD2: &str = "0.4.0.19495.2";
+/// `id-at-organizationIdentifier`
+pub const OID_ORGANIZATION_IDENTIFIER: &str = "2.5.4.97";
+
+/// PSD2 roles of payment service providers, by OID (ETSI TS 119 495)
+pub const PSD2_ROLES: &[(&str, &str)] = &[
+    ("0.4.0.19495.1.1", "PSP_AS"),
+    ("0.4.0.19495.1.2", "PSP_PI"),
+    ("0.4.0.19495.1.3", "PSP_AI"),
+    ("0.4.0.19495.1.4", "PSP_IC"),
+];
+
+/// Key usage bits, in bit order, named as in ETSI TS 119 612 `KeyUsageBit`
+pub const KEY_USAGE_BITS: &[&str] = &[
+    "digitalSignature",
+    "nonRepudiation",
+    "keyEncipherment",
+    "dataEncipherment",
+    "keyAgreement",
+    "keyCertSign",
+    "crlSign",
+    "encipherOnly",
+    "decipherOnly",
+];
+
+/// Type of a qualified certificate, from the `QcType` statement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QcType {
+    ESign,
+    ESeal,
+    Web,
+}
+
+impl fmt::Display for QcType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QcType::ESign => write!(f, "esign"),
+            QcType::ESeal => write!(f, "eseal"),
+            QcType::Web => write!(f, "web"),
+        }
+    }
+}
+
+/// One location of a PKI disclosure statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdsLocation {
+    pub url: String,
+    pub language: String,
+}
+
+/// A PSD2 role of the subject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psd2Role {
+    pub oid: String,
+    /// Role name as encoded in the certificate, e.g. `PSP_AS`
+    pub name: String,
+}
+
+impl Psd2Role {
+    /// Whether the name matches the one ETSI TS 119 495 defines for the OID
+    pub fn is_consistent(&self) -> bool {
+        PSD2_ROLES.iter().any(|(oid, name)| *oid == self.oid && *name == self.name)
+    }
+}
+
+/// The `PSD2QcType` statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psd2Attributes {
+    pub roles: Vec<Psd2Role>,
+    /// Name of the national competent authority
+    pub nca_name: String,
+    /// Identifier of the national competent authority, e.g. `DE-BAFIN`
+    pub nca_id: String,
+}
+
+/// QcStatements of a certificate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QcStatements {
+    /// `QcCompliance` is present
+    pub compliance: bool,
+    /// `QcSSCD` is present
+    pub sscd: bool,
+    pub types: Vec<QcType>,
+    /// `QcRetentionPeriod` in years
+    pub retention_period: Option<u32>,
+    /// `QcPDS` locations
+    pub pds: Vec<PdsLocation>,
+    pub psd2: Option<Psd2Attributes>,
+    /// Statement OIDs in certificate order, including unrecognised ones
+    pub statement_ids: Vec<String>,
+}
+
+/// A parsed X.509 certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    pub der: Vec<u8>,
+    pub subject: String,
+    pub issuer: String,
+    /// DER encoding of the subject name, used for chaining
+    pub subject_raw: Vec<u8>,
+    pub issuer_raw: Vec<u8>,
+    pub serial: Vec<u8>,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// Content of the subjectPublicKey bit string: an uncompressed EC point
+    /// or a DER `RSAPublicKey`
+    pub public_key: Vec<u8>,
+    pub subject_key_id: Option<Vec<u8>>,
+    pub authority_key_id: Option<Vec<u8>>,
+    /// `cA` flag of the basic constraints, `None` without the extension
+    pub ca: Option<bool>,
+    pub path_len_constraint: Option<u32>,
+    /// Names of the asserted key usage bits, `None` without the extension
+    pub key_usage: Option<Vec<&'static str>>,
+    /// Key purpose OIDs, `None` without the extension
+    pub extended_key_usage: Option<Vec<String>>,
+    pub policies: Vec<String>,
+    /// OIDs of the subject name attributes
+    pub subject_attributes: Vec<String>,
+    /// `organizationIdentifier` attribute of the subject
+    pub organization_identifier: Option<String>,
+    pub qc_statements: QcStatements,
+    /// URIs of the CRL distribution points
+    pub crl_distribution_points: Vec<String>,
+    /// URIs of the OCSP responders named in the authority information access
+    pub ocsp_responders: Vec<String>,
+}
+
+impl Certificate {
+    pub fn from_der(der: &[u8]) -> Result<Self, CaExtractorError> {
+        let (_, cert) = X509Certificate::from_der(der).map_err(|e| invalid(format!("{}", e)))?;
+
+        let key_usage = cert.key_usage().map_err(|e| invalid(e.to_string()))?.map(|ext| {
+            let ku = ext.value;
+            let bits = [
+                ku.digital_signature(),
+                ku.non_repudiation(),
+                ku.key_encipherment(),
+                ku.data_encipherment(),
+                ku.key_agreement(),
+                ku.key_cert_sign(),
+                ku.crl_sign(),
+                ku.encipher_only(),
+                ku.decipher_only(),
+            ];
+            KEY_USAGE_BITS
+                .iter()
+                .zip(bits)
+                .filter(|(_, set)| *set)
+                .map(|(name, _)| *name)
+                .collect()
+        });
+
+        let extended_key_usage = cert.extended_key_usage().map_err(|e| invalid(e.to_string()))?.map(|ext| {
+            let eku = ext.value;
+            let known = [
+                (eku.any, "2.5.29.37.0"),
+                (eku.server_auth, "1.3.6.1.5.5.7.3.1"),
+                (eku.client_auth, "1.3.6.1.5.5.7.3.2"),
+                (eku.code_signing, "1.3.6.1.5.5.7.3.3"),
+                (eku.email_protection, "1.3.6.1.5.5.7.3.4"),
+                (eku.time_stamping, "1.3.6.1.5.5.7.3.8"),
+                (eku.ocsp_signing, "1.3.6.1.5.5.7.3.9"),
+            ];
+            known
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, oid)| oid.to_string())
+                .chain(eku.other.iter().map(|oid| oid.to_id_string()))
+                .collect()
+        });
+
+        let (ca, path_len_constraint) = match cert.basic_constraints().map_err(|e| invalid(e.to_string()))? {
+            Some(ext) => (Some(ext.value.ca), ext.value.path_len_constraint),
+            None => (None, None),
+        };
+
+        let mut subject_key_id = None;
+        let mut authority_key_id = None;
+        let mut policies = Vec::new();
+        let mut qc_statements = QcStatements::default();
+        let mut crl_distribution_points = Vec::new();
+        let mut ocsp_responders = Vec::new();
+        for extension in cert.iter_extensions() {
+            match extension.parsed_extension() {
+                ParsedExtension::SubjectKeyIdentifier(id) => subject_key_id = Some(id.0.to_vec()),
+                ParsedExtension::AuthorityKeyIdentifier(aki) => {
+                    authority_key_id = aki.key_identifier.as_ref().map(|id| id.0.to_vec())
+                }
+                ParsedExtension::CertificatePolicies(list) => {
+                    policies = list.iter().map(|policy| policy.policy_id.to_id_string()).collect()
+                }
+                ParsedExtension::CRLDistributionPoints(points) => {
+                    crl_distribution_points = points
+                        .iter()
+                        .filter_map(|point| match &point.distribution_point {
+                            Some(DistributionPointName::FullName(names)) => Some(names),
+                            _ => None,
+                        })
+                        .flatten()
+                        .filter_map(|name| match name {
+                            GeneralName::URI(uri) => Some(uri.to_string()),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                ParsedExtension::AuthorityInfoAccess(access) => {
+                    ocsp_responders = access
+                        .iter()
+                        .filter(|description| description.access_method.to_id_string() == OID_AD_OCSP)
+                        .filter_map(|description| match &description.access_location {
+                            GeneralName::URI(uri) => Some(uri.to_string()),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                _ if extension.oid.to_id_string() == OID_QC_STATEMENTS => {
+                    qc_statements = parse_qc_statements(extension.value)?
+                }
+                _ => {}
+            }
+        }
+
+        let organization_identifier = cert
+            .subject()
+            .iter_attributes()
+            .find(|attribute| attribute.attr_type().to_id_string() == OID_ORGANIZATION_IDENTIFIER)
+            .and_then(|attribute| attribute.as_str().ok())
+            .map(str::to_string);
+
+        Ok(Certificate {
+            der: der.to_vec(),
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            subject_raw: cert.subject().as_raw().to_vec(),
+            issuer_raw: cert.issuer().as_raw().to_vec(),
+            serial: cert.raw_serial().to_vec(),
+            not_before: to_date_time(cert.validity().not_before),
+            not_after: to_date_time(cert.validity().not_after),
+            public_key: cert.public_key().subject_public_key.data.to_vec(),
+            subject_key_id,
+            authority_key_id,
+            ca,
+            path_len_constraint,
+            key_usage,
+            extended_key_usage,
+            policies,
+            subject_attributes: cert
+                .subject()
+                .iter_attributes()
+                .map(|attribute| attribute.attr_type().to_id_string())
+                .collect(),
+            organization_identifier,
+            qc_statements,
+            crl_distribution_points,
+            ocsp_responders,
+        })
+    }
+
+    /// Whether `issuer` names this certificate's issuer and its key verifies
+    /// this certificate's signature
+    pub fn is_signed_by(&self, issuer: &Certificate) -> bool {
+        if self.issuer_raw != issuer.subject_raw {
+            return false;
+        }
+        let (Ok((_, cert)), Ok((_, issuer))) = (
+            X509Certificate::from_der(&self.der),
+            X509Certificate::from_der(&issuer.der),
+        ) else {
+            return false;
+        };
+        cert.verify_signature(Some(issuer.public_key())).is_ok()
+    }
+
+    /// Verifies a signature over `message` made with this certificate's key
+    pub fn verifies(
+        &self,
+        algorithm: &'static dyn signature::VerificationAlgorithm,
+        message: &[u8],
+        signature_value: &[u8],
+    ) -> bool {
+        signature::UnparsedPublicKey::new(algorithm, &self.public_key)
+            .verify(message, signature_value)
+            .is_ok()
+    }
+
+    /// SHA-256 digest of the DER encoding
+    pub fn sha256_fingerprint(&self) -> Vec<u8> {
+        digest::digest(&digest::SHA256, &self.der).as_ref().to_vec()
+    }
+
+    /// Key identifier computed from the key itself: the SHA-1 digest of the
+    /// subjectPublicKey bit string (RFC 5280 4.2.1.2, method 1). Unlike the
+    /// `subject_key_id` extension, which the issuer may set to any value, it
+    /// cannot be copied to a certificate for another key.
+    pub fn key_identifier(&self) -> Vec<u8> {
+        digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &self.public_key).as_ref().to_vec()
+    }
+
+    pub fn is_self_issued(&self) -> bool {
+        self.subject_raw == self.issuer_raw
+    }
+
+    /// Whether the subject is the distinguished name `name`, given as in an
+    /// `X509SubjectName`. Attributes are compared regardless of their order
+    /// and of the case of their values.
+    pub fn has_subject_name(&self, name: &str) -> bool {
+        name_attributes(name) == name_attributes(&self.subject)
+    }
+
+    /// Whether the key usage bit named as in TS 119 612 is asserted. Without
+    /// a key usage extension no bit is asserted.
+    pub fn has_key_usage(&self, name: &str) -> bool {
+        self.key_usage.as_ref().is_some_and(|bits| bits.contains(&name))
+    }
+
+    pub fn is_valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    pub fn pem(&self) -> String {
+        model::der_to_pem(&self.der)
+    }
+}
+
+/// Parses every certificate of a PEM bundle, ignoring any text around the
+/// PEM blocks, or a single DER certificate
+pub fn parse_certificates(data: &[u8]) -> Result<Vec<Certificate>, CaExtractorError> {
+    if !data.windows(11).any(|window| window == b"-----BEGIN ") {
+        return Certificate::from_der(data).map(|cert| vec![cert]);
+    }
+
+    let mut certificates = Vec::new();
+    for pem in Pem::iter_from_buffer(data) {
+        let pem = pem.map_err(|e| invalid(format!("malformed PEM: {}", e)))?;
+        if pem.label == "CERTIFICATE" {
+            certificates.push(Certificate::from_der(&pem.contents)?);
+        }
+    }
+    if certificates.is_empty() {
+        return Err(invalid("no CERTIFICATE block found".to_string()));
+    }
+    Ok(certificates)
+}
+
+fn parse_qc_statements(value: &[u8]) -> Result<QcStatements, CaExtractorError> {
+    let malformed = || invalid("malformed QcStatements extension".to_string());
+    let (_, statements) = parse_der(value).map_err(|_| malformed())?;
+
+    let mut result = QcStatements::default();
+    for statement in statements.as_sequence().map_err(|_| malformed())? {
+        let fields = statement.as_sequence().map_err(|_| malformed())?;
+        let id = fields
+            .first()
+            .and_then(|field| field.as_oid().ok())
+            .ok_or_else(malformed)?
+            .to_id_string();
+
+        match id.as_str() {
+            OID_QCS_COMPLIANCE => result.compliance = true,
+            OID_QCS_SSCD => result.sscd = true,
+            OID_QCS_RETENTION_PERIOD => {
+                let years = fields.get(1).ok_or_else(malformed)?;
+                result.retention_period = Some(years.as_u32().map_err(|_| malformed())?);
+            }
+            OID_QCS_PDS => {
+                let locations = fields.get(1).ok_or_else(malformed)?.as_sequence().map_err(|_| malformed())?;
+                for location in locations {
+                    let location = location.as_sequence().map_err(|_| malformed())?;
+                    let text = |index: usize| {
+                        location
+                            .get(index)
+                            .and_then(|field| field.as_str().ok())
+                            .map(str::to_string)
+                            .ok_or_else(malformed)
+                    };
+                    result.pds.push(PdsLocation {
+                        url: text(0)?,
+                        language: text(1)?,
+                    });
+                }
+            }
+            OID_QCS_PSD2 => result.psd2 = Some(parse_psd2(fields.get(1)).ok_or_else(malformed)?),
+            OID_QCS_TYPE => {
+                let types = fields.get(1).map(BerObject::as_sequence).transpose().map_err(|_| malformed())?;
+                for qc_type in types.into_iter().flatten() {
+                    match qc_type.as_oid().map(|oid| oid.to_id_string()).as_deref() {
+                        Ok(OID_QCT_ESIGN) => result.types.push(QcType::ESign),
+                        Ok(OID_QCT_ESEAL) => result.types.push(QcType::ESeal),
+                        Ok(OID_QCT_WEB) => result.types.push(QcType::Web),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        result.statement_ids.push(id);
+    }
+    Ok(result)
+}
+
+/// `PSD2QcType ::= SEQUENCE { rolesOfPSP, nCAName, nCAId }`
+fn parse_psd2(info: Option<&BerObject>) -> Option<Psd2Attributes> {
+    let fields = info?.as_sequence().ok()?;
+    let roles = fields
+        .first()?
+        .as_sequence()
+        .ok()?
+        .iter()
+        .map(|role| {
+            let role = role.as_sequence().ok()?;
+            Some(Psd2Role {
+                oid: role.first()?.as_oid().ok()?.to_id_string(),
+                name: role.get(1)?.as_str().ok()?.to_string(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Psd2Attributes {
+        roles,
+        nca_name: fields.get(1)?.as_str().ok()?.to_string(),
+        nca_id: fields.get(2)?.as_str().ok()?.to_string(),
+    })
+}
+
+/// `type=value` attributes of a distinguished name in string form, with the
+/// type in upper case and the value in lower case, sorted. Escaped
+/// separators stay part of the value.
+fn name_attributes(name: &str) -> Vec<(String, String)> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in name.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            ',' | '+' | ';' => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    let mut attributes: Vec<(String, String)> = parts
+        .iter()
+        .filter_map(|part| part.split_once('='))
+        .map(|(kind, value)| (kind.trim().to_uppercase(), value.trim().to_lowercase()))
+        .collect();
+    attributes.sort();
+    attributes
+}
+
+pub(crate) fn to_date_time(time: ASN1Time) -> DateTime<Utc> {
+    DateTime::from_timestamp(time.timestamp(), 0).unwrap_or_default()
+}
+
+fn invalid(message: String) -> CaExtractorError {
+    CaExtractorError::InvalidCertificateFormat(message)
+}
@@ -0,0 +1,952 @@
+//! Validation against W3C XML Schema 1.0 definitions loaded at run time.
+//!
+//! Only the subset of XML Schema the ETSI TS 119 612 schemas use is
+//! supported: global and local element declarations, named and anonymous
+//! complex types with element-only, mixed, simple or empty content,
+//! derivation by extension, simple types restricting a built-in type with
+//! `length`, `minLength`, `maxLength`, `pattern` and `enumeration` facets,
+//! attribute declarations and wildcards. Content models must reduce to a
+//! sequence of elements, wildcards and choices among elements; loading fails
+//! on anything else rather than validating it loosely.
+//!
+//! Elements and types of namespaces without a loaded schema, such as XML
+//! Signature and XAdES, are accepted as they are.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use base64::Engine;
+use regex::Regex;
+use xml::namespace::Namespace;
+use xml::reader::XmlEvent;
+
+use crate::dom::{self, Element};
+use crate::limits::ParseLimits;
+use crate::schema::SchemaViolation;
+
+/// Namespace of XML Schema itself and of its built-in types
+pub const XSD_NS: &str = "http://www.w3.org/2001/XMLSchema";
+/// Namespace of the `xml:` attributes
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+const UNBOUNDED: usize = usize::MAX;
+
+/// Namespace URI and local name
+type QName = (String, String);
+
+/// Schemas loaded together, so that they can refer to each other
+#[derive(Debug, Default)]
+pub struct SchemaSet {
+    elements: HashMap<QName, ElementDecl>,
+    complex_types: HashMap<QName, ComplexType>,
+    simple_types: HashMap<QName, SimpleType>,
+    /// Target namespaces of the loaded schemas
+    namespaces: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ElementDecl {
+    name: QName,
+    ty: TypeRef,
+}
+
+#[derive(Debug, Clone)]
+enum TypeRef {
+    Named(QName),
+    Complex(Box<ComplexType>),
+    Simple(Box<SimpleType>),
+}
+
+#[derive(Debug, Clone, Default)]
+struct ComplexType {
+    /// Type extended through `complexContent`
+    base: Option<QName>,
+    mixed: bool,
+    content: Content,
+    attributes: Vec<AttributeDecl>,
+    any_attribute: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+    /// Child elements matching a sequence of items; no items means empty content
+    Elements(Vec<Item>),
+    /// Text of a simple type
+    Simple(TypeRef),
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Elements(Vec::new())
+    }
+}
+
+/// A particle of a sequence
+#[derive(Debug, Clone)]
+struct Item {
+    term: Term,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Element(ElementDecl),
+    /// Reference to a global element declaration
+    Ref(QName),
+    Any(Wildcard),
+    /// One of the alternatives, each an element, reference or wildcard
+    Choice(Vec<Item>),
+    /// Only while loading: a group not yet reduced to items
+    Sequence(Vec<Item>),
+}
+
+#[derive(Debug, Clone)]
+struct Wildcard {
+    namespaces: Namespaces,
+    process: Process,
+}
+
+#[derive(Debug, Clone)]
+enum Namespaces {
+    Any,
+    /// Any namespace other than the target namespace, unqualified names excluded
+    Other(String),
+    /// The listed namespaces, `""` standing for unqualified names
+    List(Vec<String>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Process {
+    Strict,
+    Lax,
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+struct AttributeDecl {
+    /// Name as written in documents, e.g. `Critical` or `xml:lang`
+    name: String,
+    /// Value type, unless it belongs to a namespace without a loaded schema
+    ty: Option<TypeRef>,
+    required: bool,
+}
+
+/// A simple type derived by restriction
+#[derive(Debug, Clone)]
+struct SimpleType {
+    base: TypeRef,
+    facets: Facets,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Facets {
+    length: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    patterns: Vec<Regex>,
+    enumeration: Vec<String>,
+}
+
+/// Built-in types of XML Schema the loaded schemas may use
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Builtin {
+    AnySimpleType,
+    String,
+    NormalizedString,
+    Token,
+    AnyUri,
+    Id,
+    Boolean,
+    DateTime,
+    Integer,
+    PositiveInteger,
+    NonNegativeInteger,
+    Base64Binary,
+}
+
+impl Builtin {
+    fn named(name: &str) -> Option<Builtin> {
+        Some(match name {
+            "anySimpleType" => Builtin::AnySimpleType,
+            "string" => Builtin::String,
+            "normalizedString" => Builtin::NormalizedString,
+            "token" => Builtin::Token,
+            "anyURI" => Builtin::AnyUri,
+            "ID" => Builtin::Id,
+            "boolean" => Builtin::Boolean,
+            "dateTime" => Builtin::DateTime,
+            "integer" => Builtin::Integer,
+            "positiveInteger" => Builtin::PositiveInteger,
+            "nonNegativeInteger" => Builtin::NonNegativeInteger,
+            "base64Binary" => Builtin::Base64Binary,
+            _ => return None,
+        })
+    }
+
+    /// Applies the `whiteSpace` facet of the type
+    fn normalize(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Builtin::String | Builtin::AnySimpleType => Cow::Borrowed(text),
+            Builtin::NormalizedString => Cow::Owned(text.replace(['\t', '\n', '\r'], " ")),
+            _ => Cow::Owned(text.split_whitespace().collect::<Vec<_>>().join(" ")),
+        }
+    }
+
+    /// Checks the lexical form of a normalized value
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            Builtin::AnySimpleType | Builtin::String | Builtin::NormalizedString | Builtin::Token => true,
+            Builtin::AnyUri => !value.contains(' '),
+            Builtin::Id => !value.is_empty() && !value.contains([' ', ':']),
+            Builtin::Boolean => matches!(value, "true" | "false" | "1" | "0"),
+            Builtin::DateTime => is_date_time(value),
+            // At most one leading sign, then digits only
+            Builtin::Integer => is_digits(value.strip_prefix(['+', '-']).unwrap_or(value)),
+            Builtin::PositiveInteger => {
+                let digits = value.strip_prefix('+').unwrap_or(value);
+                is_digits(digits) && digits.bytes().any(|b| b != b'0')
+            }
+            Builtin::NonNegativeInteger => is_digits(value.strip_prefix('+').unwrap_or(value)),
+            Builtin::Base64Binary => {
+                let compact: String = value.chars().filter(|c| *c != ' ').collect();
+                base64::engine::general_purpose::STANDARD.decode(compact).is_ok()
+            }
+        }
+    }
+}
+
+/// What a type reference resolves to
+enum Resolved<'a> {
+    Complex(Cow<'a, ComplexType>),
+    Simple(&'a TypeRef),
+    /// `xsd:anyType`, or a type of a namespace without a loaded schema
+    Unchecked,
+}
+
+/// Per-schema state needed to resolve names while loading
+struct Loader<'a> {
+    target: String,
+    prefixes: &'a Namespace,
+    qualified: bool,
+}
+
+impl Loader<'_> {
+    fn qname(&self, value: &str) -> Result<QName, String> {
+        let (prefix, local) = value.split_once(':').unwrap_or(("", value));
+        let namespace = self
+            .prefixes
+            .get(prefix)
+            .ok_or_else(|| format!("undeclared prefix in '{}'", value))?;
+        Ok((namespace.to_string(), local.to_string()))
+    }
+
+    fn attr<'e>(&self, element: &'e Element, name: &str) -> Result<&'e str, String> {
+        element
+            .attr(name)
+            .ok_or_else(|| format!("<xsd:{}> at line {} has no '{}' attribute", element.name, element.line, name))
+    }
+
+    fn element(&self, element: &Element, global: bool) -> Result<ElementDecl, String> {
+        let namespace = if global || self.qualified { self.target.clone() } else { String::new() };
+        let name = (namespace, self.attr(element, "name")?.to_string());
+        let ty = match element.attr("type") {
+            Some(ty) => TypeRef::Named(self.qname(ty)?),
+            None => match schema_children(element).next() {
+                Some(child) if child.name == "complexType" => TypeRef::Complex(Box::new(self.complex_type(child)?)),
+                Some(child) if child.name == "simpleType" => TypeRef::Simple(Box::new(self.simple_type(child)?)),
+                _ => TypeRef::Named((XSD_NS.to_string(), "anyType".to_string())),
+            },
+        };
+        Ok(ElementDecl { name, ty })
+    }
+
+    fn complex_type(&self, element: &Element) -> Result<ComplexType, String> {
+        let mut ty = ComplexType {
+            mixed: element.attr("mixed") == Some("true"),
+            ..ComplexType::default()
+        };
+        for child in schema_children(element) {
+            match child.name.as_str() {
+                "sequence" | "choice" => ty.content = Content::Elements(reduce(self.particle(child)?)?),
+                "attribute" | "anyAttribute" => self.attribute(child, &mut ty)?,
+                "simpleContent" => {
+                    let derivation = self.derivation(child)?;
+                    let base = TypeRef::Named(self.qname(self.attr(derivation, "base")?)?);
+                    ty.content = Content::Simple(match derivation.name.as_str() {
+                        "extension" => base,
+                        _ => TypeRef::Simple(Box::new(SimpleType { base, facets: self.facets(derivation)? })),
+                    });
+                    for attribute in schema_children(derivation) {
+                        self.attribute(attribute, &mut ty)?;
+                    }
+                }
+                "complexContent" => {
+                    let derivation = self.derivation(child)?;
+                    if derivation.name != "extension" {
+                        return Err(format!("unsupported complex type restriction at line {}", derivation.line));
+                    }
+                    ty.base = Some(self.qname(self.attr(derivation, "base")?)?);
+                    for part in schema_children(derivation) {
+                        match part.name.as_str() {
+                            "sequence" | "choice" => ty.content = Content::Elements(reduce(self.particle(part)?)?),
+                            _ => self.attribute(part, &mut ty)?,
+                        }
+                    }
+                }
+                other => return Err(format!("unsupported <xsd:{}> at line {}", other, child.line)),
+            }
+        }
+        Ok(ty)
+    }
+
+    fn derivation<'e>(&self, element: &'e Element) -> Result<&'e Element, String> {
+        schema_children(element)
+            .find(|child| child.name == "extension" || child.name == "restriction")
+            .ok_or_else(|| format!("<xsd:{}> at line {} derives from nothing", element.name, element.line))
+    }
+
+    fn attribute(&self, element: &Element, ty: &mut ComplexType) -> Result<(), String> {
+        match element.name.as_str() {
+            "anyAttribute" => ty.any_attribute = true,
+            "attribute" => {
+                let (name, value_type) = match element.attr("ref") {
+                    // Attributes of the xml: namespace, e.g. xml:lang, come without a loaded schema
+                    Some(reference) => match self.qname(reference)? {
+                        (namespace, local) if namespace == XML_NS => (format!("xml:{}", local), None),
+                        _ => return Err(format!("unsupported attribute reference '{}'", reference)),
+                    },
+                    None => {
+                        let value_type = match (element.attr("type"), schema_children(element).next()) {
+                            (Some(name), _) => TypeRef::Named(self.qname(name)?),
+                            (None, Some(simple)) => TypeRef::Simple(Box::new(self.simple_type(simple)?)),
+                            (None, None) => TypeRef::Named((XSD_NS.to_string(), "anySimpleType".to_string())),
+                        };
+                        (self.attr(element, "name")?.to_string(), Some(value_type))
+                    }
+                };
+                ty.attributes.push(AttributeDecl {
+                    name,
+                    ty: value_type,
+                    required: element.attr("use") == Some("required"),
+                });
+            }
+            other => return Err(format!("unsupported <xsd:{}> at line {}", other, element.line)),
+        }
+        Ok(())
+    }
+
+    fn simple_type(&self, element: &Element) -> Result<SimpleType, String> {
+        let restriction = schema_children(element)
+            .find(|child| child.name == "restriction")
+            .ok_or_else(|| format!("unsupported simple type at line {}", element.line))?;
+        Ok(SimpleType {
+            base: TypeRef::Named(self.qname(self.attr(restriction, "base")?)?),
+            facets: self.facets(restriction)?,
+        })
+    }
+
+    fn facets(&self, restriction: &Element) -> Result<Facets, String> {
+        let mut facets = Facets::default();
+        for facet in schema_children(restriction) {
+            let value = self.attr(facet, "value")?;
+            let length = || value.parse::<usize>().map_err(|e| format!("invalid {} '{}': {}", facet.name, value, e));
+            match facet.name.as_str() {
+                "length" => facets.length = Some(length()?),
+                "minLength" => facets.min_length = Some(length()?),
+                "maxLength" => facets.max_length = Some(length()?),
+                // Patterns match the whole value
+                "pattern" => facets
+                    .patterns
+                    .push(Regex::new(&format!("^(?:{})$", value)).map_err(|e| e.to_string())?),
+                "enumeration" => facets.enumeration.push(value.to_string()),
+                other => return Err(format!("unsupported facet '{}' at line {}", other, facet.line)),
+            }
+        }
+        Ok(facets)
+    }
+
+    /// The particles of a sequence or choice, in order
+    fn particles(&self, element: &Element) -> Result<Vec<Item>, String> {
+        schema_children(element).map(|child| self.particle(child)).collect()
+    }
+
+    fn particle(&self, element: &Element) -> Result<Item, String> {
+        let min = match element.attr("minOccurs") {
+            Some(min) => min.parse().map_err(|_| format!("invalid minOccurs '{}'", min))?,
+            None => 1,
+        };
+        let max = match element.attr("maxOccurs") {
+            Some("unbounded") => UNBOUNDED,
+            Some(max) => max.parse().map_err(|_| format!("invalid maxOccurs '{}'", max))?,
+            None => 1,
+        };
+        let term = match element.name.as_str() {
+            "element" => match element.attr("ref") {
+                Some(reference) => Term::Ref(self.qname(reference)?),
+                None => Term::Element(self.element(element, false)?),
+            },
+            "any" => Term::Any(Wildcard {
+                namespaces: match element.attr("namespace").unwrap_or("##any") {
+                    "##any" => Namespaces::Any,
+                    "##other" => Namespaces::Other(self.target.clone()),
+                    list => Namespaces::List(
+                        list.split_whitespace()
+                            .map(|namespace| match namespace {
+                                "##targetNamespace" => self.target.clone(),
+                                "##local" => String::new(),
+                                uri => uri.to_string(),
+                            })
+                            .collect(),
+                    ),
+                },
+                process: match element.attr("processContents").unwrap_or("strict") {
+                    "lax" => Process::Lax,
+                    "skip" => Process::Skip,
+                    _ => Process::Strict,
+                },
+            }),
+            "sequence" => Term::Sequence(self.particles(element)?),
+            "choice" => Term::Choice(self.particles(element)?),
+            other => return Err(format!("unsupported particle <xsd:{}> at line {}", other, element.line)),
+        };
+        Ok(Item { term, min, max })
+    }
+}
+
+/// Children of a schema element, without annotations
+fn schema_children(element: &Element) -> impl Iterator<Item = &Element> {
+    element
+        .children
+        .iter()
+        .filter(|child| child.in_namespace(XSD_NS) && child.name != "annotation")
+}
+
+/// Reduces a particle to a flat sequence of items. Nested groups are merged
+/// into their parent when that keeps the same language, and rejected
+/// otherwise.
+fn reduce(item: Item) -> Result<Vec<Item>, String> {
+    let Item { term, min, max } = item;
+    match term {
+        Term::Sequence(mut items) | Term::Choice(mut items) if items.len() == 1 => {
+            let inner = items.remove(0);
+            if (min, max) != (1, 1) && (inner.min, inner.max) != (1, 1) {
+                return Err("unsupported repeated group of a repeated particle".to_string());
+            }
+            reduce(Item {
+                min: min * inner.min,
+                max: max.saturating_mul(inner.max),
+                term: inner.term,
+            })
+        }
+        Term::Sequence(items) if (min, max) == (1, 1) => {
+            items.into_iter().map(reduce).try_fold(Vec::new(), |mut all, reduced| {
+                all.extend(reduced?);
+                Ok(all)
+            })
+        }
+        Term::Sequence(_) => Err("unsupported repeated sequence of several particles".to_string()),
+        Term::Choice(alternatives) => {
+            let alternatives = alternatives
+                .into_iter()
+                .map(|alternative| match reduce(alternative)?.as_slice() {
+                    [single] if !matches!(single.term, Term::Choice(_) | Term::Sequence(_)) => Ok(single.clone()),
+                    _ => Err("unsupported choice among groups".to_string()),
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(vec![Item { term: Term::Choice(alternatives), min, max }])
+        }
+        term => Ok(vec![Item { term, min, max }]),
+    }
+}
+
+impl SchemaSet {
+    /// Loads schemas from their text. References between them are resolved,
+    /// and every name referring to a loaded namespace must be declared.
+    pub fn load(sources: &[&str]) -> Result<SchemaSet, String> {
+        let mut set = SchemaSet::default();
+        let limits = ParseLimits::default();
+
+        for source in sources {
+            let root = dom::parse_document(source, &limits).map_err(|e| e.to_string())?;
+            if !(root.in_namespace(XSD_NS) && root.name == "schema") {
+                return Err(format!("<{}> is not an XML Schema", root.name));
+            }
+            // Prefixes are declared on the schema element
+            let prefixes = limits
+                .event_reader(source)
+                .find_map(|event| match event {
+                    Ok(XmlEvent::StartElement { namespace, .. }) => Some(namespace),
+                    _ => None,
+                })
+                .unwrap_or_else(Namespace::empty);
+            let loader = Loader {
+                target: root.attr("targetNamespace").unwrap_or_default().to_string(),
+                prefixes: &prefixes,
+                qualified: root.attr("elementFormDefault") == Some("qualified"),
+            };
+
+            for child in schema_children(&root) {
+                match child.name.as_str() {
+                    "import" => {}
+                    "element" => {
+                        let declaration = loader.element(child, true)?;
+                        set.elements.insert(declaration.name.clone(), declaration);
+                    }
+                    "complexType" => {
+                        let name = (loader.target.clone(), loader.attr(child, "name")?.to_string());
+                        set.complex_types.insert(name, loader.complex_type(child)?);
+                    }
+                    "simpleType" => {
+                        let name = (loader.target.clone(), loader.attr(child, "name")?.to_string());
+                        set.simple_types.insert(name, loader.simple_type(child)?);
+                    }
+                    other => return Err(format!("unsupported top-level <xsd:{}> at line {}", other, child.line)),
+                }
+            }
+            set.namespaces.push(loader.target);
+        }
+
+        set.check_references()?;
+        Ok(set)
+    }
+
+    fn check_references(&self) -> Result<(), String> {
+        let declared = |name: &QName, table: &dyn Fn(&QName) -> bool| {
+            if table(name) || !self.namespaces.contains(&name.0) && name.0 != XSD_NS {
+                Ok(())
+            } else {
+                Err(format!("{{{}}}{} is not declared", name.0, name.1))
+            }
+        };
+        let check_type = |ty: &TypeRef| -> Result<(), String> {
+            match ty {
+                TypeRef::Named(name) => declared(name, &|name| {
+                    self.complex_types.contains_key(name)
+                        || self.simple_types.contains_key(name)
+                        || name.0 == XSD_NS && (name.1 == "anyType" || Builtin::named(&name.1).is_some())
+                }),
+                _ => Ok(()),
+            }
+        };
+
+        let mut types: Vec<&TypeRef> = self.elements.values().map(|declaration| &declaration.ty).collect();
+        let mut complex: Vec<&ComplexType> = self.complex_types.values().collect();
+        let mut simple: Vec<&SimpleType> = self.simple_types.values().collect();
+        while let Some(ty) = types.pop() {
+            check_type(ty)?;
+            match ty {
+                TypeRef::Complex(ty) => complex.push(ty),
+                TypeRef::Simple(ty) => simple.push(ty),
+                TypeRef::Named(_) => {}
+            }
+            while let Some(ty) = simple.pop() {
+                check_type(&ty.base)?;
+                types.push(&ty.base);
+            }
+            while let Some(ty) = complex.pop() {
+                if let Some(base) = &ty.base {
+                    declared(base, &|name| self.complex_types.contains_key(name))?;
+                }
+                if let Content::Simple(base) = &ty.content {
+                    types.push(base);
+                }
+                types.extend(ty.attributes.iter().filter_map(|attribute| attribute.ty.as_ref()));
+                let Content::Elements(items) = &ty.content else { continue };
+                let mut pending: Vec<&Item> = items.iter().collect();
+                while let Some(item) = pending.pop() {
+                    match &item.term {
+                        Term::Element(declaration) => types.push(&declaration.ty),
+                        Term::Ref(name) => declared(name, &|name| self.elements.contains_key(name))?,
+                        Term::Choice(alternatives) | Term::Sequence(alternatives) => pending.extend(alternatives),
+                        Term::Any(_) => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates `root` against its global element declaration. `aliases`
+    /// map namespaces of the document to those of the loaded schemas.
+    pub fn validate(&self, root: &Element, aliases: &[(&str, &str)]) -> Vec<SchemaViolation> {
+        let mut validation = Validation {
+            set: self,
+            aliases,
+            out: Vec::new(),
+        };
+        let name = validation.name_of(root);
+        match self.elements.get(&name) {
+            Some(declaration) => validation.element(root, &declaration.ty),
+            None => validation.report(root, "has no global declaration in the schemas".to_string()),
+        }
+        validation.out
+    }
+
+    fn resolve<'a>(&'a self, ty: &'a TypeRef) -> Resolved<'a> {
+        match ty {
+            TypeRef::Complex(complex) => Resolved::Complex(self.with_base(complex)),
+            TypeRef::Simple(_) => Resolved::Simple(ty),
+            TypeRef::Named(name) if name.0 == XSD_NS && name.1 == "anyType" => Resolved::Unchecked,
+            TypeRef::Named(name) if name.0 == XSD_NS => Resolved::Simple(ty),
+            TypeRef::Named(name) => match self.complex_types.get(name) {
+                Some(complex) => Resolved::Complex(self.with_base(complex)),
+                None if self.simple_types.contains_key(name) => Resolved::Simple(ty),
+                None => Resolved::Unchecked,
+            },
+        }
+    }
+
+    /// The complex type with the content and attributes of the type it extends
+    fn with_base<'a>(&'a self, ty: &'a ComplexType) -> Cow<'a, ComplexType> {
+        let Some(base) = ty.base.as_ref().and_then(|base| self.complex_types.get(base)) else {
+            return Cow::Borrowed(ty);
+        };
+        let base = self.with_base(base);
+        let (Content::Elements(inherited), Content::Elements(own)) = (&base.content, &ty.content) else {
+            return Cow::Borrowed(ty);
+        };
+        Cow::Owned(ComplexType {
+            base: None,
+            mixed: ty.mixed || base.mixed,
+            content: Content::Elements(inherited.iter().chain(own).cloned().collect()),
+            attributes: base.attributes.iter().chain(&ty.attributes).cloned().collect(),
+            any_attribute: ty.any_attribute || base.any_attribute,
+        })
+    }
+
+    /// Checks `text` against a simple type, failing with the name of the type
+    /// it does not match
+    fn check_value(&self, ty: &TypeRef, text: &str) -> Result<(), String> {
+        let (builtin, restrictions) = self.simple_chain(ty);
+        let value = builtin.normalize(text);
+        if !builtin.accepts(&value) {
+            return Err(format!("xsd:{}", type_name(builtin)));
+        }
+        for (name, facets) in restrictions {
+            let length = value.chars().count();
+            let valid = facets.length.is_none_or(|expected| length == expected)
+                && facets.min_length.is_none_or(|min| length >= min)
+                && facets.max_length.is_none_or(|max| length <= max)
+                && facets.patterns.iter().all(|pattern| pattern.is_match(&value))
+                && (facets.enumeration.is_empty() || facets.enumeration.iter().any(|option| *option == value));
+            if !valid {
+                return Err(name.unwrap_or_else(|| "anonymous type".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The built-in type a simple type derives from, with the facets of every
+    /// restriction on the way
+    fn simple_chain<'a>(&'a self, mut ty: &'a TypeRef) -> (Builtin, Vec<(Option<String>, &'a Facets)>) {
+        let mut restrictions = Vec::new();
+        loop {
+            let (name, simple) = match ty {
+                TypeRef::Simple(simple) => (None, &**simple),
+                TypeRef::Named(name) if name.0 == XSD_NS => {
+                    return (Builtin::named(&name.1).unwrap_or(Builtin::AnySimpleType), restrictions);
+                }
+                TypeRef::Named(name) => match self.simple_types.get(name) {
+                    Some(simple) => (Some(name.1.clone()), simple),
+                    None => return (Builtin::AnySimpleType, restrictions),
+                },
+                TypeRef::Complex(_) => return (Builtin::AnySimpleType, restrictions),
+            };
+            restrictions.push((name, &simple.facets));
+            ty = &simple.base;
+        }
+    }
+}
+
+fn type_name(builtin: Builtin) -> &'static str {
+    match builtin {
+        Builtin::AnySimpleType => "anySimpleType",
+        Builtin::String => "string",
+        Builtin::NormalizedString => "normalizedString",
+        Builtin::Token => "token",
+        Builtin::AnyUri => "anyURI",
+        Builtin::Id => "ID",
+        Builtin::Boolean => "boolean",
+        Builtin::DateTime => "dateTime",
+        Builtin::Integer => "integer",
+        Builtin::PositiveInteger => "positiveInteger",
+        Builtin::NonNegativeInteger => "nonNegativeInteger",
+        Builtin::Base64Binary => "base64Binary",
+    }
+}
+
+/// One validation run over a document
+struct Validation<'a> {
+    set: &'a SchemaSet,
+    aliases: &'a [(&'a str, &'a str)],
+    out: Vec<SchemaViolation>,
+}
+
+impl Validation<'_> {
+    fn report(&mut self, element: &Element, message: String) {
+        self.out.push(SchemaViolation {
+            line: element.line,
+            column: element.column,
+            element: element.name.clone(),
+            message,
+        });
+    }
+
+    /// Name of `element` in the namespaces of the loaded schemas
+    fn name_of(&self, element: &Element) -> QName {
+        let namespace = element.namespace.as_deref().unwrap_or_default();
+        let namespace = self
+            .aliases
+            .iter()
+            .find(|(alias, _)| *alias == namespace)
+            .map_or(namespace, |(_, schema)| schema);
+        (namespace.to_string(), element.name.clone())
+    }
+
+    fn matches(&self, element: &Element, item: &Item) -> bool {
+        match &item.term {
+            Term::Element(declaration) => self.name_of(element) == declaration.name,
+            Term::Ref(name) => self.name_of(element) == *name,
+            Term::Any(wildcard) => {
+                let (namespace, _) = self.name_of(element);
+                match &wildcard.namespaces {
+                    Namespaces::Any => true,
+                    Namespaces::Other(target) => namespace != *target && !namespace.is_empty(),
+                    Namespaces::List(namespaces) => namespaces.contains(&namespace),
+                }
+            }
+            Term::Choice(alternatives) | Term::Sequence(alternatives) => {
+                alternatives.iter().any(|alternative| self.matches(element, alternative))
+            }
+        }
+    }
+
+    fn element(&mut self, element: &Element, ty: &TypeRef) {
+        match self.set.resolve(ty) {
+            Resolved::Unchecked => {}
+            Resolved::Simple(ty) => {
+                self.attributes(element, &[], false);
+                self.simple_content(element, ty);
+            }
+            Resolved::Complex(complex) => {
+                self.attributes(element, &complex.attributes, complex.any_attribute);
+                match &complex.content {
+                    Content::Simple(ty) => self.simple_content(element, ty),
+                    Content::Elements(items) if items.is_empty() => {
+                        if !element.children.is_empty() || !complex.mixed && !element.text().is_empty() {
+                            self.report(element, "must be empty".to_string());
+                        }
+                    }
+                    Content::Elements(items) => {
+                        if !complex.mixed && !element.text().is_empty() {
+                            self.report(element, "must not contain text".to_string());
+                        }
+                        self.sequence(element, items);
+                    }
+                }
+            }
+        }
+    }
+
+    fn attributes(&mut self, element: &Element, declarations: &[AttributeDecl], any_attribute: bool) {
+        for declaration in declarations {
+            match element.attr(&declaration.name) {
+                None if declaration.required => {
+                    self.report(element, format!("is missing required attribute '{}'", declaration.name));
+                }
+                Some(value) => {
+                    if let Some(Err(ty)) = declaration.ty.as_ref().map(|ty| self.set.check_value(ty, value)) {
+                        let message =
+                            format!("has invalid {} value '{}' in attribute '{}'", ty, value, declaration.name);
+                        self.report(element, message);
+                    }
+                }
+                None => {}
+            }
+        }
+        // Prefixed attributes, such as xsi:schemaLocation, belong to other vocabularies
+        for (name, _) in &element.attributes {
+            let declared = declarations.iter().any(|declaration| declaration.name == *name);
+            if !any_attribute && !name.contains(':') && !declared {
+                self.report(element, format!("has undeclared attribute '{}'", name));
+            }
+        }
+    }
+
+    fn simple_content(&mut self, element: &Element, ty: &TypeRef) {
+        if let Some(child) = element.children.first() {
+            self.report(child, format!("is not allowed inside <{}>", element.name));
+        } else if let Err(name) = self.set.check_value(ty, &element.text) {
+            self.report(element, format!("has invalid {} value '{}'", name, element.text()));
+        }
+    }
+
+    /// Validates a child matched by `term`
+    fn term(&mut self, child: &Element, term: &Term) {
+        match term {
+            Term::Element(declaration) => self.element(child, &declaration.ty),
+            Term::Ref(name) => {
+                if let Some(declaration) = self.set.elements.get(name) {
+                    self.element(child, &declaration.ty);
+                }
+            }
+            Term::Any(wildcard) if wildcard.process != Process::Skip => {
+                let name = self.name_of(child);
+                match self.set.elements.get(&name) {
+                    Some(declaration) => self.element(child, &declaration.ty),
+                    None if wildcard.process == Process::Strict && self.set.namespaces.contains(&name.0) => {
+                        self.report(child, "has no declaration in the schemas".to_string());
+                    }
+                    None => {}
+                }
+            }
+            Term::Any(_) => {}
+            Term::Choice(alternatives) | Term::Sequence(alternatives) => {
+                if let Some(alternative) = alternatives.iter().find(|alternative| self.matches(child, alternative)) {
+                    self.term(child, &alternative.term);
+                }
+            }
+        }
+    }
+
+    fn sequence(&mut self, element: &Element, items: &[Item]) {
+        let mut current = 0;
+        let mut count = 0;
+        // Alternative of the current choice and how often it repeated
+        let mut run: Option<(usize, usize)> = None;
+
+        for child in &element.children {
+            if let (Some(Term::Choice(alternatives)), Some((alternative, repeated))) =
+                (items.get(current).map(|item| &item.term), run)
+            {
+                let same = &alternatives[alternative];
+                if self.matches(child, same) && repeated < same.max {
+                    run = Some((alternative, repeated + 1));
+                    self.term(child, &same.term);
+                    continue;
+                }
+            }
+
+            let next = (current..items.len())
+                .find(|&index| self.matches(child, &items[index]) && (index != current || count < items[index].max));
+
+            let Some(index) = next else {
+                let message = if items.get(current).is_some_and(|item| self.matches(child, item)) {
+                    format!("occurs more than {} time(s) inside <{}>", items[current].max, element.name)
+                } else if items[..current].iter().any(|item| self.matches(child, item)) {
+                    format!("is out of order inside <{}>", element.name)
+                } else {
+                    format!("is not allowed inside <{}>", element.name)
+                };
+                self.report(child, message);
+                continue;
+            };
+
+            self.close_run(child, items.get(current), run);
+            if index > current {
+                for (offset, skipped) in items[current..index].iter().enumerate() {
+                    let seen = if offset == 0 { count } else { 0 };
+                    if seen < skipped.min {
+                        self.report(child, format!("is preceded by a missing {}", describe(skipped)));
+                    }
+                }
+                current = index;
+                count = 0;
+            }
+
+            count += 1;
+            run = None;
+            match &items[current].term {
+                Term::Choice(alternatives) => {
+                    let alternative = alternatives
+                        .iter()
+                        .position(|alternative| self.matches(child, alternative))
+                        .expect("the choice matched");
+                    run = Some((alternative, 1));
+                    self.term(child, &alternatives[alternative].term);
+                }
+                term => self.term(child, term),
+            }
+        }
+
+        self.close_run(element, items.get(current), run);
+        for (offset, remaining) in items[current..].iter().enumerate() {
+            let seen = if offset == 0 { count } else { 0 };
+            if seen < remaining.min {
+                self.report(element, format!("is missing required element {}", describe(remaining)));
+            }
+        }
+    }
+
+    /// Reports an alternative of a choice that repeated fewer times than required
+    fn close_run(&mut self, at: &Element, item: Option<&Item>, run: Option<(usize, usize)>) {
+        if let (Some(Item { term: Term::Choice(alternatives), .. }), Some((alternative, repeated))) = (item, run) {
+            let alternative = &alternatives[alternative];
+            if repeated < alternative.min {
+                self.report(at, format!("needs at least {} {}", alternative.min, describe(alternative)));
+            }
+        }
+    }
+}
+
+/// Names the elements an item stands for, e.g. `<TSLPolicy> or <TSLLegalNotice>`
+fn describe(item: &Item) -> String {
+    match &item.term {
+        Term::Element(declaration) => format!("<{}>", declaration.name.1),
+        Term::Ref(name) => format!("<{}>", name.1),
+        Term::Any(_) => "element".to_string(),
+        Term::Choice(alternatives) | Term::Sequence(alternatives) => {
+            alternatives.iter().map(describe).collect::<Vec<_>>().join(" or ")
+        }
+    }
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Checks the lexical form of an `xsd:dateTime`, e.g. `2024-05-01T00:00:00Z`
+pub(crate) fn is_date_time(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let digits = |range: std::ops::Range<usize>| {
+        bytes.get(range).map(|b| b.iter().all(u8::is_ascii_digit)).unwrap_or(false)
+    };
+
+    if bytes.len() < 19
+        || !digits(0..4)
+        || bytes[4] != b'-'
+        || !digits(5..7)
+        || bytes[7] != b'-'
+        || !digits(8..10)
+        || bytes[10] != b'T'
+        || !digits(11..13)
+        || bytes[13] != b':'
+        || !digits(14..16)
+        || bytes[16] != b':'
+        || !digits(17..19)
+    {
+        return false;
+    }
+
+    let mut rest = &text[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let length = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if length == 0 {
+            return false;
+        }
+        rest = &fraction[length..];
+    }
+
+    match rest {
+        "" | "Z" => true,
+        zone => {
+            let zone = zone.as_bytes();
+            zone.len() == 6
+                && (zone[0] == b'+' || zone[0] == b'-')
+                && zone[1..3].iter().all(u8::is_ascii_digit)
+                && zone[3] == b':'
+                && zone[4..6].iter().all(u8::is_ascii_digit)
+        }
+    }
+}
@@ -0,0 +1,91 @@
+#!/bin/sh
+# Generates the test PKI used by src/tests.rs. Keys are test-only.
+# Existing certificates are kept so that fixtures embedding them stay valid;
+# delete a certificate to issue it again.
+set -e
+cd "$(dirname "$0")"
+
+export CA_WORKDIR="$(mktemp -d)"
+trap 'rm -rf "$CA_WORKDIR"' EXIT
+: > "$CA_WORKDIR/index.txt"
+
+START=20240101000000Z
+END=20440101000000Z
+
+# issue <name> <serial> <subject> <extensions> [<issuer>]
+issue() {
+    [ -f "$1.pem" ] && return 0
+    echo "$2" > "$CA_WORKDIR/serial"
+    name=$1
+    shift
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$name.key"
+    openssl req -new -config openssl.cnf -key "$name.key" -subj "$2" -out "$CA_WORKDIR/$name.csr"
+    if [ -z "$4" ]; then
+        openssl ca -batch -config openssl.cnf -selfsign -keyfile "$name.key" \
+            -in "$CA_WORKDIR/$name.csr" -out "$name.pem" -extensions "$3" \
+            -startdate "$START" -enddate "$END" -notext 2>/dev/null
+    else
+        openssl ca -batch -config openssl.cnf -cert "$4.pem" -keyfile "$4.key" \
+            -in "$CA_WORKDIR/$name.csr" -out "$name.pem" -extensions "$3" \
+            -startdate "$START" -enddate "$END" -notext 2>/dev/null
+    fi
+}
+
+issue qwac_root 1000 "/C=DE/O=Example Trust GmbH/CN=Example QWAC Root CA" root_ca
+issue qseal_root 1001 "/C=DE/O=Example Trust GmbH/CN=Example QSealC Root CA" root_ca
+issue qesig_root 1002 "/C=DE/O=Beispiel Signatur AG/CN=Example QESig Root CA" root_ca
+issue qtst_tsu 1003 "/C=DE/O=Beispiel Signatur AG/CN=Example QTST Unit 1" tsa
+issue national_ca 1004 "/C=DE/O=Beispiel Signatur AG/CN=Example National CA" root_ca
+issue qwac_issuing_ca 1005 "/C=DE/O=Example Trust GmbH/CN=Example QWAC Issuing CA" sub_ca qwac_root
+issue qwac_leaf 1006 "/C=DE/O=Example Bank AG/CN=www.example-bank.de" qwac_leaf qwac_issuing_ca
+issue qseal_leaf 1007 "/C=DE/O=Example Bank AG/CN=Example Bank Seal" qseal_leaf qseal_root
+issue qesig_leaf 1008 "/C=DE/CN=Erika Mustermann" qesig_leaf qesig_root
+issue psd2_qwac_leaf 1009 "/C=DE/O=Example Payments GmbH/organizationIdentifier=PSDDE-BAFIN-123456/CN=api.example-payments.de" psd2_qwac_leaf qwac_issuing_ca
+issue tls_server 1010 "/C=DE/O=Example Bank AG/CN=www.example-bank.de" tls_server
+issue two_qwac 1011 "/C=DE/O=Example Bank AG/CN=www.example-bank.de" two_qwac qwac_issuing_ca
+issue revoked_ca 1012 "/C=DE/O=Example Trust GmbH/CN=Example QSealC Issuing CA 2" sub_ca_crl qseal_root
+issue qseal_issuing_ca 1013 "/C=DE/O=Example Trust GmbH/CN=Example QSealC Issuing CA" sub_ca_ocsp qseal_root
+issue ocsp_responder 1014 "/C=DE/O=Example Trust GmbH/CN=Example QWAC Root OCSP Responder" ocsp_signing qwac_root
+issue forged_ca 1015 "/C=DE/O=Example Trust GmbH/CN=Example QWAC Issuing CA" forged_ca
+issue forged_leaf 1016 "/C=DE/O=Example Bank AG/CN=www.example-bank.de" qwac_leaf forged_ca
+issue tsu_issued_tsu 1017 "/C=DE/O=Beispiel Signatur AG/CN=Example QTST Unit 2" tsa qtst_tsu
+issue leaf_issued_leaf 1018 "/C=DE/O=Example Bank AG/CN=shop.example-bank.de" qwac_leaf qwac_leaf
+
+# crl <issuer> [<revoked certificate>]: a CRL valid for 20 years, revoking for key compromise
+crl() {
+    [ -f "$1.crl" ] && return 0
+    : > "$CA_WORKDIR/index.txt"
+    if [ -n "$2" ]; then
+        openssl ca -config openssl.cnf -cert "$1.pem" -keyfile "$1.key" -revoke "$2.pem" \
+            -crl_reason keyCompromise 2>/dev/null
+    fi
+    openssl ca -config openssl.cnf -cert "$1.pem" -keyfile "$1.key" -gencrl -crldays 7300 \
+        -out "$CA_WORKDIR/$1.crl" 2>/dev/null
+    openssl crl -in "$CA_WORKDIR/$1.crl" -outform DER -out "$1.crl"
+}
+
+crl qseal_root revoked_ca
+crl qwac_root
+
+# RFC 3161 time-stamp over document.txt, requested with the TSA certificate
+if [ ! -f document.tsr ]; then
+    echo 01 > "$CA_WORKDIR/tsaserial"
+    printf 'Archived contract, signed 2025-06-01\n' > document.txt
+    openssl ts -query -data document.txt -sha256 -cert -out "$CA_WORKDIR/document.tsq"
+    openssl ts -reply -config openssl.cnf -section tsa_config -queryfile "$CA_WORKDIR/document.tsq" \
+        -signer qtst_tsu.pem -inkey qtst_tsu.key -out document.tsr 2>/dev/null
+fi
+
+# The same request answered by a TSU certificate that qtst_tsu, not a CA, issued
+if [ ! -f tsu_issued_tsu.tsr ]; then
+    echo 01 > "$CA_WORKDIR/tsaserial"
+    openssl ts -query -data document.txt -sha256 -cert -out "$CA_WORKDIR/document.tsq"
+    openssl ts -reply -config openssl.cnf -section tsa_config -queryfile "$CA_WORKDIR/document.tsq" \
+        -signer tsu_issued_tsu.pem -inkey tsu_issued_tsu.key -out tsu_issued_tsu.tsr 2>/dev/null
+fi
+
+# Detached CAdES signature over document.txt, sealed with the QSealC
+if [ ! -f document.p7s ]; then
+    openssl cms -sign -binary -cades -md sha256 -in document.txt -signer qseal_leaf.pem -inkey qseal_leaf.key \
+        -outform DER -out document.p7s
+fi